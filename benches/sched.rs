@@ -0,0 +1,86 @@
+#![feature(test)]
+extern crate test;
+
+use mco::coroutine::yield_now;
+use mco::Runtime;
+use test::Bencher;
+
+// some cpu work between two yields
+fn work(n: usize) -> usize {
+    let mut v = 0usize;
+    for i in 0..n {
+        v = test::black_box(v.wrapping_mul(31).wrapping_add(i));
+    }
+    v
+}
+
+fn runtime() -> Runtime {
+    Runtime::builder().workers(4).build().unwrap()
+}
+
+#[bench]
+fn sched_balanced_bench(b: &mut Bencher) {
+    let rt = runtime();
+    b.iter(|| {
+        // every coroutine is spawned from the thread into the global queue
+        let v = (0..400)
+            .map(|_| {
+                rt.spawn(|| {
+                    for _ in 0..10 {
+                        work(1000);
+                        yield_now();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in v {
+            h.join().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn sched_imbalance_bench(b: &mut Bencher) {
+    let rt = runtime();
+    b.iter(|| {
+        // a single coroutine produces all the work, the yielded tasks
+        // pile up in one local queue and the idle workers must steal them
+        rt.block_on(|| {
+            let v = (0..400)
+                .map(|_| {
+                    mco::coroutine::spawn(|| {
+                        for _ in 0..10 {
+                            work(1000);
+                            yield_now();
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for h in v {
+                h.join().unwrap();
+            }
+        });
+    });
+}
+
+#[bench]
+fn sched_skewed_bench(b: &mut Bencher) {
+    let rt = runtime();
+    b.iter(|| {
+        // a few heavy coroutines mixed with a lot of light ones
+        let v = (0..400)
+            .map(|i| {
+                let n = if i % 50 == 0 { 100_000 } else { 100 };
+                rt.spawn(move || {
+                    for _ in 0..10 {
+                        work(n);
+                        yield_now();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in v {
+            h.join().unwrap();
+        }
+    });
+}
@@ -1,8 +1,12 @@
 #![feature(test)]
 extern crate test;
 
+use mco::coroutine;
 use mco::std::sync::WaitGroup;
 use mco::std::time::time::Time;
+use mco::std::time::Timer;
+use mco::{Runtime, TimerKind};
+use std::time::Duration;
 use test::Bencher;
 
 //test bench::single_thread_test ... bench:          44 ns/iter (+/- 1)
@@ -24,3 +28,54 @@ fn mult_thread_test(b: &mut Bencher) {
         let now = Time::now();
     });
 }
+
+// the timers with distinct durations, like the deadlines of many connections
+fn timer_add_stop(b: &mut Bencher, kind: TimerKind) {
+    let rt = Runtime::builder().workers(2).timer(kind).build().unwrap();
+    b.iter(|| {
+        rt.block_on(|| {
+            let timers: Vec<_> = (0..10000)
+                .map(|i| Timer::new(Duration::from_millis(1000 + i * 7)))
+                .collect();
+            timers.iter().for_each(|t| {
+                t.stop();
+            });
+        })
+    });
+}
+
+#[bench]
+fn timer_list_add_stop(b: &mut Bencher) {
+    timer_add_stop(b, TimerKind::List);
+}
+
+#[bench]
+fn timer_wheel_add_stop(b: &mut Bencher) {
+    timer_add_stop(b, TimerKind::Wheel);
+}
+
+// the coroutines sleep for distinct durations and are woken up by the timers
+fn timer_sleep(b: &mut Bencher, kind: TimerKind) {
+    let rt = Runtime::builder().workers(2).timer(kind).build().unwrap();
+    b.iter(|| {
+        let wg = WaitGroup::new();
+        for i in 0..1000 {
+            let wg = wg.clone();
+            rt.spawn(move || {
+                coroutine::sleep(Duration::from_micros(i * 10));
+                drop(wg);
+            });
+        }
+        wg.wait();
+    });
+}
+
+#[bench]
+fn timer_list_sleep(b: &mut Bencher) {
+    timer_sleep(b, TimerKind::List);
+}
+
+#[bench]
+fn timer_wheel_sleep(b: &mut Bencher) {
+    timer_sleep(b, TimerKind::Wheel);
+}
@@ -3,9 +3,8 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 
-use crate::coroutine_impl::CoroutineImpl;
+use crate::coroutine_impl::{co_scheduler, CoroutineImpl};
 use crate::io::cancel::CancelIoImpl;
-use crate::scheduler::get_scheduler;
 use crate::std::sync::AtomicOption;
 use crate::yield_now::{get_co_para, set_co_para};
 use mco_gen::Error;
@@ -106,7 +105,7 @@ impl<T: CancelIo> CancelImpl<T> {
                     .map(|mut co| {
                         // set the cancel result for the coroutine
                         set_co_para(&mut co, io::Error::new(io::ErrorKind::Other, "Canceled"));
-                        get_scheduler().schedule(co);
+                        co_scheduler(&co).schedule(co);
                     })
                     .unwrap_or(());
                 Ok(())
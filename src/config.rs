@@ -1,7 +1,11 @@
 //! `mco` Configuration interface
 //!
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::scheduler::is_scheduler_inited;
+use crate::std::blocking::{DEFAULT_KEEP_ALIVE, DEFAULT_MAX_THREADS};
 
 // default stack size, in usize
 // windows has a minimal size as 0x4a8!!!!
@@ -9,6 +13,34 @@ pub const DEFAULT_STACK_SIZE: usize = 6 * 1024 * 1024;
 
 static WORKERS: AtomicUsize = AtomicUsize::new(0);
 static STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_SIZE);
+static BLOCKING_THREADS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_THREADS);
+// the keep alive of the idle blocking threads in micro seconds
+static BLOCKING_KEEP_ALIVE: AtomicU64 = AtomicU64::new(DEFAULT_KEEP_ALIVE.as_micros() as u64);
+static BLOCKING_QUEUE_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+// the watchdog threshold in micro seconds, 0 means disabled
+static WATCHDOG: AtomicU64 = AtomicU64::new(0);
+// the implementation of the timers, 0 for the list and 1 for the wheel
+static TIMER_KIND: AtomicUsize = AtomicUsize::new(0);
+
+/// The implementation of the timers kept by the timer thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimerKind {
+    /// a binary heap of the lists of the timers with the same duration, it's
+    /// fast when most of the timers share a few durations
+    #[default]
+    List,
+    /// a hierarchical timing wheel for each worker, inserting and cancelling
+    /// a timer is O(1) no matter how the durations spread. the timers are
+    /// fired in the resolution of 1ms
+    Wheel,
+}
+
+// the default runtime only read the config once
+fn warn_started() {
+    if is_scheduler_inited() {
+        warn!("the default runtime is already started, config change is ignored");
+    }
+}
 
 /// `mco` Configuration type
 pub struct Config;
@@ -21,13 +53,17 @@ pub fn config() -> Config {
 /// the config should be called at the program beginning
 ///
 /// successive call would not tack effect for that the scheduler
-/// is already started
+/// is already started, use [`Runtime::builder`] to create another
+/// runtime with different settings
+///
+/// [`Runtime::builder`]: runtime/struct.Runtime.html#method.builder
 impl Config {
     /// set the worker thread number
     ///
     /// the minimum worker thread is 1, if you pass 0 to it, will use internal default
     pub fn set_workers(&self, workers: usize) -> &Self {
         info!("set workers={:?}", workers);
+        warn_started();
         WORKERS.store(workers, Ordering::Relaxed);
         self
     }
@@ -47,8 +83,12 @@ impl Config {
     /// set default coroutine stack size in usize
     ///
     /// if you pass 0 to it, will use internal default
+    ///
+    /// each coroutine owns a dedicated stack of this size, use a smaller one
+    /// when there are a large number of alive coroutines
     pub fn set_stack_size(&self, size: usize) -> &Self {
         info!("set stack size={:?}", size);
+        warn_started();
         STACK_SIZE.store(size, Ordering::Release);
         self
     }
@@ -57,4 +97,89 @@ impl Config {
     pub fn get_stack_size(&self) -> usize {
         STACK_SIZE.load(Ordering::Acquire)
     }
+
+    /// set the max thread number of the default blocking pool
+    ///
+    /// it takes effect only before the first `spawn_blocking`,
+    /// if you pass 0 to it, will use internal default
+    pub fn set_max_blocking_threads(&self, n: usize) -> &Self {
+        info!("set max blocking threads={:?}", n);
+        let n = if n == 0 { DEFAULT_MAX_THREADS } else { n };
+        BLOCKING_THREADS.store(n, Ordering::Relaxed);
+        self
+    }
+
+    /// get the max thread number of the default blocking pool
+    pub fn get_max_blocking_threads(&self) -> usize {
+        BLOCKING_THREADS.load(Ordering::Relaxed)
+    }
+
+    /// set the time an idle thread of the default blocking pool waits for
+    /// new tasks before exiting
+    ///
+    /// it takes effect only before the first `spawn_blocking`
+    pub fn set_blocking_keep_alive(&self, dur: Duration) -> &Self {
+        info!("set blocking keep alive={:?}", dur);
+        BLOCKING_KEEP_ALIVE.store(dur.as_micros() as u64, Ordering::Relaxed);
+        self
+    }
+
+    /// get the keep alive of the idle threads of the default blocking pool
+    pub fn get_blocking_keep_alive(&self) -> Duration {
+        Duration::from_micros(BLOCKING_KEEP_ALIVE.load(Ordering::Relaxed))
+    }
+
+    /// set the max number of tasks of the default blocking pool that wait for
+    /// a free thread, the default is unbounded
+    ///
+    /// it takes effect only before the first `spawn_blocking`, after that
+    /// `spawn_blocking` returns an error when the queue is full
+    pub fn set_blocking_queue_limit(&self, n: usize) -> &Self {
+        info!("set blocking queue limit={:?}", n);
+        BLOCKING_QUEUE_LIMIT.store(n, Ordering::Relaxed);
+        self
+    }
+
+    /// get the queue limit of the default blocking pool
+    pub fn get_blocking_queue_limit(&self) -> usize {
+        BLOCKING_QUEUE_LIMIT.load(Ordering::Relaxed)
+    }
+
+    /// enable the watchdog of the default runtime, see [`Builder::watchdog`]
+    ///
+    /// if you pass 0 to it, the watchdog is disabled
+    ///
+    /// [`Builder::watchdog`]: runtime/struct.Builder.html#method.watchdog
+    pub fn set_watchdog(&self, threshold: Duration) -> &Self {
+        info!("set watchdog={:?}", threshold);
+        warn_started();
+        WATCHDOG.store(threshold.as_micros() as u64, Ordering::Relaxed);
+        self
+    }
+
+    /// get the watchdog threshold of the default runtime, None if it's disabled
+    pub fn get_watchdog(&self) -> Option<Duration> {
+        match WATCHDOG.load(Ordering::Relaxed) {
+            0 => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+
+    /// set the timer implementation of the default runtime, see [`Builder::timer`]
+    ///
+    /// [`Builder::timer`]: runtime/struct.Builder.html#method.timer
+    pub fn set_timer(&self, kind: TimerKind) -> &Self {
+        info!("set timer={:?}", kind);
+        warn_started();
+        TIMER_KIND.store(kind as usize, Ordering::Relaxed);
+        self
+    }
+
+    /// get the timer implementation of the default runtime
+    pub fn get_timer(&self) -> TimerKind {
+        match TIMER_KIND.load(Ordering::Relaxed) {
+            0 => TimerKind::List,
+            _ => TimerKind::Wheel,
+        }
+    }
 }
@@ -3,6 +3,8 @@ pub use crate::cancel::trigger_cancel_panic;
 pub use crate::coroutine_impl::{
     current, is_coroutine, park, park_timeout, spawn, try_current, Builder, Coroutine,
 };
+pub use crate::dump::{CoroutineInfo, CoroutineState, WaitReason};
+pub use crate::future::block_on;
 pub use crate::join::JoinHandle;
 pub use crate::park::ParkError;
 pub use crate::scoped::scope;
@@ -18,6 +20,7 @@ pub trait Spawn {
 }
 
 impl Spawn for i32 {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -28,6 +31,7 @@ impl Spawn for i32 {
 }
 
 impl Spawn for &str {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -38,6 +42,7 @@ impl Spawn for &str {
 }
 
 impl Spawn for (&str, i32) {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -51,6 +56,7 @@ impl Spawn for (&str, i32) {
 }
 
 impl Spawn for (String, i32) {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -64,6 +70,7 @@ impl Spawn for (String, i32) {
 }
 
 impl Spawn for String {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -74,6 +81,7 @@ impl Spawn for String {
 }
 
 impl Spawn for &String {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -84,6 +92,7 @@ impl Spawn for &String {
 }
 
 impl Spawn for Builder {
+    #[track_caller]
     fn spawn<F, T>(self, f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
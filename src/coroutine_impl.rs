@@ -1,22 +1,23 @@
-use std::cell::UnsafeCell;
 use std::fmt;
 use std::io;
 use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::thread::ThreadId;
 use std::time::Duration;
 
 use crate::cancel::Cancel;
-use crate::config::{config};
+use crate::dump::{CoroutineInfo, CoroutineState, WaitReason};
 use crate::err;
 use crate::join::{make_join_handle, Join, JoinHandle};
 use crate::local::get_co_local_data;
 use crate::local::CoroutineLocal;
 use crate::park::Park;
-use crate::scheduler::get_scheduler;
+use crate::runtime::Runtime;
+use crate::scheduler::{get_scheduler, set_worker_running, SchedRef, Scheduler};
 use crossbeam::atomic::AtomicCell;
 use once_cell::sync::Lazy;
-use mco_gen::{Generator, Gn, Stack};
+use mco_gen::{Generator, Gn};
 
 /// /////////////////////////////////////////////////////////////////////////////
 /// Coroutine framework types
@@ -41,11 +42,22 @@ impl EventSubscriber {
         let resource = unsafe { &mut *self.resource };
         resource.subscribe(c);
     }
+
+    #[inline]
+    fn wait_reason(&self) -> WaitReason {
+        unsafe { &*self.resource }.wait_reason()
+    }
 }
 
 pub trait EventSource {
     /// kernel handler of the event
     fn subscribe(&mut self, _c: CoroutineImpl);
+    /// what the coroutine is waiting for, shown in the coroutine dump
+    ///
+    /// most of the event sources are io requests, so the default is `Io`
+    fn wait_reason(&self) -> WaitReason {
+        WaitReason::Io
+    }
     /// after yield back process
     fn yield_back(&self, cancel: &'static Cancel) {
         // after return back we should re-check the panic and clear it
@@ -66,6 +78,9 @@ impl Done {
         // destroy the local storage
         let local = unsafe { Box::from_raw(get_co_local(&co)) };
         let name = local.get_co().name();
+        // it's finished once joined, stop tracking it before waking the joiner
+        local.get_co().inner.sched.remove_coroutine(local.get_co());
+        local.get_join().trigger();
 
         // recycle the coroutine
         let (size, used) = co.stack_usage();
@@ -91,31 +106,11 @@ impl EventSource for Done {
 
 /// coroutines are static generator
 /// the para type is EventResult, the result type is EventSubscriber
+///
+/// each coroutine owns its stack, so it can be resumed by any worker thread
 #[derive(Debug)]
 pub struct CoroutineImpl {
-    pub worker_thread_id: Option<ThreadId>,
     pub inner: Generator<'static, EventResult, EventSubscriber>,
-    pub reduce: Option<Vec<u8>>,
-}
-
-impl CoroutineImpl {
-    pub fn stack_reduce(&mut self) {
-        if self.reduce.is_none() {
-            let reduce_data = unsafe { &*self.gen.stack.get() }.stack_reduce(crate::config().get_stack_size());
-            if reduce_data.len() != 0 {
-                self.reduce = Some(reduce_data);
-                //unsafe { &*self.gen.stack.get() }.drop_stack();
-                //self.gen.stack = UnsafeCell::new(Stack::new(4096));
-            }
-        }
-    }
-
-    pub fn stack_restore(&mut self, mut stack: Stack) {
-        if let Some(v) = self.reduce.take() {
-            stack.write_stack_data(v);
-            self.gen.stack = UnsafeCell::new(stack);
-        }
-    }
 }
 
 impl Deref for CoroutineImpl {
@@ -144,10 +139,21 @@ fn get_co_local(co: &CoroutineImpl) -> *mut CoroutineLocal {
 
 /// The internal representation of a `Coroutine` handle
 struct Inner {
+    id: usize,
     name: Option<String>,
+    // where the coroutine is spawned
+    location: &'static Location<'static>,
+    // the `CoroutineState`
+    state: AtomicU8,
+    // the `WaitReason` labeled by the blocking primitive, 0 is none
+    wait_label: AtomicU8,
+    // the `WaitReason` of the event source that the coroutine parked on
+    parked_on: AtomicU8,
     stack_size: usize,
     park: Park,
     cancel: Cancel,
+    // the scheduler that runs the coroutine
+    sched: SchedRef,
 }
 
 #[derive(Clone)]
@@ -160,17 +166,34 @@ unsafe impl Send for Coroutine {}
 
 impl Coroutine {
     // Used only internally to construct a coroutine object without spawning
-    fn new(name: Option<String>, stack_size: usize) -> Coroutine {
+    fn new(
+        name: Option<String>,
+        stack_size: usize,
+        sched: &'static Scheduler,
+        location: &'static Location<'static>,
+    ) -> Coroutine {
+        static ID: AtomicUsize = AtomicUsize::new(1);
         Coroutine {
             inner: Arc::new(Inner {
+                id: ID.fetch_add(1, Ordering::Relaxed),
                 name,
+                location,
+                state: AtomicU8::new(CoroutineState::Ready as u8),
+                wait_label: AtomicU8::new(0),
+                parked_on: AtomicU8::new(0),
                 stack_size,
                 park: Park::new(),
                 cancel: Cancel::new(),
+                sched: SchedRef::new(sched),
             }),
         }
     }
 
+    /// Gets the unique id of the coroutine.
+    pub fn id(&self) -> usize {
+        self.inner.id
+    }
+
     /// Gets the coroutine stack size.
     pub fn stack_size(&self) -> usize {
         self.inner.stack_size
@@ -196,6 +219,48 @@ impl Coroutine {
         self.inner.name.as_deref()
     }
 
+    /// Gets the runtime that the coroutine is running in.
+    pub fn runtime(&self) -> Runtime {
+        Runtime::from_scheduler(self.inner.sched.get())
+    }
+
+    /// Gets where the coroutine is spawned.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.inner.location
+    }
+
+    /// Gets the running state of the coroutine.
+    pub fn state(&self) -> CoroutineState {
+        CoroutineState::from_u8(self.inner.state.load(Ordering::Relaxed))
+    }
+
+    /// Gets what the coroutine is waiting for, return `None` if it's not parked.
+    pub fn wait_reason(&self) -> Option<WaitReason> {
+        if self.state() != CoroutineState::Parked {
+            return None;
+        }
+        WaitReason::from_u8(self.inner.wait_label.load(Ordering::Relaxed))
+            .or_else(|| WaitReason::from_u8(self.inner.parked_on.load(Ordering::Relaxed)))
+    }
+
+    /// Take a snapshot of the coroutine for the dump.
+    pub fn info(&self) -> CoroutineInfo {
+        CoroutineInfo {
+            id: self.id(),
+            name: self.name().map(|n| n.to_owned()),
+            location: self.location(),
+            state: self.state(),
+            wait_reason: self.wait_reason(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_ready(&self) {
+        self.inner
+            .state
+            .store(CoroutineState::Ready as u8, Ordering::Relaxed);
+    }
+
     /// Get the internal cancel
     #[cfg(unix)]
     pub(crate) fn get_cancel(&self) -> &Cancel {
@@ -218,10 +283,11 @@ impl fmt::Debug for Coroutine {
 ///
 /// Methods can be chained on it in order to configure it.
 ///
-/// The two configurations available are:
+/// The configurations available are:
 ///
 /// - [`name`]: specifies an [associated name for the coroutine][naming-coroutines]
 /// - [`stack_size`]: specifies the [desired stack size for the coroutine][stack-size]
+/// - [`runtime`]: specifies the runtime that runs the coroutine
 ///
 /// The [`spawn`] method will take ownership of the builder and create an
 /// `io::Result` to the coroutine handle with the given configuration.
@@ -251,6 +317,7 @@ impl fmt::Debug for Coroutine {
 /// [`coroutine::spawn`]: ./fn.spawn.html
 /// [`stack_size`]: ./struct.Builder.html#method.stack_size
 /// [`name`]: ./struct.Builder.html#method.name
+/// [`runtime`]: ./struct.Builder.html#method.runtime
 /// [`spawn`]: ./struct.Builder.html#method.spawn
 /// [naming-coroutines]: ./index.html#naming-coroutine
 /// [stack-size]: ./index.html#stack-siz
@@ -260,6 +327,8 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned coroutine
     stack_size: Option<usize>,
+    // The runtime for the spawned coroutine
+    runtime: Option<Runtime>,
 }
 
 impl Builder {
@@ -269,6 +338,7 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            runtime: None,
         }
     }
 
@@ -285,24 +355,49 @@ impl Builder {
         self
     }
 
+    /// Sets the runtime for the new coroutine.
+    ///
+    /// if not set, the coroutine is spawned in the runtime of the current
+    /// coroutine, or the default runtime in a thread context.
+    pub fn runtime(mut self, rt: Runtime) -> Builder {
+        self.runtime = Some(rt);
+        self
+    }
+
+    // get the scheduler that the new coroutine would run in
+    fn get_scheduler(&self) -> &'static Scheduler {
+        match &self.runtime {
+            Some(rt) => rt.scheduler(),
+            None => get_scheduler(),
+        }
+    }
+
     /// Spawns a new coroutine, and returns a join handle for it.
     /// The join handle can be used to block on
     /// termination of the child coroutine, including recovering its panics.
-    fn spawn_impl<F, T>(self, f: F) -> (CoroutineImpl, JoinHandle<T>)
+    #[track_caller]
+    fn spawn_impl<F, T>(self, f: F) -> (Option<CoroutineImpl>, JoinHandle<T>)
         where
             F: FnOnce() -> T + Send + 'static,
             T: Send + 'static,
     {
         static DONE: Done = Done {};
 
-        let stack_size = self.stack_size.unwrap_or_else(|| config().get_stack_size());
+        let s = self.get_scheduler();
+        let stack_size = self.stack_size.unwrap_or_else(|| s.stack_size());
 
         // create a join resource, shared by waited coroutine and *this* coroutine
         let panic = Arc::new(AtomicCell::new(None));
         let join = Arc::new(Join::new(panic.clone()));
         let packet = Arc::new(AtomicCell::new(None));
-        let their_join = join.clone();
         let their_packet = packet.clone();
+        let handle = Coroutine::new(self.name, stack_size, s, Location::caller());
+        if !s.add_coroutine(&handle) {
+            // the runtime is shutdown, the handle is cancelled at once
+            warn!("runtime is shutdown, reject to spawn coroutine {:?}", handle.name());
+            join.trigger();
+            return (None, make_join_handle(handle, join, packet, panic));
+        }
 
         let subscriber = EventSubscriber {
             resource: &DONE as &dyn EventSource as *const _ as *mut dyn EventSource,
@@ -314,39 +409,19 @@ impl Builder {
             // to unwind these local data. for the panic err we would set it in the
             // coroutine local data so that can return from the packet variable
 
-            // set the return packet
+            // set the return packet, the join is triggered when the coroutine is dropped
             their_packet.swap(Some(f()));
-
-            their_join.trigger();
             subscriber
         };
-        let s = get_scheduler();
-        let mut tid = None;
-        let mut stack = None;
-        for x in &s.stacks {
-            tid = Some(x.0.clone());
-            stack = Some(x.1.shadow_clone());
-            break;
-        }
-        let c: fn() -> EventSubscriber = unsafe { std::mem::transmute_copy(&closure) };
-        let mut stack = stack.unwrap();
-        stack.reset();
-        // let s = Stack::new(stack_size);
-        // let stack_data = s.get_stack_data();
-        //
         let mut co = CoroutineImpl {
-            worker_thread_id: tid,
-            inner: Gn::new_opt_stack(c, stack),
-            reduce: None,
+            inner: Gn::new_opt(stack_size, closure),
         };
-        co.init_code(closure);
-        let handle = Coroutine::new(self.name, stack_size);
         // create the local storage
         let local = CoroutineLocal::new(handle.clone(), join.clone());
         // attache the local storage to the coroutine
         co.set_local_data(Box::into_raw(local) as *mut u8);
 
-        (co, make_join_handle(handle, join, packet, panic))
+        (Some(co), make_join_handle(handle, join, packet, panic))
     }
 
     /// Spawns a new coroutine by taking ownership of the `Builder`, and returns an
@@ -388,14 +463,20 @@ impl Builder {
     /// [`TLS`]: ./index.html#TLS
     /// [`go!`]: ../macro.go.html
     /// [`spawn`]: ./fn.spawn.html
+    ///
+    /// if the runtime is shutdown, the coroutine is not run and joining
+    /// the returned handle gets a cancel error.
+    #[track_caller]
     pub fn spawn<F, T>(self, f: F) -> JoinHandle<T>
         where
             F: FnOnce() -> T + Send + 'static,
             T: Send + 'static,
     {
+        let s = self.get_scheduler();
         let (co, handle) = self.spawn_impl(f);
-        let s = get_scheduler();
-        s.schedule_global(co);
+        if let Some(co) = co {
+            s.schedule_global(co);
+        }
         handle
     }
 
@@ -407,6 +488,7 @@ impl Builder {
     /// Cancel would drop all the resource of the coroutine.
     /// Normally this is safe but for some cases you should
     /// take care of the side effect
+    #[track_caller]
     pub fn spawn_local<F, T>(self, f: F) -> JoinHandle<T>
         where
             F: FnOnce() -> T + Send + 'static,
@@ -415,7 +497,9 @@ impl Builder {
         // we will still get optimizations in spawn_impl
         let (co, handle) = self.spawn_impl(f);
         // first run the coroutine in current thread
-        run_coroutine(co);
+        if let Some(co) = co {
+            run_coroutine(co);
+        }
         handle
     }
 }
@@ -470,6 +554,7 @@ impl Builder {
 /// [`join`]: struct.JoinHandle.html#method.join
 /// [`Builder::spawn`]: struct.Builder.html#method.spawn
 /// [`Builder`]: struct.Builder.html
+#[track_caller]
 pub fn spawn<F, T>(f: F) -> JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
@@ -525,6 +610,47 @@ pub(crate) fn co_cancel_data(co: &CoroutineImpl) -> &'static Cancel {
     &local.get_co().inner.cancel
 }
 
+/// get the handle of the coroutine
+#[inline]
+pub(crate) fn co_handle(co: &CoroutineImpl) -> &'static Coroutine {
+    let local = unsafe { &*get_co_local(co) };
+    local.get_co()
+}
+
+/// label what the current coroutine is going to wait for until the guard is dropped
+///
+/// the outermost label wins, so a channel that waits on a semphore is shown as
+/// a channel wait. it does nothing in a thread context
+pub(crate) struct WaitGuard(Option<Coroutine>);
+
+#[inline]
+pub(crate) fn wait_for(reason: WaitReason) -> WaitGuard {
+    let co = get_co_local_data().and_then(|local| {
+        let co = unsafe { local.as_ref() }.get_co();
+        co.inner
+            .wait_label
+            .compare_exchange(0, reason as u8, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()
+            .map(|_| co.clone())
+    });
+    WaitGuard(co)
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        if let Some(co) = self.0.take() {
+            co.inner.wait_label.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// get the scheduler that the coroutine belongs to
+#[inline]
+pub(crate) fn co_scheduler(co: &CoroutineImpl) -> &'static Scheduler {
+    let local = unsafe { &*get_co_local(co) };
+    local.get_co().inner.sched.get()
+}
+
 // windows use delay drop instead
 #[cfg(unix)]
 pub(crate) fn co_get_handle(co: &CoroutineImpl) -> Coroutine {
@@ -557,23 +683,31 @@ pub fn park_timeout(dur: Duration) {
 /// run the coroutine
 #[inline]
 pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
-    let s = get_scheduler();
-    co.stack_restore(s.get_stack(std::thread::current().id()));
-    match co.resume() {
+    let inner = &co_handle(&co).inner;
+    inner
+        .state
+        .store(CoroutineState::Running as u8, Ordering::Relaxed);
+    let prev = set_worker_running(co_handle(&co).id());
+    let ev = co.resume();
+    set_worker_running(prev);
+    match ev {
         Some(ev) => {
-            co.stack_reduce();
+            // the coroutine may be resumed by others after subscribe
+            inner
+                .parked_on
+                .store(ev.wait_reason() as u8, Ordering::Relaxed);
+            inner
+                .state
+                .store(CoroutineState::Parked as u8, Ordering::Relaxed);
             ev.subscribe(co);
         }
         None => {
             // panic happened here
             let local = unsafe { &mut *get_co_local(&co) };
-            let join = local.get_join();
-            // set the panic data
+            // set the panic data, the join is triggered when the coroutine is dropped
             if let Some(panic) = co.get_panic_data() {
-                join.set_panic_data(panic);
+                local.get_join().set_panic_data(panic);
             }
-            // trigger the join here
-            join.trigger();
             Done::drop_coroutine(co);
         }
     }
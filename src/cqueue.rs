@@ -5,10 +5,11 @@ use std::time::{Duration, Instant};
 
 use crate::cancel::Cancel;
 use crate::coroutine_impl::{
-    current_cancel_data, run_coroutine, Coroutine, CoroutineImpl, EventSource,
+    current_cancel_data, run_coroutine, wait_for, Coroutine, CoroutineImpl, EventSource,
 };
+use crate::dump::WaitReason;
 use crate::join::JoinHandle;
-use crate::scoped::spawn_unsafe;
+use crate::scoped::spawn_local_unsafe;
 use crate::std::sync::Mutex;
 use crate::std::sync::{AtomicOption, Blocker};
 use crate::yield_now::yield_with;
@@ -128,6 +129,10 @@ impl<'a> EventSource for EventSender<'a> {
     fn yield_back(&self, _cancel: &'static Cancel) {
         // ignore the cancel to let the bottom half get processed
     }
+
+    fn wait_reason(&self) -> WaitReason {
+        WaitReason::Select
+    }
 }
 
 impl<'a> Drop for EventSender<'a> {
@@ -167,21 +172,25 @@ impl Cqueue {
     /// register a select coroutine with the cqueue
     /// should use `cqueue_add` and `cqueue_add_oneshot` macros to
     /// create select coroutines correctly
+    ///
+    /// the select coroutine first runs its top half in current context until
+    /// it's blocked or the event is sent, so a ready event is queued when
+    /// this returns
+    #[track_caller]
     fn add_impl<'a, F>(&self, token: usize, f: F) -> Selector
     where
         F: FnOnce(EventSender) + Send + 'a,
     {
         let sender = EventSender {
-            id: self.total.load(Ordering::Relaxed),
+            id: self.total.fetch_add(1, Ordering::Relaxed),
             token,
             extra: 0.into(),
             cqueue: self,
         };
-        let h = unsafe { spawn_unsafe(move || f(sender)) };
-        let co = h.coroutine().clone();
+        // count it before running, the select coroutine may finish at once
         self.cnt.fetch_add(1, Ordering::Relaxed);
-
-        self.total.fetch_add(1, Ordering::Relaxed);
+        let h = unsafe { spawn_local_unsafe(move || f(sender)) };
+        let co = h.coroutine().clone();
         self.selectors.lock().unwrap().push(Some(h));
         Selector { co }
     }
@@ -189,6 +198,7 @@ impl Cqueue {
     /// register a select coroutine with the cqueue
     /// should use `cqueue_add` and `cqueue_add_oneshot` macros to
     /// create select coroutines correctly
+    #[track_caller]
     pub fn add<'a, F>(&self, token: usize, f: F) -> Selector
     where
         F: FnOnce(EventSender) + Send + 'a,
@@ -251,6 +261,7 @@ impl Cqueue {
                 }
             }
 
+            let _w = wait_for(WaitReason::Select);
             let cur = Blocker::current();
             // register the waiter
             self.to_wake.swap(cur.clone());
@@ -275,6 +286,34 @@ impl Cqueue {
             }
         }
     }
+
+    /// poll an event without blocking, it's used by the `default` arm of `select!`
+    ///
+    /// the select coroutines already ran their top half when they were added,
+    /// return `PollError::Timeout` if none of them has sent an event
+    pub fn try_poll(&self) -> Result<Event, PollError> {
+        while let Some(mut ev) = self.ev_queue.pop() {
+            if ev.kind == EventKind::Done {
+                self.check_panic(ev.id);
+                continue;
+            }
+            ev.continue_bottom();
+            return Ok(ev);
+        }
+        if self.cnt.load(Ordering::Relaxed) == 0 {
+            return Err(PollError::Finished);
+        }
+        Err(PollError::Timeout)
+    }
+}
+
+/// only the first arm of `select!` that claims the flag takes effect
+#[doc(hidden)]
+#[inline]
+pub fn claim(selected: &AtomicBool) -> bool {
+    selected
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+        .is_ok()
 }
 
 impl Drop for Cqueue {
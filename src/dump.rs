@@ -0,0 +1,248 @@
+//! coroutine dump
+//!
+//! every runtime keeps the handles of its live coroutines, each handle
+//! records where the coroutine is spawned, its running state and what
+//! it is waiting for. `Runtime::dump` formats them like a go routine dump.
+
+use std::fmt::{self, Write};
+use std::panic::Location;
+
+/// The running state of a coroutine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CoroutineState {
+    /// the coroutine is in a ready queue waiting for a worker
+    Ready = 0,
+    /// the coroutine is running on a worker thread
+    Running = 1,
+    /// the coroutine is parked on an event source
+    Parked = 2,
+}
+
+impl CoroutineState {
+    pub(crate) fn from_u8(v: u8) -> Self {
+        match v {
+            0 => CoroutineState::Ready,
+            1 => CoroutineState::Running,
+            _ => CoroutineState::Parked,
+        }
+    }
+
+    /// the lower case name of the state
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoroutineState::Ready => "ready",
+            CoroutineState::Running => "running",
+            CoroutineState::Parked => "parked",
+        }
+    }
+}
+
+impl fmt::Display for CoroutineState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a parked coroutine is waiting for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum WaitReason {
+    /// parked by `coroutine::park`
+    Park = 1,
+    /// sleeping
+    Sleep = 2,
+    /// yielded to the other coroutines
+    Yield = 3,
+    /// waiting for an io event
+    Io = 4,
+    /// joining another coroutine
+    Join = 5,
+    /// waiting in a `select`
+    Select = 6,
+    /// waiting for a `Mutex`
+    Mutex = 7,
+    /// waiting for a `RwLock`
+    RwLock = 8,
+    /// waiting for a `Condvar`
+    Condvar = 9,
+    /// waiting for a `Semphore`
+    Semphore = 10,
+    /// waiting for a `SyncFlag`
+    SyncFlag = 11,
+    /// waiting for a `WaitGroup`
+    WaitGroup = 12,
+    /// waiting to send to a channel
+    ChanSend = 13,
+    /// waiting to receive from a channel
+    ChanRecv = 14,
+    /// waiting for a future in `block_on`
+    Future = 15,
+}
+
+impl WaitReason {
+    pub(crate) fn from_u8(v: u8) -> Option<Self> {
+        let r = match v {
+            1 => WaitReason::Park,
+            2 => WaitReason::Sleep,
+            3 => WaitReason::Yield,
+            4 => WaitReason::Io,
+            5 => WaitReason::Join,
+            6 => WaitReason::Select,
+            7 => WaitReason::Mutex,
+            8 => WaitReason::RwLock,
+            9 => WaitReason::Condvar,
+            10 => WaitReason::Semphore,
+            11 => WaitReason::SyncFlag,
+            12 => WaitReason::WaitGroup,
+            13 => WaitReason::ChanSend,
+            14 => WaitReason::ChanRecv,
+            15 => WaitReason::Future,
+            _ => return None,
+        };
+        Some(r)
+    }
+
+    /// a short description of the reason
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WaitReason::Park => "park",
+            WaitReason::Sleep => "sleep",
+            WaitReason::Yield => "yield",
+            WaitReason::Io => "io wait",
+            WaitReason::Join => "join",
+            WaitReason::Select => "select",
+            WaitReason::Mutex => "mutex",
+            WaitReason::RwLock => "rwlock",
+            WaitReason::Condvar => "condvar",
+            WaitReason::Semphore => "semphore",
+            WaitReason::SyncFlag => "sync flag",
+            WaitReason::WaitGroup => "wait group",
+            WaitReason::ChanSend => "chan send",
+            WaitReason::ChanRecv => "chan receive",
+            WaitReason::Future => "future",
+        }
+    }
+}
+
+impl fmt::Display for WaitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A snapshot of a live coroutine
+#[derive(Debug, Clone)]
+pub struct CoroutineInfo {
+    /// the unique id of the coroutine
+    pub id: usize,
+    /// the name set by `Builder::name`
+    pub name: Option<String>,
+    /// where the coroutine is spawned
+    pub location: &'static Location<'static>,
+    /// the running state
+    pub state: CoroutineState,
+    /// what the coroutine is waiting for, only set when it's parked
+    pub wait_reason: Option<WaitReason>,
+}
+
+impl fmt::Display for CoroutineInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "coroutine {}", self.id)?;
+        if let Some(name) = &self.name {
+            write!(f, " {:?}", name)?;
+        }
+        write!(f, " [{}", self.state)?;
+        if let Some(reason) = self.wait_reason {
+            write!(f, ", {}", reason)?;
+        }
+        write!(f, "]\n    spawned at {}", self.location)
+    }
+}
+
+/// format the coroutines as text, one block for each coroutine
+pub(crate) fn to_text(cos: &[CoroutineInfo]) -> String {
+    let mut s = String::new();
+    for co in cos {
+        let _ = writeln!(s, "{}\n", co);
+    }
+    s
+}
+
+// write a json string literal
+fn write_json_str(s: &mut String, v: &str) {
+    s.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(s, "\\u{:04x}", c as u32);
+            }
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+/// format the coroutines as a json array
+pub(crate) fn to_json(cos: &[CoroutineInfo]) -> String {
+    let mut s = String::from("[");
+    for (i, co) in cos.iter().enumerate() {
+        if i != 0 {
+            s.push(',');
+        }
+        let _ = write!(s, "{{\"id\":{},\"name\":", co.id);
+        match &co.name {
+            Some(name) => write_json_str(&mut s, name),
+            None => s.push_str("null"),
+        }
+        s.push_str(",\"location\":");
+        write_json_str(&mut s, &co.location.to_string());
+        s.push_str(",\"state\":");
+        write_json_str(&mut s, co.state.as_str());
+        s.push_str(",\"wait_reason\":");
+        match co.wait_reason {
+            Some(r) => write_json_str(&mut s, r.as_str()),
+            None => s.push_str("null"),
+        }
+        s.push('}');
+    }
+    s.push(']');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_format() {
+        let cos = vec![
+            CoroutineInfo {
+                id: 1,
+                name: Some("a\"b".to_owned()),
+                location: Location::caller(),
+                state: CoroutineState::Parked,
+                wait_reason: Some(WaitReason::ChanRecv),
+            },
+            CoroutineInfo {
+                id: 2,
+                name: None,
+                location: Location::caller(),
+                state: CoroutineState::Ready,
+                wait_reason: None,
+            },
+        ];
+        let text = to_text(&cos);
+        assert!(text.starts_with("coroutine 1 \"a\\\"b\" [parked, chan receive]\n    spawned at src/dump.rs:"));
+        assert!(text.contains("coroutine 2 [ready]\n"));
+
+        let json = to_json(&cos);
+        assert!(json.starts_with("[{\"id\":1,\"name\":\"a\\\"b\",\"location\":\"src/dump.rs:"));
+        assert!(json.ends_with("\"state\":\"ready\",\"wait_reason\":null}]"));
+    }
+}
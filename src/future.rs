@@ -0,0 +1,120 @@
+//! bridge between `Future` and coroutines
+//!
+//! `block_on` polls a future on the current coroutine and parks the coroutine
+//! when the future is pending, the waker unparks it. in a thread context the
+//! thread is parked instead.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use crate::coroutine_impl::{current, is_coroutine, park, wait_for, Coroutine};
+use crate::dump::WaitReason;
+
+enum Unparker {
+    Coroutine(Coroutine),
+    Thread(Thread),
+}
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        match &**self {
+            Unparker::Coroutine(co) => co.unpark(),
+            Unparker::Thread(t) => t.unpark(),
+        }
+    }
+}
+
+/// run the future to completion on the current coroutine or thread
+///
+/// the coroutine is parked when the future is pending, so the other coroutines
+/// keep running on the worker. it's not bound to any async runtime, so futures
+/// that need a reactor of a specific runtime, like the tokio io types, would
+/// not work.
+///
+/// # Examples
+///
+/// ```
+/// use mco::coroutine::block_on;
+///
+/// let h = mco::co!(|| block_on(async { 1 + 1 }));
+/// assert_eq!(h.join().unwrap(), 2);
+/// ```
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let in_coroutine = is_coroutine();
+    let unparker = if in_coroutine {
+        Unparker::Coroutine(current())
+    } else {
+        Unparker::Thread(thread::current())
+    };
+    let waker = Waker::from(Arc::new(unparker));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = Box::pin(fut);
+    let _w = wait_for(WaitReason::Future);
+    loop {
+        if let Poll::Ready(v) = fut.as_mut().poll(&mut cx) {
+            return v;
+        }
+        // a wake before the park is not lost, the park returns at once
+        if in_coroutine {
+            park();
+        } else {
+            thread::park();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::Pin;
+    use std::time::Duration;
+
+    // a future that is ready after woken once from another thread
+    struct WakeLater(bool);
+
+    impl Future for WakeLater {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            if self.0 {
+                return Poll::Ready(42);
+            }
+            self.0 = true;
+            let waker = cx.waker().clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                waker.wake();
+            });
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_block_on() {
+        assert_eq!(block_on(WakeLater(false)), 42);
+        let h = crate::coroutine::spawn(|| block_on(WakeLater(false)));
+        assert_eq!(h.join().unwrap(), 42);
+    }
+
+    #[test]
+    fn test_await_coroutine() {
+        let (tx, rx) = crate::chan!();
+        let h = crate::coroutine::spawn(move || {
+            crate::coroutine::sleep(Duration::from_millis(10));
+            tx.send(3).unwrap();
+            4
+        });
+        let v = block_on(async move {
+            let v: i32 = rx.recv_async().await.unwrap();
+            assert!(rx.recv_async().await.is_err());
+            v + h.await.unwrap()
+        });
+        assert_eq!(v, 7);
+    }
+}
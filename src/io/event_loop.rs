@@ -1,5 +1,6 @@
 use std::io;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use super::sys::{Selector, SysEvent};
 use crate::scheduler::WORKER_ID;
@@ -7,16 +8,25 @@ use crate::scheduler::WORKER_ID;
 /// Single threaded IO event loop.
 pub struct EventLoop {
     selector: Selector,
+    io_workers: usize,
+    // set when the event loop is asked to exit
+    stopped: AtomicBool,
 }
 
 impl EventLoop {
     pub fn new(io_workers: usize) -> io::Result<EventLoop> {
-        Selector::new(io_workers).map(|selector| EventLoop { selector })
+        Selector::new(io_workers).map(|selector| EventLoop {
+            selector,
+            io_workers,
+            stopped: AtomicBool::new(false),
+        })
     }
 
-    /// Keep spinning the event loop indefinitely, and notify the handler whenever
-    /// any of the registered handles are ready.
-    pub fn run(&self, id: usize) -> io::Result<()> {
+    /// Keep spinning the event loop until `stop` is called, and notify the handler
+    /// whenever any of the registered handles are ready.
+    ///
+    /// `idle_wakeup` is the longest time the loop would wait without any event
+    pub fn run(&self, id: usize, idle_wakeup: Duration) -> io::Result<()> {
         use std::mem::MaybeUninit;
         #[cfg(nightly)]
         WORKER_ID.store(id, Ordering::Relaxed);
@@ -25,17 +35,33 @@ impl EventLoop {
 
         let events_buf: MaybeUninit<[SysEvent; 1024]> = MaybeUninit::uninit();
         let mut events_buf = unsafe { events_buf.assume_init() };
-        // wake up every 1 second
-        let mut next_expire = Some(1_000_000_000);
-        loop {
+        // wake up at least every idle_wakeup
+        let idle = idle_wakeup.as_nanos() as u64;
+        let mut next_expire = Some(idle);
+        while !self.stopped.load(Ordering::Acquire) {
             next_expire = match self.selector.select(id, &mut events_buf, next_expire) {
-                Ok(v) => v.or(Some(1_000_000_000)),
+                Ok(v) => v.or(Some(idle)),
                 Err(e) => {
                     error!("selector error={:?}", e);
                     continue;
                 }
             }
         }
+        Ok(())
+    }
+
+    /// ask all the running loops to exit, they would return after the current select
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        self.wakeup_all();
+    }
+
+    /// wake up all the running loops, each one checks its expired IO timers
+    /// after it's woken up
+    pub fn wakeup_all(&self) {
+        for id in 0..self.io_workers {
+            self.selector.wakeup(id);
+        }
     }
 
     // get the internal selector
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use super::EventData;
 use crate::cancel::CancelIo;
-use crate::scheduler::get_scheduler;
+use crate::coroutine_impl::co_scheduler;
 use crate::std::sync::AtomicOption;
 
 pub struct CancelIoImpl(AtomicOption<Arc<EventData>>);
@@ -25,7 +25,7 @@ impl CancelIo for CancelIoImpl {
     fn cancel(&self) -> Result<(), std::io::Error> {
         if let Some(e) = self.0.take() {
             if let Some(co) = e.co.take() {
-                get_scheduler().schedule(co);
+                co_scheduler(&co).schedule(co);
             }
         }
         Ok(())
@@ -89,23 +89,17 @@ impl Selector {
         // //info!("select; timeout={:?}", timeout_ms);
 
         // Wait for epoll events for at most timeout_ms milliseconds
-        let mask = self.vec.len() + id;
         let single_selector = unsafe { self.vec.get_unchecked(id) };
         let epfd = single_selector.epfd;
         // first register thread handle
         let scheduler = get_scheduler();
-        scheduler
-            .workers
-            .parked
-            .fetch_or(mask as u64, Ordering::Relaxed);
+        // don't block if any task is pushed before the worker is parked
+        let timeout_ms = if scheduler.park_worker(id) { timeout_ms } else { 0 };
 
         let n = epoll_wait(epfd, events, timeout_ms).map_err(from_nix_error)?;
 
         // clear the park stat after comeback
-        scheduler
-            .workers
-            .parked
-            .fetch_and((mask - self.vec.len()) as u64, Ordering::Relaxed);
+        scheduler.unpark_worker(id);
 
         for event in events[..n].iter() {
             if event.data() == 0 {
@@ -139,6 +133,10 @@ impl Selector {
                 h.remove()
             });
 
+            let counters = scheduler.worker_counters(id);
+            counters.io_events.inc();
+            counters.polled.inc();
+
             // schedule the coroutine
             run_coroutine(co);
         }
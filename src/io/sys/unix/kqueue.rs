@@ -93,6 +93,12 @@ impl Selector {
         events: &mut [SysEvent],
         timeout: Option<u64>,
     ) -> io::Result<Option<u64>> {
+        let single_selector = unsafe { self.vec.get_unchecked(id) };
+        // first register thread handle
+        let scheduler = get_scheduler();
+        // don't block if any task is pushed before the worker is parked
+        let timeout = if scheduler.park_worker(id) { timeout } else { Some(0) };
+
         let timeout = timeout.map(|to| {
             let dur = ns_to_dur(to);
             libc::timespec {
@@ -107,15 +113,6 @@ impl Selector {
             .unwrap_or(ptr::null_mut());
         // //info!("select; timeout={:?}", timeout_ms);
 
-        let mask = self.vec.len() + id;
-        let single_selector = unsafe { self.vec.get_unchecked(id) };
-        // first register thread handle
-        let scheduler = get_scheduler();
-        scheduler
-            .workers
-            .parked
-            .fetch_or(mask as u64, Ordering::Relaxed);
-
         // Wait for epoll events for at most timeout_ms milliseconds
         let kqfd = single_selector.kqfd;
         let n = unsafe {
@@ -130,10 +127,7 @@ impl Selector {
         };
 
         // clear the park stat after comeback
-        scheduler
-            .workers
-            .parked
-            .fetch_and((mask - self.vec.len()) as u64, Ordering::Relaxed);
+        scheduler.unpark_worker(id);
 
         if n < 0 {
             return Err(io::Error::last_os_error());
@@ -171,6 +165,10 @@ impl Selector {
                 h.remove()
             });
 
+            let counters = scheduler.worker_counters(id);
+            counters.io_events.inc();
+            counters.polled.inc();
+
             // schedule the coroutine
             run_coroutine(co);
         }
@@ -27,7 +27,7 @@ use std::sync::Arc;
 use std::{fmt, io, ptr};
 
 use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
-use crate::scheduler::get_scheduler;
+use crate::scheduler::{get_scheduler, SchedRef};
 use crate::std::sync::AtomicOption;
 use crate::timeout_list::{TimeOutList, TimeoutHandle};
 use crate::yield_now::{get_co_para, set_co_para};
@@ -36,13 +36,19 @@ pub use self::select::{Selector, SysEvent};
 
 #[inline]
 pub fn add_socket<T: AsRawFd + ?Sized>(t: &T) -> io::Result<IoData> {
-    get_scheduler().get_selector().add_fd(IoData::new(t))
+    let sched = get_scheduler();
+    let mut io = sched.get_selector().add_fd(IoData::new(t))?;
+    // remember the owner, the io may be dropped in another runtime or thread
+    io.sched = Some(SchedRef::new(sched));
+    Ok(io)
 }
 
 #[inline]
-fn del_socket(io: &IoData) {
-    // transfer the io to the selector
-    get_scheduler().get_selector().del_fd(io);
+fn del_socket(io: &mut IoData) {
+    // transfer the io to the selector that it's registered to
+    if let Some(sched) = io.sched.take() {
+        sched.get_selector().del_fd(io);
+    }
 }
 
 // deal with the io result
@@ -142,13 +148,20 @@ impl EventData {
 }
 
 // each file associated data
-pub struct IoData(Arc<EventData>);
+pub struct IoData {
+    data: Arc<EventData>,
+    // the scheduler whose selector the io is registered to, None if it's not
+    sched: Option<SchedRef>,
+}
 
 impl IoData {
     pub fn new<T: AsRawFd + ?Sized>(t: &T) -> Self {
         let fd = t.as_raw_fd();
         let event_data = Arc::new(EventData::new(fd));
-        IoData(event_data)
+        IoData {
+            data: event_data,
+            sched: None,
+        }
     }
 
     // clear the io flag
@@ -162,7 +175,7 @@ impl Deref for IoData {
     type Target = Arc<EventData>;
 
     fn deref(&self) -> &Arc<EventData> {
-        &self.0
+        &self.data
     }
 }
 
@@ -102,15 +102,12 @@ impl Selector {
         events: &mut [SysEvent],
         timeout: Option<u64>,
     ) -> io::Result<Option<u64>> {
-        let timeout = timeout.map(ns_to_dur);
-        // //info!("select; timeout={:?}", timeout);
-        let mask = self.vec.len() + id;
         let single_selector = unsafe { self.vec.get_unchecked(id) };
         let scheduler = get_scheduler();
-        scheduler
-            .workers
-            .parked
-            .fetch_or(mask as u64, Ordering::Relaxed);
+        // don't block if any task is pushed before the worker is parked
+        let timeout = if scheduler.park_worker(id) { timeout } else { Some(0) };
+        let timeout = timeout.map(ns_to_dur);
+        // //info!("select; timeout={:?}", timeout);
         let n = match single_selector.port.get_many(events, timeout) {
             Ok(statuses) => statuses.len(),
             Err(ref e) if e.raw_os_error() == Some(WAIT_TIMEOUT as i32) => 0,
@@ -118,10 +115,7 @@ impl Selector {
         };
 
         // clear the park stat after comeback
-        scheduler
-            .workers
-            .parked
-            .fetch_and((mask - self.vec.len()) as u64, Ordering::Relaxed);
+        scheduler.unpark_worker(id);
 
         for status in events[..n].iter() {
             // need to check the status for each io
@@ -182,6 +176,10 @@ impl Selector {
                 }
             }
 
+            let counters = scheduler.worker_counters(id);
+            counters.io_events.inc();
+            counters.polled.inc();
+
             // schedule the coroutine
             run_coroutine(co);
         }
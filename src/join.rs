@@ -1,10 +1,14 @@
 use std::any::Any;
 use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
 use std::thread::Result;
 
-use crate::coroutine_impl::Coroutine;
+use crate::coroutine_impl::{wait_for, Coroutine};
+use crate::dump::WaitReason;
 use crate::std::sync::{AtomicOption, Blocker};
 use crossbeam::atomic::AtomicCell;
 use mco_gen::Error;
@@ -12,6 +16,8 @@ use mco_gen::Error;
 pub struct Join {
     // the coroutine that waiting for this join handler
     to_wake: AtomicOption<Arc<Blocker>>,
+    // the task that awaiting for this join handler
+    waker: AtomicOption<Waker>,
     // the flag indicate if the host coroutine is not finished
     // when set to false, the coroutine is done
     state: AtomicBool,
@@ -28,6 +34,7 @@ impl Join {
     pub fn new(panic: Arc<AtomicCell<Option<Box<dyn Any + Send>>>>) -> Self {
         Join {
             to_wake: AtomicOption::none(),
+            waker: AtomicOption::none(),
             state: AtomicBool::new(true),
             panic,
         }
@@ -43,10 +50,14 @@ impl Join {
         if let Some(w) = self.to_wake.take() {
             let _ = w.unpark();
         }
+        if let Some(w) = self.waker.take() {
+            w.wake();
+        }
     }
 
     fn wait(&self) {
         if self.state.load(Ordering::Acquire) {
+            let _w = wait_for(WaitReason::Join);
             let cur = Blocker::current();
             // register the blocker first
             self.to_wake.swap(cur.clone());
@@ -109,14 +120,33 @@ impl<T> JoinHandle<T> {
     /// Join the coroutine, returning the result it produced.
     pub fn join(self) -> Result<T> {
         self.join.wait();
+        self.take_result()
+    }
 
-        // take the result
+    fn take_result(&self) -> Result<T> {
         self.packet
             .take()
             .ok_or_else(|| self.panic.take().unwrap_or_else(|| Box::new(Error::Cancel)))
     }
 }
 
+/// await the coroutine in async code, the output is the same as `join`
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T>> {
+        if !self.is_done() {
+            // register the waker first
+            self.join.waker.swap(cx.waker().clone());
+            // re-check the state
+            if !self.is_done() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(self.take_result())
+    }
+}
+
 impl<T> fmt::Debug for JoinHandle<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.pad("JoinHandle { .. }")
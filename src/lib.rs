@@ -32,8 +32,11 @@ extern crate core;
 
 mod cancel;
 mod config;
+mod dump;
+mod future;
 mod join;
 mod local;
+mod metrics;
 mod park;
 mod pool;
 mod sleep;
@@ -43,15 +46,19 @@ mod coroutine_impl;
 mod scheduler;
 mod scoped;
 mod timeout_list;
+mod timer_wheel;
 mod yield_now;
+mod watchdog;
 pub extern crate mco_gen;
 pub mod coroutine;
 pub mod cqueue;
 pub mod io;
 pub mod net;
 pub mod os;
+pub mod runtime;
 #[macro_use]
 pub mod std;
 
-pub use crate::config::{config, Config};
+pub use crate::config::{config, Config, TimerKind};
 pub use crate::local::LocalKey;
+pub use crate::runtime::{Runtime, ShutdownReport};
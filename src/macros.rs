@@ -117,9 +117,20 @@ macro_rules! cqueue_add_oneshot {
 
 /// macro used to select for only one event
 /// it will return the index of which event happens first
+///
+/// besides the `pattern = expression => body` arms, there are three arm kinds
+/// like the go select cases:
+/// * `send(tx, value) => body`, or `result = send(tx, value) => body` that
+///   binds the result of the send. it's selected when the value is sent to
+///   a full bounded channel, or the channel is closed
+/// * `after(duration) => body`, it's selected when the duration elapses
+/// * `default => body`, at most one, it's selected when none of the other arms
+///   is ready without blocking
+///
 /// for example:
 /// ```rust
 /// use mco::{chan, select};
+/// use std::time::Duration;
 ///
 ///     let (s, r) = chan!();
 ///     s.send(1);
@@ -131,15 +142,33 @@ macro_rules! cqueue_add_oneshot {
 ///             println!("{}",msg);
 ///         }
 ///     };
+///
+///     // the buffer is full and no receiver is there
+///     let (s, _r) = chan!(1);
+///     s.send(1).unwrap();
+///     let id = select! {
+///         send(s, 2) => unreachable!(),
+///         after(Duration::from_millis(10)) => println!("send timeout"),
+///     };
+///     assert_eq!(id, 1);
+///
+///     let id = select! {
+///         v = r.recv() => println!("{:?}", v),
+///         default => println!("nothing is ready"),
+///     };
+///     assert_eq!(id, 1);
 /// ```
 #[macro_export]
 macro_rules! select {
     (
-        $($name:pat = $top:expr => $bottom:expr), +$(,)?
-    ) => ($crate::select_token!($($name = $top => $bottom), +););
+        $($arms:tt)+
+    ) => ($crate::select_token!($($arms)+));
 }
 /// macro used to select for only one event
 /// it will return the index of which event happens first
+///
+/// the arms are the same as [`select!`]
+///
 /// for example:
 /// ```rust
 /// use mco::{chan, select_token};
@@ -152,23 +181,137 @@ macro_rules! select {
 ///         }
 ///     };
 /// ```
+///
+/// [`select!`]: macro.select.html
 #[macro_export]
 macro_rules! select_token {
     (
-        $($name:pat = $top:expr => $bottom:expr), +$(,)?
+        $($arms:tt)+
+    ) => ($crate::__select_arms!(@arms [] @index [0] @default [] $($arms)+));
+}
+
+// parse the select arms one by one, the special arms are checked before
+// the general one whose pattern fragment can't backtrack
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_arms {
+    (
+        @arms [$($arms:tt)*] @index [$($i:tt)*] @default []
+        default => $bottom:expr $(, $($rest:tt)*)?
+    ) => ($crate::__select_arms!(
+        @arms [$($arms)*] @index [$($i)* + 1] @default [($bottom) ($($i)*)] $($($rest)*)?
+    ));
+    (
+        @arms [$($arms:tt)*] @index [$($i:tt)*] @default [$($default:tt)*]
+        after($dur:expr) => $bottom:expr $(, $($rest:tt)*)?
+    ) => ($crate::__select_arms!(
+        @arms [$($arms)* (arm (_) ($crate::coroutine::sleep($dur)) ($bottom))]
+        @index [$($i)* + 1] @default [$($default)*] $($($rest)*)?
+    ));
+    (
+        @arms [$($arms:tt)*] @index [$($i:tt)*] @default [$($default:tt)*]
+        send($tx:expr, $v:expr) => $bottom:expr $(, $($rest:tt)*)?
+    ) => ($crate::__select_arms!(
+        @arms [$($arms)* (send (_) ($tx) ($v) ($bottom))]
+        @index [$($i)* + 1] @default [$($default)*] $($($rest)*)?
+    ));
+    (
+        @arms [$($arms:tt)*] @index [$($i:tt)*] @default [$($default:tt)*]
+        $name:pat = send($tx:expr, $v:expr) => $bottom:expr $(, $($rest:tt)*)?
+    ) => ($crate::__select_arms!(
+        @arms [$($arms)* (send ($name) ($tx) ($v) ($bottom))]
+        @index [$($i)* + 1] @default [$($default)*] $($($rest)*)?
+    ));
+    (
+        @arms [$($arms:tt)*] @index [$($i:tt)*] @default [$($default:tt)*]
+        $name:pat = $top:expr => $bottom:expr $(, $($rest:tt)*)?
+    ) => ($crate::__select_arms!(
+        @arms [$($arms)* (arm ($name) ($top) ($bottom))]
+        @index [$($i)* + 1] @default [$($default)*] $($($rest)*)?
+    ));
+    // all the arms are parsed, without a default arm
+    (
+        @arms [$($arm:tt)*] @index [$($i:tt)*] @default []
     ) => ({
+        let selected = ::std::sync::atomic::AtomicBool::new(false);
         $crate::cqueue::scope(|cqueue| {
             let mut _token = 0;
             $(
-                $crate::cqueue_add_oneshot!(cqueue, _token, $name = $top => $bottom);
+                $crate::__select_arm!(cqueue, _token, selected, $arm);
                 _token += 1;
-            )+
+            )*
             match cqueue.poll(None) {
                 Ok(ev) => return ev.token,
                 _ => unreachable!("select error"),
             }
         })
     });
+    // the default arm is selected if no event is ready
+    (
+        @arms [$($arm:tt)*] @index [$($i:tt)*]
+        @default [($default:expr) ($($default_token:tt)*)]
+    ) => ({
+        let default_token: usize = $($default_token)*;
+        let selected = ::std::sync::atomic::AtomicBool::new(false);
+        // the default arm may never return
+        #[allow(unreachable_code)]
+        let token = $crate::cqueue::scope(|cqueue| {
+            let mut _token = 0;
+            $(
+                if _token == default_token {
+                    _token += 1;
+                }
+                $crate::__select_arm!(cqueue, _token, selected, $arm);
+                _token += 1;
+            )*
+            match cqueue.try_poll() {
+                Ok(ev) => ev.token,
+                // no arm is ready, stop the others from taking effect
+                Err(_) if $crate::cqueue::claim(&selected) => {
+                    $default;
+                    default_token
+                }
+                // an arm is selected but its event is not sent yet
+                Err(_) => match cqueue.poll(None) {
+                    Ok(ev) => ev.token,
+                    _ => unreachable!("select error"),
+                },
+            }
+        });
+        token
+    });
+}
+
+// add the select coroutine of an arm, only the first one that claims the
+// `selected` flag would run its body and send the event
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_arm {
+    ($cqueue:ident, $token:expr, $selected:ident, (send ($name:pat) ($tx:expr) ($v:expr) ($bottom:expr))) => {
+        // the pattern is often a plain binding or `_`, `co!` adds an unsafe block
+        #[allow(irrefutable_let_patterns, unused_unsafe)]
+        let _ = $crate::co!($cqueue, $token, |es| {
+            // the value is sent only when the arm is selected
+            if let Some(r) = $tx.send_claim($v, || $crate::cqueue::claim(&$selected)) {
+                if let $name = r {
+                    $bottom
+                }
+                es.send(es.get_token());
+            }
+        });
+    };
+    ($cqueue:ident, $token:expr, $selected:ident, (arm ($name:pat) ($top:expr) ($bottom:expr))) => {
+        #[allow(irrefutable_let_patterns, unused_unsafe)]
+        let _ = $crate::co!($cqueue, $token, |es| {
+            let r = $top;
+            if $crate::cqueue::claim(&$selected) {
+                if let $name = r {
+                    $bottom
+                }
+                es.send(es.get_token());
+            }
+        });
+    };
 }
 
 /// macro used to join all scoped sub coroutines
@@ -0,0 +1,192 @@
+//! scheduler counters
+//!
+//! every worker owns a group of counters that only itself increases, so the
+//! counters are relaxed atomics on a separate cache line and are cheap
+//! enough to be always on. a snapshot is taken by `Runtime::metrics`.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, Weak};
+
+/// a monotonic counter
+#[derive(Debug, Default)]
+pub(crate) struct Counter(AtomicU64);
+
+impl Counter {
+    #[inline]
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// the counters of one worker thread
+#[repr(align(128))]
+#[derive(Debug, Default)]
+pub(crate) struct WorkerCounters {
+    // coroutines resumed by the worker
+    pub polled: Counter,
+    // times the worker is going to wait for events
+    pub parks: Counter,
+    // times the worker comes back from waiting
+    pub unparks: Counter,
+    // successful batch steals from the other workers
+    pub steals: Counter,
+    // successful batch steals from the global queue
+    pub global_steals: Counter,
+    // io events that resumed a coroutine
+    pub io_events: Counter,
+    // runs found blocking the worker by the watchdog, increased by the watchdog thread
+    pub blocked: Counter,
+    // times the worker starts or stops running a coroutine, only with the watchdog
+    pub switches: Counter,
+    // the id of the coroutine the worker is running, 0 when it's not running any
+    running: AtomicUsize,
+}
+
+impl WorkerCounters {
+    pub fn snapshot(&self, local_queue_depth: usize) -> WorkerMetrics {
+        WorkerMetrics {
+            polled: self.polled.get(),
+            local_queue_depth,
+            parks: self.parks.get(),
+            unparks: self.unparks.get(),
+            steals: self.steals.get(),
+            global_steals: self.global_steals.get(),
+            io_events: self.io_events.get(),
+            blocked: self.blocked.get(),
+        }
+    }
+
+    /// record the coroutine the worker starts running, return the previous one
+    #[inline]
+    pub fn set_running(&self, id: usize) -> usize {
+        self.switches.inc();
+        self.running.swap(id, Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn running(&self) -> usize {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+/// A snapshot of the counters of one worker thread
+///
+/// all the counters are accumulated from the start of the runtime
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkerMetrics {
+    /// the number of times a coroutine is resumed by the worker
+    pub polled: u64,
+    /// the number of coroutines in the local queue of the worker
+    pub local_queue_depth: usize,
+    /// the number of times the worker went to wait for events
+    pub parks: u64,
+    /// the number of times the worker came back from waiting
+    pub unparks: u64,
+    /// the number of batch steals from the other workers' queues
+    pub steals: u64,
+    /// the number of batch steals from the global queue
+    pub global_steals: u64,
+    /// the number of io events that resumed a coroutine
+    pub io_events: u64,
+    /// the number of times a coroutine blocked the worker longer than the
+    /// watchdog threshold, always 0 if the watchdog is not enabled
+    pub blocked: u64,
+}
+
+/// A snapshot of the runtime counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// the counters of each worker, indexed by the worker id
+    pub workers: Vec<WorkerMetrics>,
+    /// the number of coroutines in the global queue
+    pub global_queue_depth: usize,
+    /// the number of pending entries in the timer thread
+    pub timer_entries: usize,
+    /// the number of spawned coroutines that are not finished yet
+    pub live_coroutines: usize,
+    /// the live named channels of the process, see `channel::named`
+    pub channels: Vec<ChannelMetrics>,
+}
+
+impl RuntimeMetrics {
+    /// the total number of times a coroutine is resumed by all the workers
+    pub fn total_polled(&self) -> u64 {
+        self.workers.iter().map(|w| w.polled).sum()
+    }
+
+    /// the total number of batch steals between the workers
+    pub fn total_steals(&self) -> u64 {
+        self.workers.iter().map(|w| w.steals).sum()
+    }
+
+    /// the total number of times a coroutine blocked its worker, see `Builder::watchdog`
+    pub fn total_blocked(&self) -> u64 {
+        self.workers.iter().map(|w| w.blocked).sum()
+    }
+
+    /// the named channel with the most blocked senders, the number of queued
+    /// messages breaks the tie. it's likely the one causing backpressure
+    pub fn most_blocked_channel(&self) -> Option<&ChannelMetrics> {
+        self.channels
+            .iter()
+            .filter(|c| c.blocked_senders > 0 || c.len > 0)
+            .max_by_key(|c| (c.blocked_senders, c.len))
+    }
+}
+
+/// A snapshot of the counters of a channel
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelMetrics {
+    /// the name given by `channel::named`
+    pub name: Option<String>,
+    /// the buffer size, `None` for an unbounded channel
+    pub capacity: Option<usize>,
+    /// the number of messages in the buffer
+    pub len: usize,
+    /// the total number of messages sent
+    pub sent: u64,
+    /// the total number of messages received
+    pub received: u64,
+    /// the number of senders blocked for room, or for an unbuffered
+    /// channel until the message is received
+    pub blocked_senders: usize,
+    /// the number of receivers blocked for a message, not including the
+    /// awaiting tasks
+    pub blocked_receivers: usize,
+    /// the number of `Sender`s
+    pub senders: usize,
+    /// the number of `Receiver`s
+    pub receivers: usize,
+    /// the channel is closed by `close`
+    pub closed: bool,
+}
+
+/// a channel listed in the metrics
+pub(crate) trait ChannelStat: Send + Sync {
+    fn metrics(&self) -> ChannelMetrics;
+}
+
+// the named channels, the dropped ones are removed lazily
+static CHANNELS: Mutex<Vec<Weak<dyn ChannelStat>>> = Mutex::new(Vec::new());
+
+pub(crate) fn register_channel(c: Weak<dyn ChannelStat>) {
+    let mut channels = CHANNELS.lock().unwrap_or_else(|e| e.into_inner());
+    channels.retain(|c| c.strong_count() > 0);
+    channels.push(c);
+}
+
+/// snapshot the live named channels
+pub(crate) fn channels() -> Vec<ChannelMetrics> {
+    let mut channels = CHANNELS.lock().unwrap_or_else(|e| e.into_inner());
+    channels.retain(|c| c.strong_count() > 0);
+    channels
+        .iter()
+        .filter_map(|c| c.upgrade())
+        .map(|c| c.metrics())
+        .collect()
+}
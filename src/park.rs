@@ -6,11 +6,14 @@ use std::sync::{Arc, PoisonError};
 use std::time::Duration;
 
 use crate::cancel::Cancel;
-use crate::coroutine_impl::{co_cancel_data, run_coroutine, CoroutineImpl, EventSource};
-use crate::scheduler::get_scheduler;
+use crate::coroutine_impl::{
+    co_cancel_data, co_scheduler, run_coroutine, CoroutineImpl, EventSource,
+};
+use crate::dump::WaitReason;
+use crate::scheduler::{get_scheduler, TimerData};
 use crate::std::sync::atomic_dur::AtomicDuration;
 use crate::std::sync::AtomicOption;
-use crate::timeout_list::TimeoutHandle;
+use crate::timeout_list::TimerHandle;
 use crate::yield_now::{get_co_para, yield_now, yield_with};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -38,7 +41,7 @@ pub struct Park {
     // timeout settings in ms, 0 is none (park forever)
     timeout: AtomicDuration,
     // timer handle, can be null
-    timeout_handle: AtomicPtr<TimeoutHandle<Arc<AtomicOption<CoroutineImpl>>>>,
+    timeout_handle: AtomicPtr<TimerHandle<TimerData>>,
     // a flag if kernel is entered
     wait_kernel: AtomicBool,
 }
@@ -70,8 +73,8 @@ impl Park {
     #[inline]
     fn set_timeout_handle(
         &self,
-        handle: Option<TimeoutHandle<Arc<AtomicOption<CoroutineImpl>>>>,
-    ) -> Option<TimeoutHandle<Arc<AtomicOption<CoroutineImpl>>>> {
+        handle: Option<TimerHandle<TimerData>>,
+    ) -> Option<TimerHandle<TimerData>> {
         let ptr = match handle {
             None => ptr::null_mut(),
             Some(h) => h.into_ptr(),
@@ -81,7 +84,7 @@ impl Park {
         if old_ptr.is_null() {
             None
         } else {
-            Some(unsafe { TimeoutHandle::from_ptr(old_ptr) })
+            Some(unsafe { TimerHandle::from_ptr(old_ptr) })
         }
     }
 
@@ -164,7 +167,7 @@ impl Park {
             if b_sync {
                 run_coroutine(co);
             } else {
-                get_scheduler().schedule(co);
+                co_scheduler(&co).schedule(co);
             }
         }
     }
@@ -280,6 +283,10 @@ impl EventSource for Park {
             cancel.check_cancel();
         }
     }
+
+    fn wait_reason(&self) -> WaitReason {
+        WaitReason::Park
+    }
 }
 
 impl fmt::Debug for Park {
@@ -0,0 +1,479 @@
+//! `mco` Runtime interface
+//!
+//! a `Runtime` owns a group of worker threads, a timer thread and the
+//! io event loop that drive the coroutines spawned into it. Several runtimes
+//! can live in the same process, each one is isolated from the others.
+//!
+//! the free functions such as `coroutine::spawn` use the default runtime
+//! which is created from the global [`Config`] at the first use. when they
+//! are called inside a coroutine they use the runtime of that coroutine.
+//!
+//! a runtime can be stopped by [`Runtime::shutdown_timeout`], which gives the
+//! alive coroutines a deadline to finish and joins all of the runtime threads.
+//!
+//! [`Config`]: ../struct.Config.html
+//! [`Runtime::shutdown_timeout`]: struct.Runtime.html#method.shutdown_timeout
+
+use std::fmt;
+use std::io;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::time::Duration;
+
+use crate::config::{config, TimerKind, DEFAULT_STACK_SIZE};
+use crate::coroutine_impl;
+use crate::dump::{self, CoroutineInfo};
+use crate::join::JoinHandle;
+pub use crate::metrics::{ChannelMetrics, RuntimeMetrics, WorkerMetrics};
+use crate::scheduler::{default_scheduler, get_scheduler, Scheduler};
+
+// the default max waiting time of an idle worker
+const DEFAULT_IDLE_WAKEUP: Duration = Duration::from_secs(1);
+
+/// Runtime factory, which can be used in order to configure the properties of
+/// a new runtime.
+///
+/// # Examples
+///
+/// ```
+/// use mco::Runtime;
+///
+/// let rt = Runtime::builder()
+///     .workers(2)
+///     .thread_name("rpc")
+///     .build()
+///     .unwrap();
+/// let v = rt.block_on(|| 1 + 1);
+/// assert_eq!(v, 2);
+/// ```
+#[derive(Debug)]
+pub struct Builder {
+    // worker thread number, 0 means use the cpu number
+    workers: usize,
+    // default coroutine stack size, 0 means use the internal default
+    stack_size: usize,
+    // prefix of the worker thread names
+    thread_name: Option<String>,
+    // name of the timer thread
+    timer_thread_name: Option<String>,
+    // the longest time an idle worker sleeps before re-checking its queues
+    idle_wakeup: Duration,
+    // report the coroutines that run longer than this without yielding
+    watchdog: Option<Duration>,
+    // the implementation of the timers
+    timer: TimerKind,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// create a builder with the internal default settings
+    pub fn new() -> Builder {
+        Builder {
+            workers: 0,
+            stack_size: DEFAULT_STACK_SIZE,
+            thread_name: None,
+            timer_thread_name: None,
+            idle_wakeup: DEFAULT_IDLE_WAKEUP,
+            watchdog: None,
+            timer: TimerKind::default(),
+        }
+    }
+
+    // create a builder from the global config, used by the default runtime
+    pub(crate) fn from_config() -> Builder {
+        let c = config();
+        let builder = Builder::new()
+            .workers(c.get_workers())
+            .stack_size(c.get_stack_size())
+            .timer(c.get_timer());
+        match c.get_watchdog() {
+            Some(threshold) => builder.watchdog(threshold),
+            None => builder,
+        }
+    }
+
+    /// set the worker thread number
+    ///
+    /// if you pass 0 to it, will use the cpu number
+    pub fn workers(mut self, workers: usize) -> Builder {
+        self.workers = workers;
+        self
+    }
+
+    /// set default coroutine stack size in usize
+    ///
+    /// if you pass 0 to it, will use internal default
+    ///
+    /// each coroutine owns a dedicated stack of this size
+    pub fn stack_size(mut self, size: usize) -> Builder {
+        self.stack_size = size;
+        self
+    }
+
+    /// set the name prefix of the worker threads
+    ///
+    /// the workers are named as `{name}-{id}`, and the timer thread
+    /// is named as `{name}-timer` if `timer_thread_name` is not set
+    pub fn thread_name(mut self, name: &str) -> Builder {
+        self.thread_name = Some(name.to_string());
+        self
+    }
+
+    /// set the name of the timer thread
+    pub fn timer_thread_name(mut self, name: &str) -> Builder {
+        self.timer_thread_name = Some(name.to_string());
+        self
+    }
+
+    /// set the longest time an idle worker would wait before it re-checks
+    /// the queues and the timers, the default is 1 second
+    ///
+    /// a worker that missed a wakeup signal would pick up its work after this
+    pub fn idle_wakeup(mut self, dur: Duration) -> Builder {
+        if !dur.is_zero() {
+            self.idle_wakeup = dur;
+        }
+        self
+    }
+
+    /// enable the watchdog that detects the coroutines blocking their worker
+    ///
+    /// a watchdog thread samples the workers, when a worker has been running
+    /// the same coroutine longer than `threshold` without a switch, it logs a
+    /// warning with the coroutine and a backtrace of the worker thread, and
+    /// increases `WorkerMetrics::blocked`. each run is reported only once.
+    ///
+    /// the backtrace is only available on linux with glibc, it interrupts the
+    /// worker with `SIGURG` unless the application has its own handler for it.
+    /// the symbols are not demangled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::Runtime;
+    /// use std::time::Duration;
+    ///
+    /// let rt = Runtime::builder()
+    ///     .workers(1)
+    ///     .watchdog(Duration::from_millis(20))
+    ///     .build()
+    ///     .unwrap();
+    /// // a blocking call on the worker
+    /// rt.block_on(|| std::thread::sleep(Duration::from_millis(200)));
+    /// assert_eq!(rt.metrics().total_blocked(), 1);
+    /// ```
+    pub fn watchdog(mut self, threshold: Duration) -> Builder {
+        self.watchdog = if threshold.is_zero() {
+            None
+        } else {
+            Some(threshold)
+        };
+        self
+    }
+
+    /// select the implementation of the timers, the default is `TimerKind::List`
+    ///
+    /// `TimerKind::Wheel` suits the applications with lots of timers in
+    /// different durations, such as the read and write deadlines of many
+    /// connections
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::{Runtime, TimerKind};
+    /// use std::time::Duration;
+    ///
+    /// let rt = Runtime::builder()
+    ///     .workers(1)
+    ///     .timer(TimerKind::Wheel)
+    ///     .build()
+    ///     .unwrap();
+    /// rt.block_on(|| mco::coroutine::sleep(Duration::from_millis(10)));
+    /// ```
+    pub fn timer(mut self, kind: TimerKind) -> Builder {
+        self.timer = kind;
+        self
+    }
+
+    pub(crate) fn get_workers(&self) -> usize {
+        if self.workers != 0 {
+            self.workers
+        } else {
+            num_cpus::get()
+        }
+    }
+
+    pub(crate) fn get_stack_size(&self) -> usize {
+        if self.stack_size != 0 {
+            self.stack_size
+        } else {
+            DEFAULT_STACK_SIZE
+        }
+    }
+
+    pub(crate) fn get_worker_name(&self, id: usize) -> Option<String> {
+        self.thread_name.as_ref().map(|n| format!("{}-{}", n, id))
+    }
+
+    pub(crate) fn get_timer_thread_name(&self) -> Option<String> {
+        match &self.timer_thread_name {
+            Some(n) => Some(n.clone()),
+            None => self.thread_name.as_ref().map(|n| format!("{}-timer", n)),
+        }
+    }
+
+    pub(crate) fn get_idle_wakeup(&self) -> Duration {
+        self.idle_wakeup
+    }
+
+    pub(crate) fn get_watchdog(&self) -> Option<Duration> {
+        self.watchdog
+    }
+
+    pub(crate) fn get_timer(&self) -> TimerKind {
+        self.timer
+    }
+
+    pub(crate) fn get_watchdog_thread_name(&self) -> Option<String> {
+        self.thread_name.as_ref().map(|n| format!("{}-watchdog", n))
+    }
+
+    /// create the runtime and start all of its threads
+    pub fn build(self) -> io::Result<Runtime> {
+        info!("build runtime {:?}", self);
+        Scheduler::start(self).map(|sched| Runtime { sched })
+    }
+}
+
+/// The result of a runtime shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// the number of coroutines that didn't finish before the deadline and were cancelled
+    pub canceled: usize,
+    /// the number of coroutines that were still alive when the threads were stopped,
+    /// they are never resumed again
+    pub alive: usize,
+}
+
+/// A handle to a coroutine runtime
+///
+/// the handle is cheap to clone and can be shared between threads. when the
+/// last handle of a runtime created by [`Builder`] is dropped, the runtime
+/// is shutdown like [`Runtime::shutdown_timeout`] without waiting, the alive
+/// coroutines are cancelled at once. the runtime is freed after that, once
+/// all of its coroutines and timers are gone.
+///
+/// [`Builder`]: struct.Builder.html
+/// [`Runtime::shutdown_timeout`]: struct.Runtime.html#method.shutdown_timeout
+pub struct Runtime {
+    sched: &'static Scheduler,
+}
+
+impl Runtime {
+    /// create a runtime with the default settings
+    pub fn new() -> io::Result<Runtime> {
+        Builder::new().build()
+    }
+
+    /// get a runtime builder
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// get the default runtime which is used by the free functions
+    ///
+    /// the default runtime is never shutdown by dropping the handles
+    pub fn global() -> Runtime {
+        Runtime::from_scheduler(default_scheduler())
+    }
+
+    /// get the runtime of the current coroutine
+    ///
+    /// in a thread context that is not driven by any runtime,
+    /// the default runtime is returned
+    pub fn current() -> Runtime {
+        Runtime::from_scheduler(get_scheduler())
+    }
+
+    pub(crate) fn from_scheduler(sched: &'static Scheduler) -> Runtime {
+        sched.add_handle();
+        Runtime { sched }
+    }
+
+    pub(crate) fn scheduler(&self) -> &'static Scheduler {
+        self.sched
+    }
+
+    /// the worker thread number of the runtime
+    pub fn workers(&self) -> usize {
+        self.sched.workers()
+    }
+
+    /// the default coroutine stack size of the runtime
+    pub fn stack_size(&self) -> usize {
+        self.sched.stack_size()
+    }
+
+    /// Spawns a new coroutine in the runtime, returning a [`JoinHandle`] for it.
+    ///
+    /// use [`Builder::runtime`] if you need to set the name or the stack size
+    ///
+    /// [`JoinHandle`]: coroutine/struct.JoinHandle.html
+    /// [`Builder::runtime`]: coroutine/struct.Builder.html#method.runtime
+    #[track_caller]
+    pub fn spawn<F, T>(&self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        coroutine_impl::Builder::new().runtime(self.clone()).spawn(f)
+    }
+
+    /// run the closure in a coroutine of the runtime and block until it's done
+    ///
+    /// if the coroutine panics, the panic is propagated to the caller
+    #[track_caller]
+    pub fn block_on<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        match self.spawn(f).join() {
+            Ok(v) => v,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// take a snapshot of the runtime counters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::Runtime;
+    ///
+    /// let rt = Runtime::builder().workers(2).build().unwrap();
+    /// rt.block_on(|| {});
+    /// let metrics = rt.metrics();
+    /// assert_eq!(metrics.workers.len(), 2);
+    /// assert!(metrics.total_polled() >= 1);
+    /// ```
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.sched.metrics()
+    }
+
+    /// take a snapshot of all the live coroutines of the runtime, ordered by id
+    ///
+    /// the states are read without stopping the workers, so a busy coroutine
+    /// may already be in another state when the snapshot is returned
+    pub fn coroutines(&self) -> Vec<CoroutineInfo> {
+        self.sched.coroutine_infos()
+    }
+
+    /// dump all the live coroutines as text, like a go routine dump
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::Runtime;
+    /// use mco::coroutine::Builder;
+    ///
+    /// let rt = Runtime::builder().workers(1).build().unwrap();
+    /// let (tx, rx) = mco::chan!();
+    /// let h = Builder::new()
+    ///     .name("waiter".to_owned())
+    ///     .runtime(rt.clone())
+    ///     .spawn(move || rx.recv().unwrap());
+    /// while rt.coroutines()[0].wait_reason.is_none() {
+    ///     std::thread::yield_now();
+    /// }
+    /// let dump = rt.dump();
+    /// assert!(dump.contains("\"waiter\" [parked, chan receive]"));
+    /// tx.send(()).unwrap();
+    /// h.join().unwrap();
+    /// ```
+    pub fn dump(&self) -> String {
+        dump::to_text(&self.coroutines())
+    }
+
+    /// dump all the live coroutines as a json array
+    ///
+    /// each element has `id`, `name`, `location`, `state` and `wait_reason`
+    pub fn dump_json(&self) -> String {
+        dump::to_json(&self.coroutines())
+    }
+
+    /// return true if the runtime is shutting down or already stopped
+    pub fn is_shutdown(&self) -> bool {
+        self.sched.is_shutdown()
+    }
+
+    /// shutdown the runtime and join all of its threads
+    ///
+    /// new spawns are rejected at once, joining their handles gets a cancel error.
+    /// the alive coroutines have `timeout` to finish, those still running after
+    /// that are cancelled and have another `timeout` to unwind. then the timer
+    /// thread and the worker threads are stopped and joined.
+    ///
+    /// a coroutine that blocks its worker thread would also block the shutdown.
+    ///
+    /// # Errors
+    ///
+    /// return an error if it's called from one of the runtime's own threads,
+    /// or if the runtime is already shutdown
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::Runtime;
+    /// use std::time::Duration;
+    ///
+    /// let rt = Runtime::builder().workers(1).build().unwrap();
+    /// rt.spawn(|| println!("hello"));
+    /// let report = rt.shutdown_timeout(Duration::from_secs(1)).unwrap();
+    /// assert_eq!(report.alive, 0);
+    /// ```
+    pub fn shutdown_timeout(&self, timeout: Duration) -> io::Result<ShutdownReport> {
+        let (canceled, alive) = self.sched.shutdown(timeout, timeout)?;
+        info!("runtime shutdown, canceled={}, alive={}", canceled, alive);
+        Ok(ShutdownReport { canceled, alive })
+    }
+}
+
+impl Clone for Runtime {
+    fn clone(&self) -> Self {
+        Runtime::from_scheduler(self.sched)
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        unsafe { Scheduler::drop_handle(self.sched) }
+    }
+}
+
+// the runtime state is never left inconsistent by a panic in the caller
+impl UnwindSafe for Runtime {}
+impl RefUnwindSafe for Runtime {}
+
+impl PartialEq for Runtime {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.sched, other.sched)
+    }
+}
+
+impl Eq for Runtime {}
+
+impl fmt::Debug for Runtime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Runtime {{ workers: {}, stack_size: {} }}",
+            self.workers(),
+            self.stack_size()
+        )
+    }
+}
@@ -1,22 +1,27 @@
+use std::collections::HashMap;
 use std::io;
-use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::ops::Deref;
+use std::ptr;
+use std::sync::atomic::{self, AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Once};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::config::{config};
-use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
+use crate::coroutine_impl::{co_handle, run_coroutine, Coroutine, CoroutineImpl};
+use crate::dump::CoroutineInfo;
 use crate::io::{EventLoop, Selector};
-use crate::std::sync::AtomicOption;
+use crate::metrics::{self, RuntimeMetrics, WorkerCounters};
+use crate::runtime::Builder;
+use crate::std::sync::{AtomicOption, SyncFlag};
 use crate::timeout_list;
+use crate::watchdog;
 use crate::yield_now::set_co_para;
 use crossbeam::deque;
 use crossbeam::utils::Backoff;
+use parking_lot::Mutex;
 
 #[cfg(nightly)]
 use std::intrinsics::likely;
-use std::thread::ThreadId;
-use mco_gen::{Stack};
 
 #[cfg(not(nightly))]
 #[inline]
@@ -32,9 +37,73 @@ pub static WORKER_ID: AtomicUsize = AtomicUsize::new(!1);
 #[cfg(not(nightly))]
 thread_local! { pub static WORKER_ID: AtomicUsize = AtomicUsize::new(!1); }
 
-// here we use Arc<AtomicOption<>> for that in the select implementation
-// other event may try to consume the coroutine while timer thread consume it
-type TimerData = Arc<AtomicOption<CoroutineImpl>>;
+// the scheduler that owns the current thread, only set for worker and timer threads
+#[cfg(nightly)]
+#[thread_local]
+static CURRENT_SCHED: AtomicPtr<Scheduler> = AtomicPtr::new(ptr::null_mut());
+
+#[cfg(not(nightly))]
+thread_local! { static CURRENT_SCHED: AtomicPtr<Scheduler> = AtomicPtr::new(ptr::null_mut()); }
+
+#[inline]
+fn current_sched() -> *const Scheduler {
+    #[cfg(nightly)]
+    let s = CURRENT_SCHED.load(Ordering::Relaxed);
+    #[cfg(not(nightly))]
+    let s = CURRENT_SCHED.with(|s| s.load(Ordering::Relaxed));
+    s
+}
+
+// record the coroutine the current worker starts running for the watchdog,
+// return the previous one. it does nothing if the watchdog is off
+#[inline]
+pub(crate) fn set_worker_running(co_id: usize) -> usize {
+    let s = current_sched();
+    if s.is_null() {
+        return 0;
+    }
+    let s = unsafe { &*s };
+    if s.watchdog.is_none() {
+        return 0;
+    }
+    #[cfg(nightly)]
+    let id = WORKER_ID.load(Ordering::Relaxed);
+    #[cfg(not(nightly))]
+    let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
+    if id >= s.workers_len {
+        return 0;
+    }
+    s.worker_counters(id).set_running(co_id)
+}
+
+// the id of the current worker, `!1` if it's not a worker thread
+#[inline]
+pub(crate) fn worker_id() -> usize {
+    #[cfg(nightly)]
+    let id = WORKER_ID.load(Ordering::Relaxed);
+    #[cfg(not(nightly))]
+    let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
+    id
+}
+
+#[inline]
+fn set_current_sched(s: &'static Scheduler) {
+    let s = s as *const Scheduler as *mut Scheduler;
+    #[cfg(nightly)]
+    CURRENT_SCHED.store(s, Ordering::Relaxed);
+    #[cfg(not(nightly))]
+    CURRENT_SCHED.with(|c| c.store(s, Ordering::Relaxed));
+}
+
+// the event of a timer
+pub(crate) enum TimerData {
+    // here we use Arc<AtomicOption<>> for that in the select implementation
+    // other event may try to consume the coroutine while timer thread consume it
+    Coroutine(Arc<AtomicOption<CoroutineImpl>>),
+    // run on the timer thread, so it must not block
+    Callback(Box<dyn FnOnce() + Send>),
+}
+
 type TimerThread = timeout_list::TimerThread<TimerData>;
 
 // filter out the cancel panic, don't print anything for it
@@ -54,7 +123,49 @@ fn filter_cancel_panic() {
     }));
 }
 
-static mut SCHED: *const Scheduler = std::ptr::null();
+static SCHED: AtomicPtr<Scheduler> = AtomicPtr::new(ptr::null_mut());
+
+// the number of shards of the coroutine registry
+const REGISTRY_SHARDS: usize = 16;
+
+// the spawned coroutines that are not finished yet
+//
+// it's sharded by the coroutine id, so that the spawns and exits on different
+// workers seldom contend for the same lock
+struct Registry {
+    shards: Vec<Mutex<HashMap<usize, Coroutine>>>,
+    // the number of registered coroutines
+    live: AtomicUsize,
+    // fired when the last coroutine exits after the scheduler is closed
+    drained: SyncFlag,
+}
+
+impl Registry {
+    fn new() -> Self {
+        Registry {
+            shards: (0..REGISTRY_SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            live: AtomicUsize::new(0),
+            drained: SyncFlag::new(),
+        }
+    }
+
+    #[inline]
+    fn shard(&self, id: usize) -> &Mutex<HashMap<usize, Coroutine>> {
+        &self.shards[id % REGISTRY_SHARDS]
+    }
+
+    fn all(&self) -> Vec<Coroutine> {
+        let mut v = Vec::with_capacity(self.live.load(Ordering::Relaxed));
+        for shard in self.shards.iter() {
+            v.extend(shard.lock().values().cloned());
+        }
+        v
+    }
+}
+
+// how long the coroutines of a dropped runtime have to unwind after they
+// are cancelled
+const DROP_UNWIND: Duration = Duration::from_secs(1);
 
 pub struct ParkStatus {
     pub parked: AtomicU64,
@@ -69,6 +180,9 @@ impl ParkStatus {
 
     #[inline]
     fn wake_one(&self, scheduler: &Scheduler) {
+        // pairs with the fence in `Scheduler::park_worker`, either we see the
+        // parked bit or the worker sees the task that was just pushed
+        atomic::fence(Ordering::SeqCst);
         // when the worker thread is idle, the corresponding bit would set to 1
         let parked = self.parked.load(Ordering::Relaxed);
         // find the right most set bit
@@ -80,138 +194,206 @@ impl ParkStatus {
         if first_thread < self.workers {
             // mark the thread as busy in advance (clear to 0)
             // the worker thread would set it to 1 when idle
-            let mask = self.workers + first_thread;
+            let mask = 1 << first_thread;
             self.parked.fetch_and(!mask, Ordering::Relaxed);
             scheduler.get_selector().wakeup(first_thread as usize);
         }
     }
+
+    /// mark the worker as idle before it waits for events
+    #[inline]
+    pub fn park(&self, id: usize) {
+        self.parked.fetch_or(1u64 << id, Ordering::Relaxed);
+    }
+
+    /// mark the worker as busy after it comes back from waiting
+    #[inline]
+    pub fn unpark(&self, id: usize) {
+        self.parked.fetch_and(!(1u64 << id), Ordering::Relaxed);
+    }
+
+    // return true if the worker is idle
+    #[inline]
+    fn is_parked(&self, id: usize) -> bool {
+        self.parked.load(Ordering::Relaxed) & (1u64 << id) != 0
+    }
+}
+
+// a counted reference to the scheduler, a runtime scheduler is freed when
+// it's shutdown and all of the references are dropped
+pub(crate) struct SchedRef {
+    sched: &'static Scheduler,
+}
+
+impl SchedRef {
+    pub(crate) fn new(sched: &'static Scheduler) -> Self {
+        sched.refs.fetch_add(1, Ordering::Relaxed);
+        SchedRef { sched }
+    }
+
+    // the scheduler lives as long as the reference
+    #[inline]
+    pub(crate) fn get(&self) -> &'static Scheduler {
+        self.sched
+    }
+}
+
+impl Deref for SchedRef {
+    type Target = Scheduler;
+
+    #[inline]
+    fn deref(&self) -> &Scheduler {
+        self.sched
+    }
+}
+
+impl Clone for SchedRef {
+    fn clone(&self) -> Self {
+        SchedRef::new(self.sched)
+    }
+}
+
+impl Drop for SchedRef {
+    fn drop(&mut self) {
+        unsafe { Scheduler::release(self.sched) }
+    }
 }
 
+// the pointer of a scheduler that is passed to the thread releasing it
+struct RawSched(*const Scheduler);
+
+unsafe impl Send for RawSched {}
+
 static SCHEDULER_INITED: AtomicBool = AtomicBool::new(false);
 
+/// return true if the default scheduler is already started
+pub(crate) fn is_scheduler_inited() -> bool {
+    SCHEDULER_INITED.load(Ordering::Relaxed)
+}
+
 #[inline(never)]
 fn init_scheduler() {
-    let workers = config().get_workers();
-    let b: Box<Scheduler> = Scheduler::new(workers);
-    unsafe {
-        SCHED = Box::into_raw(b);
-    }
-    filter_cancel_panic();
-
-    // timer thread
-    thread::spawn(move || {
-        println!("init timer worker {:?}", std::thread::current().id());
-        let s = unsafe { &*SCHED };
-        // timer function
-        let timer_event_handler = |co: Arc<AtomicOption<CoroutineImpl>>| {
-            // just re-push the co to the visit list
-            if let Some(mut c) = co.take() {
-                // set the timeout result for the coroutine
-                set_co_para(&mut c, io::Error::new(io::ErrorKind::TimedOut, "timeout"));
-                // s.schedule_global(c);
-                // run_coroutine(c);
-                if let Some(t) = &c.worker_thread_id {
-                    let id = s.worker_ids.get(t);
-                    if let Some(id) = id {
-                        s.local_queues[*id].push(c);
-                        s.get_selector().wakeup(*id);
-                    }
-                }
-            }
-        };
-        s.timer_thread.run(&timer_event_handler);
-    });
-
-    println!("init workers {}", workers);
-    let wg = crossbeam::sync::WaitGroup::new();
-    // io event loop thread
-    for id in 0..workers {
-        let w = wg.clone();
-        thread::spawn(move || {
-            println!("init worker {:?}", std::thread::current().id());
-            let s = unsafe { &*SCHED };
-            s.worker_ids.insert(std::thread::current().id(), id);
-            s.stacks.insert(std::thread::current().id(), Stack::new(crate::config().get_stack_size()));
-            drop(w);
-            s.event_loop.run(id as usize).unwrap_or_else(|e| {
-                panic!("event_loop failed running, err={}", e);
-            });
-        });
-    }
-    wg.wait();
-    SCHEDULER_INITED.store(true, Ordering::Relaxed);
+    let s = Scheduler::start(Builder::from_config()).expect("can't start the default scheduler");
+    SCHED.store(s as *const Scheduler as *mut Scheduler, Ordering::Release);
+    SCHEDULER_INITED.store(true, Ordering::Release);
 }
 
+/// get the scheduler of the current thread, if the current thread is not
+/// a worker of any runtime return the default scheduler
 #[inline]
 pub fn get_scheduler() -> &'static Scheduler {
-    unsafe {
-        if likely(!SCHED.is_null()) {
-            return &*SCHED;
-        }
+    let s = current_sched();
+    if likely(!s.is_null()) {
+        return unsafe { &*s };
+    }
+    default_scheduler()
+}
+
+/// get the default scheduler, it's created from the global `Config` at the first use
+#[inline]
+pub fn default_scheduler() -> &'static Scheduler {
+    let s = SCHED.load(Ordering::Acquire);
+    if likely(!s.is_null()) {
+        return unsafe { &*s };
     }
     static ONCE: Once = Once::new();
     ONCE.call_once(init_scheduler);
 
     loop {
-        let v = SCHEDULER_INITED.load(Ordering::Relaxed);
+        let v = SCHEDULER_INITED.load(Ordering::Acquire);
         if v == true {
             break;
         }
     }
-    unsafe { &*SCHED }
+    unsafe { &*SCHED.load(Ordering::Acquire) }
 }
 
-// #[inline]
-// fn steal_global<T>(global: &deque::Injector<T>, local: &deque::Worker<T>) -> Option<T> {
-//     static GLOBABLE_LOCK: AtomicUsize = AtomicUsize::new(0);
-//     if GLOBABLE_LOCK
-//         .compare_exchange(0, 1, Ordering::Relaxed, Ordering::Relaxed)
-//         .is_err()
-//     {
-//         return None;
-//     }
-//
-//     let backoff = Backoff::new();
-//     let ret = loop {
-//         match global.steal_batch_and_pop(local) {
-//             deque::Steal::Success(t) => break Some(t),
-//             deque::Steal::Empty => break None,
-//             deque::Steal::Retry => backoff.snooze(),
-//         }
-//     };
-//     GLOBABLE_LOCK.store(0, Ordering::Relaxed);
-//     ret
-// }
-
-// #[inline]
-// fn steal_local<T>(stealer: &deque::Stealer<T>, local: &deque::Worker<T>) -> Option<T> {
-//     let backoff = Backoff::new();
-//     loop {
-//         match stealer.steal_batch_and_pop(local) {
-//             deque::Steal::Success(t) => return Some(t),
-//             deque::Steal::Empty => return None,
-//             deque::Steal::Retry => backoff.snooze(),
-//         }
-//     }
-// }
+// how many local pops a worker makes before it checks the global queue
+const GLOBAL_QUEUE_INTERVAL: u32 = 61;
+
+#[inline]
+fn steal_global<T>(
+    global: &deque::Injector<T>,
+    local: &deque::Worker<T>,
+    lock: &AtomicBool,
+) -> Option<T> {
+    // only one worker steals from the global queue at a time,
+    // the others would try the local queues of their neighbours
+    if lock
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        return None;
+    }
+
+    let backoff = Backoff::new();
+    let ret = loop {
+        match global.steal_batch_and_pop(local) {
+            deque::Steal::Success(t) => break Some(t),
+            deque::Steal::Empty => break None,
+            deque::Steal::Retry => backoff.snooze(),
+        }
+    };
+    lock.store(false, Ordering::Release);
+    ret
+}
+
+#[inline]
+fn steal_local<T>(stealer: &deque::Stealer<T>, local: &deque::Worker<T>) -> Option<T> {
+    let backoff = Backoff::new();
+    loop {
+        match stealer.steal_batch_and_pop(local) {
+            deque::Steal::Success(t) => return Some(t),
+            deque::Steal::Empty => return None,
+            deque::Steal::Retry => backoff.snooze(),
+        }
+    }
+}
 
 #[repr(align(128))]
 pub struct Scheduler {
     event_loop: EventLoop,
-    global_queue: dark_std::sync::SyncVec<CoroutineImpl>,
+    global_queue: deque::Injector<CoroutineImpl>,
+    // only one worker could steal from the global queue at a time
+    global_lock: AtomicBool,
     local_queues: Vec<deque::Worker<CoroutineImpl>>,
     pub(crate) workers: ParkStatus,
     timer_thread: TimerThread,
-    // stealers: Vec<Vec<(usize, deque::Stealer<CoroutineImpl>)>>,
+    stealers: Vec<Vec<(usize, deque::Stealer<CoroutineImpl>)>>,
+    // used to get the local queue depth of each worker from any thread
+    queue_stealers: Vec<deque::Stealer<CoroutineImpl>>,
+    // per worker counters
+    counters: Vec<WorkerCounters>,
     workers_len: usize,
-    pub(crate) worker_ids: dark_std::sync::SyncHashMap<ThreadId, usize>,
-    pub(crate) stacks: dark_std::sync::SyncHashMap<ThreadId, Stack>,
+    stack_size: usize,
+    // the timer thread and the worker threads, joined by shutdown
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+    // set when the scheduler is shutting down, no more spawn is accepted
+    closed: AtomicBool,
+    // all the spawned coroutines that are not finished yet
+    coroutines: Registry,
+    // the threshold of the watchdog, None if it's not enabled
+    watchdog: Option<Duration>,
+    // the native handles of the worker threads, used by the watchdog
+    worker_threads: Vec<AtomicUsize>,
+    // the number of `Runtime` handles, the runtime is shutdown when the last
+    // one is dropped
+    handles: AtomicUsize,
+    // the number of `SchedRef`s, plus one for all the `Runtime` handles
+    refs: AtomicUsize,
+    // set when the `Runtime` handles released their reference
+    released: AtomicBool,
+    // the pointer of the leaked box, the scheduler is freed through it
+    raw: *mut Scheduler,
 }
 
+unsafe impl Sync for Scheduler {}
+
 impl Scheduler {
-    pub fn new(workers: usize) -> Box<Self> {
+    pub fn new(workers: usize, stack_size: usize) -> Box<Self> {
         let mut local_queues = Vec::with_capacity(workers);
         (0..workers).for_each(|_| local_queues.push(deque::Worker::new_fifo()));
+        let queue_stealers = local_queues.iter().map(|w| w.stealer()).collect();
         let mut stealers = Vec::with_capacity(workers);
         for id in 0..workers {
             let mut stealers_l = Vec::with_capacity(workers);
@@ -225,50 +407,326 @@ impl Scheduler {
         }
         Box::new(Scheduler {
             event_loop: EventLoop::new(workers).expect("can't create event_loop"),
-            global_queue: dark_std::sync::SyncVec::new(),
+            global_queue: deque::Injector::new(),
+            global_lock: AtomicBool::new(false),
             local_queues,
             timer_thread: TimerThread::new(),
             workers: ParkStatus::new(workers as u64),
-            //stealers,
+            stealers,
+            queue_stealers,
+            counters: (0..workers).map(|_| WorkerCounters::default()).collect(),
             workers_len: workers,
-            worker_ids: {
-                let v = dark_std::sync::SyncHashMap::new();
-                v
-            },
-            stacks: dark_std::sync::SyncHashMap::new(),
+            stack_size,
+            threads: Mutex::new(Vec::with_capacity(workers + 1)),
+            closed: AtomicBool::new(false),
+            coroutines: Registry::new(),
+            watchdog: None,
+            worker_threads: (0..workers).map(|_| AtomicUsize::new(0)).collect(),
+            handles: AtomicUsize::new(1),
+            refs: AtomicUsize::new(1),
+            released: AtomicBool::new(false),
+            raw: ptr::null_mut(),
         })
     }
 
+    /// create a scheduler from the builder settings and start all its threads
+    ///
+    /// the scheduler has one `Runtime` handle, it's freed after the last
+    /// handle and the last `SchedRef` are dropped
+    pub(crate) fn start(builder: Builder) -> io::Result<&'static Scheduler> {
+        let workers = builder.get_workers();
+        let stack_size = builder.get_stack_size();
+        let mut s = Scheduler::new(workers, stack_size);
+        s.watchdog = builder.get_watchdog();
+        s.timer_thread = TimerThread::with_kind(builder.get_timer(), workers);
+        let raw = Box::into_raw(s);
+        let s: &'static Scheduler = unsafe {
+            (*raw).raw = raw;
+            &*raw
+        };
+        filter_cancel_panic();
+
+        // timer thread
+        let mut timer = thread::Builder::new();
+        if let Some(name) = builder.get_timer_thread_name() {
+            timer = timer.name(name);
+        }
+        let timer = timer.spawn(move || {
+            debug!("init timer worker {:?}", std::thread::current().id());
+            set_current_sched(s);
+            // timer function
+            let timer_event_handler = |data: TimerData| match data {
+                TimerData::Coroutine(co) => {
+                    // just re-push the co to the visit list
+                    if let Some(mut c) = co.take() {
+                        // set the timeout result for the coroutine
+                        set_co_para(&mut c, io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+                        s.schedule_global(c);
+                    }
+                }
+                TimerData::Callback(f) => f(),
+            };
+            s.timer_thread.run(&timer_event_handler);
+        })?;
+        s.threads.lock().push(timer);
+
+        debug!("init workers {}", workers);
+        let wg = crossbeam::sync::WaitGroup::new();
+        let idle_wakeup = builder.get_idle_wakeup();
+        // io event loop thread
+        for id in 0..workers {
+            let w = wg.clone();
+            let mut worker = thread::Builder::new();
+            if let Some(name) = builder.get_worker_name(id) {
+                worker = worker.name(name);
+            }
+            let worker = worker.spawn(move || {
+                debug!("init worker {:?}", std::thread::current().id());
+                set_current_sched(s);
+                s.worker_threads[id].store(watchdog::current_thread(), Ordering::Relaxed);
+                drop(w);
+                s.event_loop.run(id as usize, idle_wakeup).unwrap_or_else(|e| {
+                    panic!("event_loop failed running, err={}", e);
+                });
+            })?;
+            s.threads.lock().push(worker);
+        }
+        wg.wait();
+
+        if let Some(threshold) = s.watchdog {
+            let mut t = thread::Builder::new();
+            if let Some(name) = builder.get_watchdog_thread_name() {
+                t = t.name(name);
+            }
+            let t = t.spawn(move || watchdog::run(s, threshold))?;
+            s.threads.lock().push(t);
+        }
+        Ok(s)
+    }
+
+    // a `Runtime` handle of the scheduler is created
+    pub(crate) fn add_handle(&self) {
+        self.handles.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // a `Runtime` handle is dropped, the last one shuts down the runtime and
+    // releases the reference of the handles
+    pub(crate) unsafe fn drop_handle(this: *const Scheduler) {
+        let s = &*this;
+        if s.handles.fetch_sub(1, Ordering::AcqRel) != 1 || s.released.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let this = RawSched(this);
+        let release = move || {
+            let s = unsafe { &*this.0 };
+            if !s.is_shutdown() {
+                if let Err(e) = s.shutdown(Duration::from_secs(0), DROP_UNWIND) {
+                    warn!("shutdown dropped runtime failed, err={}", e);
+                }
+            }
+            unsafe { Scheduler::release(this.0) }
+        };
+        if s.is_current() {
+            // the threads of the runtime can't be joined by themselves
+            if let Err(e) = thread::Builder::new().spawn(release) {
+                error!("can't spawn thread to shutdown dropped runtime, err={}", e);
+            }
+        } else {
+            release();
+        }
+    }
+
+    // drop a reference, the last one frees the scheduler. it takes a raw
+    // pointer so that no reference to the scheduler is alive across the free
+    unsafe fn release(this: *const Scheduler) {
+        if (*this).refs.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+        // the alive coroutines and the timers hold their own references,
+        // nothing could refer to the scheduler now
+        let raw = (*this).raw;
+        drop(Box::from_raw(raw));
+    }
+
+    /// return true if the current thread is one of the scheduler threads
+    #[inline]
+    pub fn is_current(&self) -> bool {
+        ptr::eq(current_sched(), self)
+    }
+
+    /// the worker thread number of the scheduler
+    #[inline]
+    pub fn workers(&self) -> usize {
+        self.workers_len
+    }
+
+    /// the default coroutine stack size of the scheduler
+    #[inline]
+    pub fn stack_size(&self) -> usize {
+        self.stack_size
+    }
+
+    /// return true if the scheduler is shutting down or already stopped
+    #[inline]
+    pub fn is_shutdown(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    // track a new spawned coroutine until it's done
+    //
+    // return false if the scheduler is closed, the coroutine is not tracked
+    pub(crate) fn add_coroutine(&self, co: &Coroutine) -> bool {
+        let registry = &self.coroutines;
+        registry.live.fetch_add(1, Ordering::SeqCst);
+        registry.shard(co.id()).lock().insert(co.id(), co.clone());
+        // check after the registration, so either the shutdown sees the
+        // coroutine or the spawn sees the closed flag
+        if self.closed.load(Ordering::SeqCst) {
+            self.remove_coroutine(co);
+            return false;
+        }
+        true
+    }
+
+    // the coroutine is done, stop tracking it
+    pub(crate) fn remove_coroutine(&self, co: &Coroutine) {
+        let registry = &self.coroutines;
+        let co = registry.shard(co.id()).lock().remove(&co.id());
+        if co.is_none() {
+            return;
+        }
+        if registry.live.fetch_sub(1, Ordering::SeqCst) == 1
+            && self.closed.load(Ordering::SeqCst)
+        {
+            registry.drained.fire();
+        }
+    }
+
+    // the number of coroutines that are not finished yet
+    pub(crate) fn alive_coroutines(&self) -> usize {
+        self.coroutines.live.load(Ordering::SeqCst)
+    }
+
+    /// snapshot of the live coroutines, ordered by id
+    pub(crate) fn coroutine_infos(&self) -> Vec<CoroutineInfo> {
+        let mut v: Vec<_> = self.coroutines.all().iter().map(|co| co.info()).collect();
+        v.sort_by_key(|info| info.id);
+        v
+    }
+
+    /// snapshot of a live coroutine
+    pub(crate) fn coroutine_info(&self, id: usize) -> Option<CoroutineInfo> {
+        self.coroutines.shard(id).lock().get(&id).map(|co| co.info())
+    }
+
+    // the native handle of the worker thread, None if not known
+    pub(crate) fn worker_thread(&self, id: usize) -> Option<usize> {
+        match self.worker_threads[id].load(Ordering::Relaxed) {
+            0 => None,
+            t => Some(t),
+        }
+    }
+
+    // wait until all the coroutines are done or the deadline is reached,
+    // the scheduler must be closed so that the last exit fires the signal
+    fn wait_coroutines(&self, deadline: Instant) -> usize {
+        loop {
+            let alive = self.alive_coroutines();
+            let now = Instant::now();
+            if alive == 0 || now >= deadline {
+                return alive;
+            }
+            self.coroutines.drained.wait_timeout(deadline - now);
+        }
+    }
+
+    /// stop the scheduler and join all of its threads
+    ///
+    /// new spawns are rejected at once, then the running coroutines have
+    /// `timeout` to finish. those still alive after that are cancelled and
+    /// have `unwind` to unwind before the threads are stopped.
+    ///
+    /// return the number of cancelled coroutines and the number of coroutines
+    /// that were still alive when the threads were stopped
+    pub(crate) fn shutdown(&self, timeout: Duration, unwind: Duration) -> io::Result<(usize, usize)> {
+        if self.is_current() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "can't shutdown the runtime from its own threads",
+            ));
+        }
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the runtime is already shutdown",
+            ));
+        }
+
+        let mut canceled = 0;
+        if self.wait_coroutines(Instant::now() + timeout) != 0 {
+            let cos = self.coroutines.all();
+            canceled = cos.len();
+            warn!("shutdown runtime, cancel {} alive coroutines", canceled);
+            for co in cos {
+                co.cancel();
+            }
+            self.wait_coroutines(Instant::now() + unwind);
+        }
+
+        self.timer_thread.stop();
+        self.event_loop.stop();
+        let threads = std::mem::take(&mut *self.threads.lock());
+        for t in threads {
+            if t.join().is_err() {
+                error!("runtime thread panicked before shutdown");
+            }
+        }
+        // the pending timers would never fire, drop them so that the timers
+        // don't keep the scheduler alive. a coroutine that is still blocked
+        // can't be dropped without resuming it, so it's leaked instead
+        self.timer_thread.clear(&|data| match data {
+            TimerData::Coroutine(co) => std::mem::forget(co.take()),
+            TimerData::Callback(f) => drop(f),
+        });
+        Ok((canceled, self.alive_coroutines()))
+    }
+
     pub fn run_queued_tasks(&self, id: usize) {
         let local = unsafe { self.local_queues.get_unchecked(id) };
-        // let stealers = unsafe { self.stealers.get_unchecked(id) };
+        let stealers = unsafe { self.stealers.get_unchecked(id) };
+        let counters = self.worker_counters(id);
+        let mut tick: u32 = 0;
         loop {
+            tick = tick.wrapping_add(1);
+            // check the global queue once in a while, or the coroutines that
+            // keep re-queuing themselves locally would starve the global ones
+            let co = if tick % GLOBAL_QUEUE_INTERVAL == 0 {
+                steal_global(&self.global_queue, local, &self.global_lock)
+                    .inspect(|_| counters.global_steals.inc())
+            } else {
+                None
+            };
             // Pop a task from the local queue
-            let co = local.pop().or_else(|| {
-                // Try stealing a of task from other local queues.
-                // let parked_threads = self.workers.parked.load(Ordering::Relaxed);
-                // stealers
-                //     .iter()
-                //     .map(|s| {
-                //         if parked_threads & (self.workers_len + s.0) as u64 != 0 {
-                //             return None;
-                //         }
-                //         steal_local(&s.1, local)
-                //     })
-                //     .find_map(|r| r)
-                //     // Try stealing a batch of tasks from the global queue.
-                //     .or_else(|| {
-                //         if self.global_queue.is_empty() {
-                //             None
-                //         } else {
-                //             steal_global(&self.global_queue, local)
-                //         }
-                //     })
-                let f = self.steal_global();
-                f
+            let co = co.or_else(|| local.pop()).or_else(|| {
+                // Try stealing a batch of tasks from the global queue.
+                steal_global(&self.global_queue, local, &self.global_lock)
+                    .inspect(|_| counters.global_steals.inc())
+                    // Try stealing a batch of tasks from other local queues.
+                    .or_else(|| {
+                        let co = stealers
+                            .iter()
+                            .filter(|s| !self.workers.is_parked(s.0))
+                            .find_map(|s| steal_local(&s.1, local));
+                        if co.is_some() {
+                            counters.steals.inc();
+                        }
+                        co
+                    })
             });
-            if let Some(mut co) = co {
-                co.worker_thread_id = Some(std::thread::current().id());
+
+            if let Some(co) = co {
+                counters.polled.inc();
                 run_coroutine(co);
             } else {
                 // do a re-check
@@ -279,68 +737,75 @@ impl Scheduler {
         }
     }
 
-    fn steal_global(&self) -> Option<CoroutineImpl> {
-        let current_id = std::thread::current().id();
-        if self.global_queue.is_empty() {
-            None
+    #[inline]
+    pub(crate) fn worker_counters(&self, id: usize) -> &WorkerCounters {
+        unsafe { self.counters.get_unchecked(id) }
+    }
+
+    /// take a snapshot of the scheduler counters
+    pub fn metrics(&self) -> RuntimeMetrics {
+        RuntimeMetrics {
+            workers: self
+                .counters
+                .iter()
+                .zip(self.queue_stealers.iter())
+                .map(|(c, q)| c.snapshot(q.len()))
+                .collect(),
+            global_queue_depth: self.global_queue.len(),
+            timer_entries: self.timer_thread.len(),
+            live_coroutines: self.alive_coroutines(),
+            channels: metrics::channels(),
+        }
+    }
+
+    /// mark the worker as idle before it waits for events
+    ///
+    /// return false if there are tasks pushed before the worker is marked,
+    /// in that case the worker should not block on waiting
+    pub fn park_worker(&self, id: usize) -> bool {
+        self.worker_counters(id).parks.inc();
+        self.workers.park(id);
+        atomic::fence(Ordering::SeqCst);
+        let local = unsafe { self.local_queues.get_unchecked(id) };
+        if self.global_queue.is_empty() && local.is_empty() {
+            true
         } else {
-            let mut index = 0;
-            for x in self.global_queue.iter() {
-                if let Some(v) = &x.worker_thread_id {
-                    if &current_id == v {
-                        let item = self.global_queue.remove(index);
-                        //recheck
-                        if let Some(v) = item {
-                            if v.worker_thread_id == None {
-                                return Some(v);
-                            } else {
-                                if v.worker_thread_id.as_ref().unwrap() != &current_id {
-                                    self.global_queue.push(v);
-                                } else {
-                                    return Some(v);
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    let item = self.global_queue.pop();
-                    //recheck
-                    if let Some(v) = item {
-                        if v.worker_thread_id == None {
-                            return Some(v);
-                        } else {
-                            if v.worker_thread_id.as_ref().unwrap() != &current_id {
-                                self.global_queue.push(v);
-                            } else {
-                                return Some(v);
-                            }
-                        }
-                    }
-                }
-                index += 1;
-            }
-            return None;
+            self.workers.unpark(id);
+            false
         }
     }
 
+    /// mark the worker as busy after it comes back from waiting
+    pub fn unpark_worker(&self, id: usize) {
+        self.worker_counters(id).unparks.inc();
+        self.workers.unpark(id);
+    }
+
     /// put the coroutine to correct queue so that next time it can be scheduled
     #[inline]
     pub fn schedule(&self, co: CoroutineImpl) {
+        co_handle(&co).set_ready();
         #[cfg(nightly)]
             let id = WORKER_ID.load(Ordering::Relaxed);
         #[cfg(not(nightly))]
             let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
 
-        if id == !1 {
+        if id == !1 || !self.is_current() {
             self.schedule_global(co);
         } else {
-            unsafe { self.local_queues.get_unchecked(id) }.push(co);
+            let local = unsafe { self.local_queues.get_unchecked(id) };
+            local.push(co);
+            // let an idle worker steal the pending tasks
+            if local.len() > 1 {
+                self.workers.wake_one(self);
+            }
         }
     }
 
     /// put the coroutine to global queue so that next time it can be scheduled
     #[inline]
-    pub fn schedule_global(&self, mut co: CoroutineImpl) {
+    pub fn schedule_global(&self, co: CoroutineImpl) {
+        co_handle(&co).set_ready();
         self.global_queue.push(co);
         // signal one waiting thread if any
         self.workers.wake_one(self);
@@ -351,32 +816,46 @@ impl Scheduler {
         &self,
         dur: Duration,
         co: Arc<AtomicOption<CoroutineImpl>>,
-    ) -> timeout_list::TimeoutHandle<TimerData> {
-        self.timer_thread.add_timer(dur, co)
+    ) -> timeout_list::TimerHandle<TimerData> {
+        self.timer_thread.add_timer(dur, TimerData::Coroutine(co))
     }
 
+    /// run the callback on the timer thread after the duration
+    ///
+    /// the returned handle must be dropped by `del_timer`, or after the
+    /// callback is run, since the timer thread may pop it concurrently
     #[inline]
-    pub fn del_timer(&self, handle: timeout_list::TimeoutHandle<TimerData>) {
-        self.timer_thread.del_timer(handle);
+    pub(crate) fn add_timer_callback(
+        &self,
+        dur: Duration,
+        f: Box<dyn FnOnce() + Send>,
+    ) -> timeout_list::TimerHandle<TimerData> {
+        self.timer_thread.add_timer(dur, TimerData::Callback(f))
     }
 
     #[inline]
-    pub fn get_selector(&self) -> &Selector {
-        self.event_loop.get_selector()
+    pub fn del_timer(&self, handle: timeout_list::TimerHandle<TimerData>) {
+        self.timer_thread.del_timer(handle);
+    }
+
+    /// wake up the timer thread and the event loops, it's needed when the
+    /// clock is changed
+    pub(crate) fn wake_timer(&self) {
+        self.timer_thread.wakeup();
+        self.event_loop.wakeup_all();
+    }
+
+    /// wait until the timers expired at the time `now` in ns are fired
+    ///
+    /// the IO timeouts are in the timer list of each event loop, they are
+    /// woken up to fire the expired ones without waiting
+    pub(crate) fn fire_timers(&self, now: u64) {
+        self.timer_thread.fire_expired(now);
+        self.event_loop.wakeup_all();
     }
 
     #[inline]
-    pub fn get_stack(&self, key: std::thread::ThreadId) -> Stack {
-        match self.stacks.get(&key) {
-            None => {
-                let v = Stack::new(crate::config().get_stack_size());
-                let r = v.shadow_clone();
-                self.stacks.insert(key, v);
-                r
-            }
-            Some(v) => {
-                v.shadow_clone()
-            }
-        }
+    pub fn get_selector(&self) -> &Selector {
+        self.event_loop.get_selector()
     }
 }
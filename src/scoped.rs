@@ -8,11 +8,12 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
 
-use crate::coroutine_impl::{spawn, Coroutine};
+use crate::coroutine_impl::{spawn, Builder, Coroutine};
 use crate::join::JoinHandle;
 use crossbeam::atomic::AtomicCell;
 
 /// Like `coroutine::spawn`, but without the closure bounds.
+#[track_caller]
 pub unsafe fn spawn_unsafe<'a, F>(f: F) -> JoinHandle<()>
 where
     F: FnOnce() + Send + 'a,
@@ -22,6 +23,18 @@ where
     spawn(move || closure())
 }
 
+/// Like `spawn_unsafe`, but first run the coroutine in current context until
+/// it's blocked or finished
+#[track_caller]
+pub unsafe fn spawn_local_unsafe<'a, F>(f: F) -> JoinHandle<()>
+where
+    F: FnOnce() + Send + 'a,
+{
+    let closure: Box<dyn FnOnce() + 'a> = Box::new(f);
+    let closure: Box<dyn FnOnce() + Send> = mem::transmute(closure);
+    Builder::new().spawn_local(move || closure())
+}
+
 pub struct Scope<'a> {
     dtors: RefCell<Option<DtorChain<'a>>>,
 }
@@ -131,6 +144,7 @@ impl<'a> Scope<'a> {
     /// before the current stack frame goes away, allowing you to reference the parent stack frame
     /// directly. This is ensured by having the parent join on the child coroutine before the
     /// scope exits.
+    #[track_caller]
     fn spawn_impl<F, T>(&self, f: F) -> ScopedJoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'a,
@@ -168,6 +182,7 @@ impl<'a> Scope<'a> {
     /// before the current stack frame goes away, allowing you to reference the parent stack frame
     /// directly. This is ensured by having the parent join on the child coroutine before the
     /// scope exits.
+    #[track_caller]
     pub unsafe fn spawn<F, T>(&self, f: F) -> ScopedJoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'a,
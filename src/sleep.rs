@@ -4,6 +4,7 @@ use std::thread;
 use std::time::Duration;
 
 use crate::coroutine_impl::{co_cancel_data, is_coroutine, CoroutineImpl, EventSource};
+use crate::dump::WaitReason;
 use crate::scheduler::get_scheduler;
 use crate::yield_now::{get_co_para, yield_with};
 
@@ -26,6 +27,10 @@ impl EventSource for Sleep {
             let _ = cancel.cancel();
         }
     }
+
+    fn wait_reason(&self) -> WaitReason {
+        WaitReason::Sleep
+    }
 }
 
 /// block the current coroutine until timeout
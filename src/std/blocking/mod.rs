@@ -1,8 +1,12 @@
+mod pool;
+
+pub use self::pool::{BlockingHandle, BlockingPool, Builder, DEFAULT_KEEP_ALIVE, DEFAULT_MAX_THREADS};
+
+use crate::config::config;
 use crate::std::errors::Result;
-use crate::std::sync::channel;
-use std::panic::set_hook;
+use once_cell::sync::Lazy;
 
-/// will spawn a thread to doing and return value by channel
+/// run the task on the default blocking pool and wait for the value
 /// for example:
 /// ```rust
 ///     let v = mco::spawn_blocking!(|| {
@@ -22,7 +26,39 @@ macro_rules! spawn_blocking {
     };
 }
 
-/// will spawn a thread to doing and return value by channel
+/// the default blocking pool, configured by `config().set_max_blocking_threads`,
+/// `set_blocking_keep_alive` and `set_blocking_queue_limit`
+static DEFAULT_POOL: Lazy<BlockingPool> = Lazy::new(|| {
+    BlockingPool::builder()
+        .max_threads(config().get_max_blocking_threads())
+        .keep_alive(config().get_blocking_keep_alive())
+        .queue_limit(config().get_blocking_queue_limit())
+        .build()
+});
+
+/// run the closure on the default blocking pool, return a handle to the result
+///
+/// for example:
+/// ```rust
+///     let h = mco::std::blocking::spawn(|| {
+///         //do something Heavy CPU arithmetic and blocking APIS
+///         return 1;
+///     }).unwrap();
+///     assert_eq!(h.join().unwrap(), 1);
+/// ```
+pub fn spawn<F, T>(f: F) -> Result<BlockingHandle<T>>
+where
+    F: FnOnce() -> T,
+    F: Send + 'static,
+    T: Send + 'static,
+{
+    DEFAULT_POOL.spawn(f)
+}
+
+/// run the closure on the default blocking pool and wait for the value
+///
+/// the current coroutine is blocked but not its worker thread,
+/// a panic in the closure is returned as an error.
 /// for example:
 /// ```rust
 ///     let v = mco::std::blocking::spawn_blocking(|| {
@@ -37,27 +73,15 @@ where
     F: Send + 'static,
     T: Send + 'static,
 {
-    let (s, r) = channel::<Result<T>>();
-    std::thread::Builder::new().spawn(move || {
-        let send_e = s.clone();
-        set_hook(Box::new(move |panic_info| {
-            let e = err!(
-                "{}",
-                panic_info
-                    .payload()
-                    .downcast_ref::<&str>()
-                    .unwrap_or(&"spawn_blocking panic!")
-            );
-            let _ = send_e.send(Err(e));
-        }));
-        let _ = s.send(Ok(f()));
-    })?;
-    return r.recv()?;
+    spawn(f)?.join()
 }
 
 #[cfg(test)]
 mod test {
-    use crate::std::blocking::spawn_blocking;
+    use super::*;
+    use crate::std::sync::channel::channel;
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn test_spawn_blocking() {
@@ -75,4 +99,73 @@ mod test {
         });
         assert_eq!(v.is_err(), true);
     }
+
+    #[test]
+    fn test_pool_limit() {
+        let pool = BlockingPool::builder()
+            .max_threads(2)
+            .queue_limit(1)
+            .keep_alive(Duration::from_millis(10))
+            .build();
+        let (tx, rx) = channel::<()>();
+        let hs: Vec<_> = (0..2)
+            .map(|_| {
+                let rx = rx.clone();
+                pool.spawn(move || rx.recv().is_err()).unwrap()
+            })
+            .collect();
+        while pool.queued() > 0 {
+            thread::yield_now();
+        }
+        // the two threads are busy, one in the queue
+        let queued = pool.spawn(|| 3).unwrap();
+        assert_eq!(pool.threads(), 2);
+        assert!(pool.spawn(|| 4).is_err());
+        drop(tx);
+        for h in hs {
+            assert!(h.join().unwrap());
+        }
+        assert_eq!(queued.join().unwrap(), 3);
+        // the idle threads exit after the keep alive
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(pool.threads(), 0);
+    }
+
+    #[test]
+    fn test_await_and_join() {
+        let h = spawn(|| {
+            thread::sleep(Duration::from_millis(10));
+            1
+        })
+        .unwrap();
+        assert_eq!(crate::coroutine::block_on(h).unwrap(), 1);
+
+        let h = spawn(|| panic!("boom")).unwrap();
+        let e = crate::coroutine::spawn(move || h.join()).join().unwrap();
+        assert!(e.unwrap_err().to_string().contains("boom"));
+    }
+
+    #[test]
+    fn test_select_join() {
+        let h = spawn(|| {
+            thread::sleep(Duration::from_millis(10));
+            1
+        })
+        .unwrap();
+        let id = select! {
+            Ok(v) = h.join() => assert_eq!(v, 1),
+            after(Duration::from_secs(10)) => unreachable!(),
+        };
+        assert_eq!(id, 0);
+
+        // the join is given up when another arm is selected
+        let h = spawn(|| thread::sleep(Duration::from_secs(1))).unwrap();
+        let (tx, rx) = channel();
+        tx.send(2).unwrap();
+        let id = select! {
+            _ = h.join() => unreachable!(),
+            v = rx.recv() => assert_eq!(v, Ok(2)),
+        };
+        assert_eq!(id, 1);
+    }
 }
@@ -0,0 +1,301 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+
+use crate::std::errors::{Error, Result};
+use crate::std::sync::oneshot::{self, Receiver};
+
+/// the default max number of threads of a blocking pool
+pub const DEFAULT_MAX_THREADS: usize = 512;
+/// the default time an idle blocking thread waits for new tasks before exiting
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+type Task = Box<dyn FnOnce() + Send>;
+
+struct State {
+    queue: VecDeque<Task>,
+    // the number of alive threads
+    threads: usize,
+    // the number of threads waiting for tasks
+    idle: usize,
+    shutdown: bool,
+}
+
+struct Inner {
+    name: String,
+    max_threads: usize,
+    keep_alive: Duration,
+    queue_limit: usize,
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl Inner {
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(task) = state.queue.pop_front() {
+                drop(state);
+                // the panic is caught by the task itself
+                task();
+                state = self.state.lock().unwrap();
+                continue;
+            }
+            if state.shutdown {
+                break;
+            }
+            state.idle += 1;
+            let (s, timeout) = self.cond.wait_timeout(state, self.keep_alive).unwrap();
+            state = s;
+            state.idle -= 1;
+            if timeout.timed_out() && state.queue.is_empty() {
+                break;
+            }
+        }
+        state.threads -= 1;
+    }
+}
+
+/// A pool of threads for the blocking tasks
+///
+/// a coroutine must not block its worker thread, the blocking calls that have
+/// no coroutine version, like the `std::fs` APIs or heavy computing, should be
+/// sent to a blocking pool. the threads are created on demand up to the max
+/// number and exit after they are idle for the keep alive time.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::blocking::BlockingPool;
+///
+/// let pool = BlockingPool::builder().max_threads(4).build();
+/// let h = pool.spawn(|| std::fs::metadata(".").is_ok()).unwrap();
+/// assert_eq!(h.join().unwrap(), true);
+/// ```
+pub struct BlockingPool {
+    inner: Arc<Inner>,
+}
+
+/// Blocking pool configuration
+#[derive(Debug, Clone)]
+pub struct Builder {
+    name: String,
+    max_threads: usize,
+    keep_alive: Duration,
+    queue_limit: usize,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// create a builder with the default settings
+    pub fn new() -> Builder {
+        Builder {
+            name: "mco-blocking".to_owned(),
+            max_threads: DEFAULT_MAX_THREADS,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            queue_limit: usize::MAX,
+        }
+    }
+
+    /// set the name of the threads
+    pub fn name(mut self, name: String) -> Builder {
+        self.name = name;
+        self
+    }
+
+    /// set the max number of threads, the minimum is 1
+    pub fn max_threads(mut self, n: usize) -> Builder {
+        self.max_threads = n.max(1);
+        self
+    }
+
+    /// set the time an idle thread waits for new tasks before exiting
+    pub fn keep_alive(mut self, dur: Duration) -> Builder {
+        self.keep_alive = dur;
+        self
+    }
+
+    /// set the max number of tasks waiting for a free thread, the default is unbounded
+    ///
+    /// `spawn` returns an error when all the threads are busy and the queue is full
+    pub fn queue_limit(mut self, n: usize) -> Builder {
+        self.queue_limit = n;
+        self
+    }
+
+    /// create the pool, the threads are created on demand
+    pub fn build(self) -> BlockingPool {
+        BlockingPool {
+            inner: Arc::new(Inner {
+                name: self.name,
+                max_threads: self.max_threads,
+                keep_alive: self.keep_alive,
+                queue_limit: self.queue_limit,
+                state: Mutex::new(State {
+                    queue: VecDeque::new(),
+                    threads: 0,
+                    idle: 0,
+                    shutdown: false,
+                }),
+                cond: Condvar::new(),
+            }),
+        }
+    }
+}
+
+impl BlockingPool {
+    /// create a pool builder
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// run the closure on a thread of the pool
+    ///
+    /// a panic in the closure is caught and returned as an error by the handle
+    ///
+    /// # Errors
+    ///
+    /// return an error if the queue is full or the thread can't be created
+    pub fn spawn<F, T>(&self, f: F) -> Result<BlockingHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let task = Box::new(move || {
+            let r = catch_unwind(AssertUnwindSafe(f)).map_err(panic_error);
+            // the handle may be dropped
+            let _ = tx.send(r);
+        });
+
+        let mut state = self.inner.state.lock().unwrap();
+        // all the threads are busy and too many tasks are waiting
+        if state.threads >= self.inner.max_threads
+            && state.queue.len() >= state.idle.saturating_add(self.inner.queue_limit)
+        {
+            return Err(err!("blocking pool queue is full"));
+        }
+        state.queue.push_back(task);
+        if state.idle > 0 {
+            self.inner.cond.notify_one();
+        }
+        if state.queue.len() > state.idle && state.threads < self.inner.max_threads {
+            let inner = self.inner.clone();
+            thread::Builder::new()
+                .name(self.inner.name.clone())
+                .spawn(move || inner.run())
+                .map_err(|e| {
+                    state.queue.pop_back();
+                    Error::from(e)
+                })?;
+            state.threads += 1;
+        }
+        Ok(BlockingHandle { rx })
+    }
+
+    /// the number of alive threads
+    pub fn threads(&self) -> usize {
+        self.inner.state.lock().unwrap().threads
+    }
+
+    /// the number of tasks waiting for a free thread
+    pub fn queued(&self) -> usize {
+        self.inner.state.lock().unwrap().queue.len()
+    }
+}
+
+impl Drop for BlockingPool {
+    fn drop(&mut self) {
+        // let the threads exit after the queue is drained
+        self.inner.state.lock().unwrap().shutdown = true;
+        self.inner.cond.notify_all();
+    }
+}
+
+impl fmt::Debug for BlockingPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.state.lock().unwrap();
+        f.debug_struct("BlockingPool")
+            .field("name", &self.inner.name)
+            .field("threads", &state.threads)
+            .field("idle", &state.idle)
+            .field("queued", &state.queue.len())
+            .finish()
+    }
+}
+
+fn panic_error(p: Box<dyn Any + Send>) -> Error {
+    match p.downcast_ref::<&str>() {
+        Some(s) => err!("blocking task panicked: {}", s),
+        None => match p.downcast_ref::<String>() {
+            Some(s) => err!("blocking task panicked: {}", s),
+            None => err!("blocking task panicked"),
+        },
+    }
+}
+
+/// A handle to the result of a blocking task
+///
+/// `join` blocks the current coroutine or thread until the task is done, so
+/// it can be used in `select!`. it can also be awaited in async code.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::blocking;
+/// use std::time::Duration;
+///
+/// let h = blocking::spawn(|| std::thread::sleep(Duration::from_secs(10))).unwrap();
+/// let (tx, rx) = mco::chan!();
+/// tx.send(1).unwrap();
+/// let id = mco::select! {
+///     _ = h.join() => {},
+///     _ = rx.recv() => {}
+/// };
+/// assert_eq!(id, 1);
+/// ```
+pub struct BlockingHandle<T> {
+    rx: Receiver<Result<T>>,
+}
+
+impl<T> BlockingHandle<T> {
+    /// return true if the task is done
+    pub fn is_done(&self) -> bool {
+        self.rx.is_ready()
+    }
+
+    /// wait for the task, returning its value or the panic as an error
+    pub fn join(self) -> Result<T> {
+        self.rx
+            .recv()
+            .unwrap_or_else(|_| Err(err!("blocking task is dropped")))
+    }
+}
+
+impl<T> Future for BlockingHandle<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T>> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|r| r.unwrap_or_else(|_| Err(err!("blocking task is dropped"))))
+    }
+}
+
+impl<T> fmt::Debug for BlockingHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("BlockingHandle { .. }")
+    }
+}
@@ -0,0 +1,554 @@
+//! Go style context
+//!
+//! a `Context` carries a cancellation signal, a deadline and request scoped
+//! values across coroutines. cancelling a context cancels all the contexts
+//! derived from it, and the coroutines bound to it by `Context::bind` or
+//! started by `Context::spawn` are cancelled with `Coroutine::cancel`, so
+//! even a coroutine parked in io would wake up and unwind.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use crate::coroutine::{self, Coroutine};
+use crate::join::JoinHandle;
+use crate::scheduler::{get_scheduler, SchedRef, TimerData};
+use crate::std::errors::Error;
+use crate::std::sync::channel::{channel, Receiver, Sender};
+use crate::std::time::clock;
+use crate::timeout_list::TimerHandle;
+
+/// The reason why a context is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContextError {
+    /// the context is cancelled by its `CancelFunc` or by its parent
+    Canceled,
+    /// the deadline of the context is passed
+    DeadlineExceeded,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContextError::Canceled => f.write_str("context canceled"),
+            ContextError::DeadlineExceeded => f.write_str("context deadline exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+impl From<ContextError> for Error {
+    fn from(e: ContextError) -> Self {
+        Error {
+            inner: e.to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CancelState {
+    // dropped when the context is done, which disconnects all the `done` receivers
+    done_tx: Option<Sender<()>>,
+    err: Option<ContextError>,
+    children: HashMap<usize, Weak<Inner>>,
+    bound: HashMap<usize, Coroutine>,
+    // the timer that fires the deadline
+    timer: Option<DeadlineTimer>,
+}
+
+// a callback of the timer thread, the scheduler is kept alive until the
+// timer is fired or deleted
+struct DeadlineTimer {
+    sched: SchedRef,
+    handle: TimerHandle<TimerData>,
+}
+
+impl DeadlineTimer {
+    fn stop(self) {
+        self.sched.del_timer(self.handle);
+    }
+}
+
+struct CancelCtx {
+    id: usize,
+    deadline: Option<Instant>,
+    done_rx: Mutex<Receiver<()>>,
+    state: Mutex<CancelState>,
+}
+
+enum Kind {
+    // never cancelled
+    Background,
+    Cancel(CancelCtx),
+    Value {
+        key: &'static str,
+        value: Box<dyn Any + Send + Sync>,
+    },
+}
+
+struct Inner {
+    parent: Option<Context>,
+    kind: Kind,
+}
+
+/// A Context carries a cancellation signal, a deadline and values
+///
+/// it's cheap to clone, all the clones share the same state.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::context;
+/// use std::time::Duration;
+///
+/// let (ctx, _cancel) = context::with_timeout(&context::background(), Duration::from_millis(10));
+/// let (_tx, rx) = mco::std::sync::channel::channel::<i32>();
+/// let id = mco::select! {
+///     _ = ctx.done().recv() => {},
+///     _ = rx.recv() => {}
+/// };
+/// assert_eq!(id, 0);
+/// assert_eq!(ctx.err(), Some(context::ContextError::DeadlineExceeded));
+/// ```
+#[derive(Clone)]
+pub struct Context {
+    inner: Arc<Inner>,
+}
+
+/// Cancels the context returned with it
+///
+/// calling it more than once does nothing, dropping it does not cancel the context
+#[derive(Clone)]
+pub struct CancelFunc {
+    inner: Weak<Inner>,
+}
+
+impl CancelFunc {
+    /// cancel the context and all the contexts derived from it
+    pub fn cancel(&self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.cancel(ContextError::Canceled, true);
+        }
+    }
+}
+
+impl fmt::Debug for CancelFunc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CancelFunc")
+    }
+}
+
+static BACKGROUND: Lazy<Context> = Lazy::new(|| Context {
+    inner: Arc::new(Inner {
+        parent: None,
+        kind: Kind::Background,
+    }),
+});
+
+// the `done` receiver of the contexts that are never cancelled
+static NEVER: Lazy<Mutex<Receiver<()>>> = Lazy::new(|| {
+    let (tx, rx) = channel();
+    // never disconnect the receiver
+    std::mem::forget(tx);
+    Mutex::new(rx)
+});
+
+/// return an empty context that is never cancelled and has no deadline and values
+///
+/// it's the root of all the derived contexts
+pub fn background() -> Context {
+    BACKGROUND.clone()
+}
+
+/// derive a context that is done when the returned `CancelFunc` is called
+/// or when the parent is done, whichever happens first
+pub fn with_cancel(parent: &Context) -> (Context, CancelFunc) {
+    Context::new_cancel(parent, None)
+}
+
+/// derive a context that is done at the deadline at the latest
+///
+/// the deadline of the derived context is never later than the parent's.
+/// it's measured by the clock installed by `clock::set_clock`, so a
+/// `MockClock` moves it forward like the other timers
+pub fn with_deadline(parent: &Context, deadline: Instant) -> (Context, CancelFunc) {
+    match parent.deadline() {
+        // the parent would be done earlier
+        Some(d) if d <= deadline => Context::new_cancel(parent, None),
+        _ => Context::new_cancel(parent, Some(deadline)),
+    }
+}
+
+/// derive a context that is done after `timeout` at the latest
+pub fn with_timeout(parent: &Context, timeout: Duration) -> (Context, CancelFunc) {
+    with_deadline(parent, clock::instant() + timeout)
+}
+
+/// derive a context that carries the value for the key
+///
+/// the derived context is done when the parent is done
+pub fn with_value<T: Any + Send + Sync>(parent: &Context, key: &'static str, value: T) -> Context {
+    Context {
+        inner: Arc::new(Inner {
+            parent: Some(parent.clone()),
+            kind: Kind::Value {
+                key,
+                value: Box::new(value),
+            },
+        }),
+    }
+}
+
+impl Context {
+    fn new_cancel(parent: &Context, deadline: Option<Instant>) -> (Context, CancelFunc) {
+        static ID: AtomicUsize = AtomicUsize::new(1);
+        let (tx, rx) = channel();
+        let inner = Arc::new(Inner {
+            parent: Some(parent.clone()),
+            kind: Kind::Cancel(CancelCtx {
+                id: ID.fetch_add(1, Ordering::Relaxed),
+                deadline: deadline.or_else(|| parent.deadline()),
+                done_rx: Mutex::new(rx),
+                state: Mutex::new(CancelState {
+                    done_tx: Some(tx),
+                    ..Default::default()
+                }),
+            }),
+        });
+
+        // register to the nearest cancellable ancestor
+        if let Some(p) = parent.inner.cancel_ctx() {
+            let mut state = p.state.lock().unwrap();
+            match state.err {
+                Some(err) => {
+                    drop(state);
+                    inner.cancel(err, false);
+                }
+                None => {
+                    state.children.insert(inner.id(), Arc::downgrade(&inner));
+                }
+            }
+        }
+
+        if let Some(deadline) = deadline {
+            let dur = deadline.saturating_duration_since(clock::instant());
+            if dur == Duration::from_secs(0) {
+                inner.cancel(ContextError::DeadlineExceeded, true);
+            } else {
+                let weak = Arc::downgrade(&inner);
+                let fire = Box::new(move || {
+                    if let Some(inner) = weak.upgrade() {
+                        inner.fire_deadline();
+                    }
+                });
+                // the timer is stored before it can fire, `fire_deadline`
+                // waits for the lock
+                let mut state = inner.cancel_ctx().unwrap().state.lock().unwrap();
+                if state.err.is_none() {
+                    let sched = SchedRef::new(get_scheduler());
+                    let handle = sched.add_timer_callback(dur, fire);
+                    state.timer = Some(DeadlineTimer { sched, handle });
+                }
+            }
+        }
+
+        let cancel = CancelFunc {
+            inner: Arc::downgrade(&inner),
+        };
+        (Context { inner }, cancel)
+    }
+
+    /// return a receiver that is disconnected when the context is done
+    ///
+    /// it never receives any value, so `recv` blocks until the context is done
+    /// and then returns an error. it can be used in `select!` like the go `ctx.Done()`.
+    /// the receiver of a context that is never cancelled blocks forever.
+    pub fn done(&self) -> Receiver<()> {
+        match self.inner.cancel_ctx() {
+            Some(c) => c.done_rx.lock().unwrap().clone(),
+            None => NEVER.lock().unwrap().clone(),
+        }
+    }
+
+    /// return the reason why the context is done, or `None` if it's not done yet
+    pub fn err(&self) -> Option<ContextError> {
+        self.inner
+            .cancel_ctx()
+            .and_then(|c| c.state.lock().unwrap().err)
+    }
+
+    /// return true if the context is done
+    pub fn is_done(&self) -> bool {
+        self.err().is_some()
+    }
+
+    /// return the time when the context would be done for the deadline
+    ///
+    /// it's an instant of the installed clock, see `with_deadline`
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.cancel_ctx().and_then(|c| c.deadline)
+    }
+
+    /// return the value of the key that is set by `with_value` on this context
+    /// or its nearest ancestor, `None` if not found or the type is not `T`
+    pub fn value<T: Any + Send + Sync>(&self, key: &str) -> Option<&T> {
+        let mut ctx = self;
+        loop {
+            if let Kind::Value { key: k, value } = &ctx.inner.kind {
+                if *k == key {
+                    return value.downcast_ref();
+                }
+            }
+            ctx = ctx.inner.parent.as_ref()?;
+        }
+    }
+
+    /// cancel the coroutine when the context is done
+    ///
+    /// the coroutine is cancelled at once if the context is already done.
+    /// a cancelled coroutine unwinds at the next blocking point, including
+    /// waiting for io, and joining it returns the `Cancel` panic.
+    /// the coroutine stays bound until `unbind` is called or the context is done
+    pub fn bind(&self, co: &Coroutine) {
+        let c = match self.inner.cancel_ctx() {
+            Some(c) => c,
+            None => return,
+        };
+        let mut state = c.state.lock().unwrap();
+        if state.err.is_some() {
+            drop(state);
+            return co.cancel();
+        }
+        state.bound.insert(co.id(), co.clone());
+    }
+
+    /// stop cancelling the coroutine when the context is done
+    pub fn unbind(&self, co: &Coroutine) {
+        if let Some(c) = self.inner.cancel_ctx() {
+            c.state.lock().unwrap().bound.remove(&co.id());
+        }
+    }
+
+    /// spawn a coroutine that is bound to the context until it returns
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::std::context;
+    /// use std::time::Duration;
+    ///
+    /// let (ctx, cancel) = context::with_cancel(&context::background());
+    /// let h = ctx.spawn(|_ctx| mco::coroutine::sleep(Duration::from_secs(10)));
+    /// cancel.cancel();
+    /// assert!(h.join().is_err());
+    /// ```
+    #[track_caller]
+    pub fn spawn<F, T>(&self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce(Context) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let ctx = self.clone();
+        coroutine::spawn(move || {
+            let co = coroutine::current();
+            ctx.bind(&co);
+            let _unbind = Unbind(&ctx, co);
+            f(ctx.clone())
+        })
+    }
+}
+
+// unbind the coroutine even if it's unwinding
+struct Unbind<'a>(&'a Context, Coroutine);
+
+impl Drop for Unbind<'_> {
+    fn drop(&mut self) {
+        self.0.unbind(&self.1);
+    }
+}
+
+impl Inner {
+    fn cancel_ctx(&self) -> Option<&CancelCtx> {
+        match &self.kind {
+            Kind::Background => None,
+            Kind::Cancel(c) => Some(c),
+            Kind::Value { .. } => self.parent.as_ref().and_then(|p| p.inner.cancel_ctx()),
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.cancel_ctx().map_or(0, |c| c.id)
+    }
+
+    // the nearest cancellable ancestor
+    fn parent_cancel_ctx(&self) -> Option<&CancelCtx> {
+        self.parent.as_ref().and_then(|p| p.inner.cancel_ctx())
+    }
+
+    fn fire_deadline(&self) {
+        if let Some(c) = self.cancel_ctx() {
+            // the timer is fired, it's dropped instead of deleted
+            c.state.lock().unwrap().timer.take();
+        }
+        self.cancel(ContextError::DeadlineExceeded, true);
+    }
+
+    fn cancel(&self, err: ContextError, remove_from_parent: bool) {
+        let c = match &self.kind {
+            Kind::Cancel(c) => c,
+            _ => return,
+        };
+        let mut state = c.state.lock().unwrap();
+        if state.err.is_some() {
+            return;
+        }
+        state.err = Some(err);
+        let done_tx = state.done_tx.take();
+        let children = std::mem::take(&mut state.children);
+        let bound = std::mem::take(&mut state.bound);
+        let timer = state.timer.take();
+        drop(state);
+
+        // wake up all the `done` receivers
+        drop(done_tx);
+        for child in children.values().filter_map(Weak::upgrade) {
+            child.cancel(err, false);
+        }
+        for co in bound.values() {
+            co.cancel();
+        }
+        if let Some(timer) = timer {
+            timer.stop();
+        }
+        if remove_from_parent {
+            if let Some(p) = self.parent_cancel_ctx() {
+                p.state.lock().unwrap().children.remove(&c.id);
+            }
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Kind::Cancel(c) = &self.kind {
+            let mut state = c.state.lock().unwrap();
+            let timer = state.timer.take();
+            if state.err.is_none() {
+                if let Some(p) = self.parent_cancel_ctx() {
+                    p.state.lock().unwrap().children.remove(&c.id);
+                }
+            }
+            drop(state);
+            if let Some(timer) = timer {
+                timer.stop();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("deadline", &self.deadline())
+            .field("err", &self.err())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancel_propagation() {
+        let (parent, cancel) = with_cancel(&background());
+        let child = with_value(&parent, "k", 1i32);
+        let (grand, _c) = with_cancel(&child);
+        assert_eq!(grand.value::<i32>("k"), Some(&1));
+        assert_eq!(grand.value::<u32>("k"), None);
+        assert_eq!(grand.err(), None);
+
+        let done = grand.done();
+        let h = crate::coroutine::spawn(move || done.recv().is_err());
+        cancel.cancel();
+        assert!(h.join().unwrap());
+        assert_eq!(grand.err(), Some(ContextError::Canceled));
+        assert_eq!(child.err(), Some(ContextError::Canceled));
+
+        // derived from a cancelled context
+        let (late, _c) = with_timeout(&grand, Duration::from_secs(10));
+        assert!(late.is_done());
+        assert!(late.done().recv().is_err());
+    }
+
+    #[test]
+    fn test_deadline() {
+        let (parent, _c) = with_timeout(&background(), Duration::from_secs(10));
+        let (child, cancel) = with_timeout(&parent, Duration::from_secs(20));
+        assert_eq!(child.deadline(), parent.deadline());
+        // the timers are driven by a `MockClock` in tests/clock.rs, here the
+        // timer is deleted and its callback is run directly
+        let c = parent.inner.cancel_ctx().unwrap();
+        let timer = c.state.lock().unwrap().timer.take();
+        timer.unwrap().stop();
+        parent.inner.fire_deadline();
+        assert!(child.done().recv().is_err());
+        assert_eq!(child.err(), Some(ContextError::DeadlineExceeded));
+        cancel.cancel();
+        assert_eq!(child.err(), Some(ContextError::DeadlineExceeded));
+        // the child is removed from the parent
+        assert!(c.state.lock().unwrap().children.is_empty());
+
+        // already expired
+        let (ctx, _c) = with_deadline(&background(), clock::instant());
+        assert_eq!(ctx.err(), Some(ContextError::DeadlineExceeded));
+    }
+
+    #[test]
+    fn test_cancel_deadline_timer() {
+        let (ctx, cancel) = with_timeout(&background(), Duration::from_secs(10));
+        let c = ctx.inner.cancel_ctx().unwrap();
+        assert!(c.state.lock().unwrap().timer.is_some());
+        cancel.cancel();
+        // the timer is deleted with the context
+        assert!(c.state.lock().unwrap().timer.is_none());
+        assert_eq!(ctx.err(), Some(ContextError::Canceled));
+    }
+
+    #[test]
+    fn test_bind_io() {
+        let (ctx, cancel) = with_cancel(&background());
+        let listener = crate::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let h = ctx.spawn(move |_| listener.accept().map(|_| ()));
+        // wait until it's parked on the io
+        while h.coroutine().wait_reason() != Some(crate::coroutine::WaitReason::Io) {
+            std::thread::yield_now();
+        }
+        cancel.cancel();
+        match h.join() {
+            Err(panic) => match panic.downcast_ref::<mco_gen::Error>() {
+                Some(mco_gen::Error::Cancel) => {}
+                _ => panic!("wrong panic type"),
+            },
+            Ok(_) => panic!("the accept should be cancelled"),
+        }
+    }
+
+    #[test]
+    fn test_background() {
+        let ctx = background();
+        assert_eq!(ctx.err(), None);
+        assert_eq!(ctx.deadline(), None);
+        assert!(ctx
+            .done()
+            .recv_timeout(Duration::from_millis(10))
+            .is_err());
+        assert!(ctx.err().is_none());
+    }
+}
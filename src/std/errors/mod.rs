@@ -129,3 +129,15 @@ impl<T> From<std::sync::mpsc::SendError<T>> for Error {
         return new(e.to_string());
     }
 }
+
+impl<T> From<crate::std::sync::channel::SendError<T>> for Error {
+    fn from(e: crate::std::sync::channel::SendError<T>) -> Self {
+        return new(e.to_string());
+    }
+}
+
+impl<T> From<crate::std::sync::channel::TrySendError<T>> for Error {
+    fn from(e: crate::std::sync::channel::TrySendError<T>) -> Self {
+        return new(e.to_string());
+    }
+}
@@ -7,6 +7,7 @@ pub mod io;
 #[macro_use]
 pub mod defer;
 pub mod blocking;
+pub mod context;
 pub mod lazy;
 pub mod pool;
 pub mod time;
@@ -0,0 +1,369 @@
+//! broadcast channel, every receiver sees every message
+//!
+//! the messages are kept in a bounded ring buffer and each receiver has its
+//! own cursor into it. a send never blocks, when the buffer is full the
+//! oldest message is overwritten, and a receiver that falls behind is told
+//! how many messages it missed before it continues from the oldest one.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::blocking::Blocker;
+use super::channel::SendError;
+use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
+use crate::park::ParkError;
+
+/// create a broadcast channel that buffers at most `capacity` messages
+///
+/// # Panics
+///
+/// panics if the capacity is zero
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::sync::broadcast::{self, RecvError};
+///
+/// let (tx, mut rx1) = broadcast::channel(2);
+/// let mut rx2 = tx.subscribe();
+/// tx.send(1).unwrap();
+/// assert_eq!(rx1.recv(), Ok(1));
+/// assert_eq!(rx2.recv(), Ok(1));
+///
+/// // rx1 falls behind, the oldest message is overwritten
+/// for i in 2..5 {
+///     tx.send(i).unwrap();
+/// }
+/// assert_eq!(rx1.recv(), Err(RecvError::Lagged(1)));
+/// assert_eq!(rx1.recv(), Ok(3));
+/// ```
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "broadcast channel capacity must be positive");
+    let shared = Arc::new(Shared {
+        capacity,
+        state: Mutex::new(State {
+            ring: VecDeque::with_capacity(capacity),
+            head: 0,
+            senders: 1,
+            receivers: 1,
+            waiters: Vec::new(),
+        }),
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+        next: 0,
+    };
+    (Sender { shared }, rx)
+}
+
+/// An error returned from `Receiver::recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvError {
+    /// all the senders are dropped and the receiver has seen every message
+    Closed,
+    /// the receiver falls behind and the number of messages are skipped,
+    /// the next receive returns the oldest message in the buffer
+    Lagged(u64),
+}
+
+/// An error returned from `Receiver::try_recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// there is no new message for the receiver
+    Empty,
+    /// all the senders are dropped and the receiver has seen every message
+    Closed,
+    /// the receiver falls behind and the number of messages are skipped
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvError::Closed => f.write_str("receiving on a closed channel"),
+            RecvError::Lagged(n) => write!(f, "receiver lagged by {} messages", n),
+        }
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Closed => f.write_str("receiving on a closed channel"),
+            TryRecvError::Lagged(n) => write!(f, "receiver lagged by {} messages", n),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+impl Error for TryRecvError {}
+
+struct State<T> {
+    ring: VecDeque<T>,
+    // the position of the first message in the ring
+    head: u64,
+    senders: usize,
+    receivers: usize,
+    // the receivers waiting for the next message
+    waiters: Vec<Arc<Blocker>>,
+}
+
+impl<T> State<T> {
+    fn wake_all(&mut self) {
+        for w in self.waiters.drain(..) {
+            let _ = w.unpark();
+        }
+    }
+}
+
+struct Shared<T> {
+    capacity: usize,
+    state: Mutex<State<T>>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // the lock is never held by user code, so it's never poisoned
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The sending half of a broadcast channel
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Clone> Sender<T> {
+    /// send the message to all the receivers, return the number of them
+    ///
+    /// it never blocks, the oldest message is overwritten if the buffer is
+    /// full. the message is returned back if there is no receiver.
+    pub fn send(&self, t: T) -> Result<usize, SendError<T>> {
+        let mut state = self.shared.lock();
+        if state.receivers == 0 {
+            return Err(SendError::Disconnected(t));
+        }
+        if state.ring.len() == self.shared.capacity {
+            state.ring.pop_front();
+            state.head += 1;
+        }
+        state.ring.push_back(t);
+        state.wake_all();
+        Ok(state.receivers)
+    }
+
+    /// create a receiver that sees the messages sent after this call
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock();
+        state.receivers += 1;
+        let next = state.head + state.ring.len() as u64;
+        Receiver {
+            shared: self.shared.clone(),
+            next,
+        }
+    }
+
+    /// the number of active receivers
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.wake_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("broadcast::Sender { .. }")
+    }
+}
+
+/// The receiving half of a broadcast channel
+///
+/// a cloned receiver starts from the same position as the original one
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the position of the next message to receive
+    next: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    fn try_recv_locked(&mut self, state: &State<T>) -> Result<T, TryRecvError> {
+        if self.next < state.head {
+            let missed = state.head - self.next;
+            self.next = state.head;
+            return Err(TryRecvError::Lagged(missed));
+        }
+        let idx = (self.next - state.head) as usize;
+        match state.ring.get(idx) {
+            Some(t) => {
+                self.next += 1;
+                Ok(t.clone())
+            }
+            None if state.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// receive the next message without blocking
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let shared = self.shared.clone();
+        let state = shared.lock();
+        self.try_recv_locked(&state)
+    }
+
+    /// block the coroutine or thread until the next message is available
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        let shared = self.shared.clone();
+        let mut state = shared.lock();
+        loop {
+            match self.try_recv_locked(&state) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Empty) => {}
+            }
+            // an unpark before the park is not lost
+            let cur = Blocker::current();
+            state.waiters.push(cur.clone());
+            drop(state);
+            let _w = wait_for(WaitReason::ChanRecv);
+            if let Err(ParkError::Canceled) = cur.park(None) {
+                trigger_cancel_panic();
+            }
+            state = shared.lock();
+        }
+    }
+
+    /// the number of messages that are not received yet
+    pub fn len(&self) -> usize {
+        let state = self.shared.lock();
+        let tail = state.head + state.ring.len() as u64;
+        (tail - self.next.max(state.head)) as usize
+    }
+
+    /// return true if there is no message to receive
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().receivers -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("broadcast::Receiver")
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_broadcast_all_receivers() {
+        let (tx, mut rx1) = channel(16);
+        let mut rx2 = rx1.clone();
+        for i in 0..10 {
+            assert_eq!(tx.send(i).unwrap(), 2);
+        }
+        let mut rx3 = tx.subscribe();
+        tx.send(10).unwrap();
+        for i in 0..11 {
+            assert_eq!(rx1.recv(), Ok(i));
+            assert_eq!(rx2.try_recv(), Ok(i));
+        }
+        assert_eq!(rx1.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(rx3.len(), 1);
+        assert_eq!(rx3.recv(), Ok(10));
+
+        drop(tx);
+        assert_eq!(rx1.recv(), Err(RecvError::Closed));
+        assert_eq!(rx3.try_recv(), Err(TryRecvError::Closed));
+    }
+
+    #[test]
+    fn test_broadcast_lagged() {
+        let (tx, mut rx) = channel(4);
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.len(), 4);
+        assert_eq!(rx.recv(), Err(RecvError::Lagged(6)));
+        for i in 6..10 {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+        assert!(rx.is_empty());
+
+        drop(rx);
+        assert_eq!(tx.receiver_count(), 0);
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+    }
+
+    #[test]
+    fn test_broadcast_blocking() {
+        let (tx, rx) = channel(8);
+        let hs: Vec<_> = (0..4)
+            .map(|_| {
+                let mut rx = rx.clone();
+                crate::coroutine::spawn(move || {
+                    let mut sum = 0;
+                    while let Ok(v) = rx.recv() {
+                        sum += v;
+                    }
+                    sum
+                })
+            })
+            .collect();
+        drop(rx);
+        thread::spawn(move || {
+            for i in 0..5 {
+                thread::sleep(Duration::from_millis(5));
+                tx.send(i).unwrap();
+            }
+        });
+        for h in hs {
+            assert_eq!(h.join().unwrap(), 10);
+        }
+    }
+}
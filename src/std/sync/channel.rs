@@ -5,13 +5,24 @@
 //! each receiver would consume one data each time so that other receivers
 //! would not see that the same data any more
 
+use std::collections::VecDeque;
+use std::error::Error;
 use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
-use std::sync::Arc;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
 use std::time::Duration;
 
-use super::Semphore;
+use parking_lot::Mutex;
+
+use super::{AtomicOption, Semphore, SyncFlag};
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
+use crate::metrics::{self, ChannelStat, Counter};
+use crate::runtime::ChannelMetrics;
 use crate::std::queue::seg_queue::SegQueue;
 
 /// Create an unbounded channel. if If you want to limit the number of messages, use bounded channel_buf()
@@ -31,7 +42,34 @@ pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
 
 /// Create a bounded channel
 pub fn bounded<T>(buf: usize) -> (Sender<T>, Receiver<T>) {
-    let a = Arc::new(MPMCBuffer::new_buffer(buf));
+    let a = Arc::new(MPMCBuffer::new_buffer(buf, None));
+    (Sender::new(a.clone()), Receiver::new(a))
+}
+
+/// Create a bounded channel with a name, `usize::MAX` for unbounded
+///
+/// a named channel is listed in `RuntimeMetrics::channels` until it's
+/// dropped, so the one causing backpressure can be found.
+///
+/// # Examples
+///
+/// ```
+/// use mco::runtime::Runtime;
+/// use mco::std::sync::channel;
+///
+/// let (tx, rx) = channel::named("jobs", 1);
+/// tx.send(1).unwrap();
+/// let metrics = Runtime::current().metrics();
+/// let jobs = metrics.channels.iter().find(|c| c.name.as_deref() == Some("jobs")).unwrap();
+/// assert_eq!(jobs.capacity, Some(1));
+/// assert_eq!(jobs.len, 1);
+/// assert_eq!(rx.recv(), Ok(1));
+/// assert_eq!(rx.metrics().received, 1);
+/// ```
+pub fn named<T: Send + 'static>(name: impl Into<String>, buf: usize) -> (Sender<T>, Receiver<T>) {
+    let a = Arc::new(MPMCBuffer::new_buffer(buf, Some(name.into())));
+    let stat: Weak<MPMCBuffer<T>> = Arc::downgrade(&a);
+    metrics::register_channel(stat);
     (Sender::new(a.clone()), Receiver::new(a))
 }
 
@@ -62,70 +100,455 @@ macro_rules! chan {
     };
 }
 
+/// An error returned from `Sender::send`, the message is returned back
+///
+/// it replaces `std::sync::mpsc::SendError`, use `into_inner` to get the
+/// message whatever the reason is
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SendError<T> {
+    /// all the receivers are dropped
+    Disconnected(T),
+    /// the channel is closed by `close`
+    Closed(T),
+}
+
+impl<T> SendError<T> {
+    /// get the message that failed to send
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Disconnected(t) | SendError::Closed(t) => t,
+        }
+    }
+
+    /// return true if the channel is closed by `close`
+    pub fn is_closed(&self) -> bool {
+        matches!(self, SendError::Closed(_))
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Disconnected(_) => f.write_str("Disconnected(..)"),
+            SendError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Disconnected(_) => f.write_str("sending on a disconnected channel"),
+            SendError::Closed(_) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// An error returned from `Sender::try_send`, the message is returned back
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// the buffer of the channel is full
+    Full(T),
+    /// all the receivers are dropped
+    Disconnected(T),
+    /// the channel is closed by `close`
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// get the message that failed to send
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(t) | TrySendError::Disconnected(t) | TrySendError::Closed(t) => t,
+        }
+    }
+
+    /// return true if the channel is closed by `close`
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TrySendError::Closed(_))
+    }
+
+    /// return true if the buffer of the channel is full
+    pub fn is_full(&self) -> bool {
+        matches!(self, TrySendError::Full(_))
+    }
+}
+
+impl<T> From<SendError<T>> for TrySendError<T> {
+    fn from(e: SendError<T>) -> Self {
+        match e {
+            SendError::Disconnected(t) => TrySendError::Disconnected(t),
+            SendError::Closed(t) => TrySendError::Closed(t),
+        }
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("Full(..)"),
+            TrySendError::Disconnected(_) => f.write_str("Disconnected(..)"),
+            TrySendError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("sending on a full channel"),
+            TrySendError::Disconnected(_) => f.write_str("sending on a disconnected channel"),
+            TrySendError::Closed(_) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+// a message sent by a blocked sender of an unbuffered channel, the sender
+// is woken up when it's taken, or when all the receivers are dropped or the
+// channel is closed and the value is left for the sender to get it back.
+// the value is also taken back by a canceled sender, then the empty message
+// is skipped
+struct Handoff<T> {
+    value: AtomicOption<T>,
+    done: SyncFlag,
+}
+
+enum Msg<T> {
+    Value(T),
+    Handoff(Arc<Handoff<T>>),
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// MPMCBuffer
 /// /////////////////////////////////////////////////////////////////////////////
 struct MPMCBuffer<T> {
-    buffer: SegQueue<T>,
+    buffer: SegQueue<Msg<T>>,
     // chan buffer length limit. Exceeding this limit will be wait.
+    // zero means unbuffered, a send is finished only when it's received
     buffer_limit: usize,
+    // the name given by `named`
+    name: Option<String>,
+    // the number of receivers blocked for a message
+    recv_waiting: AtomicUsize,
+    // the number of senders blocked for room, or for an unbuffered
+    // channel until the message is received
+    send_waiting: AtomicUsize,
+    // the total number of messages sent and received
+    sent: Counter,
+    received: Counter,
     // thread/coroutine for wake up
     wake_recv: Semphore,
     // thread/coroutine for wake up
     wake_sender: Semphore,
+    // the tasks that awaiting for data, keyed by the id of their `RecvFuture`
+    wakers: Mutex<VecDeque<(usize, Waker)>>,
+    // the id of the next `RecvFuture`
+    next_waker: AtomicUsize,
     // The number of sender channels which are currently using this queue.
     sender_num: AtomicUsize,
     // The number of receiver
     receiver_num: AtomicUsize,
+    // set by `close`, no more message is accepted
+    closed: AtomicBool,
+    // the messages of the blocked unbuffered senders, they are woken up by `close`
+    handoffs: Mutex<Vec<Arc<Handoff<T>>>>,
 }
 
 impl<T> MPMCBuffer<T> {
     /// have buffer channel. If the buffered message exceeds the limit, the sender blocks until the message is consumed
-    pub fn new_buffer(buffer: usize) -> MPMCBuffer<T> {
+    pub fn new_buffer(buffer: usize, name: Option<String>) -> MPMCBuffer<T> {
         MPMCBuffer {
             buffer: SegQueue::new(),
             wake_recv: Semphore::new(0),
             wake_sender: Semphore::new(0),
+            wakers: Mutex::new(VecDeque::new()),
+            next_waker: AtomicUsize::new(0),
             buffer_limit: buffer,
+            name,
+            recv_waiting: AtomicUsize::new(0),
+            send_waiting: AtomicUsize::new(0),
+            sent: Counter::default(),
+            received: Counter::default(),
             sender_num: AtomicUsize::new(1),
             receiver_num: AtomicUsize::new(1),
+            closed: AtomicBool::new(false),
+            handoffs: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    // no more message would come, either closed or all the senders are dropped
+    #[inline]
+    fn is_finished(&self) -> bool {
+        self.is_closed() || self.sender_num.load(Ordering::Acquire) == 0
+    }
+
+    /// close the channel, return false if it's already closed
+    ///
+    /// the blocked senders and receivers are all woken up
+    pub fn close(&self) -> bool {
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        // the unbuffered senders get their values back if not received yet
+        for h in self.handoffs.lock().drain(..) {
+            h.done.fire();
+        }
+        while self.wake_recv.get_value() == 0 {
+            self.wake_recv.post();
+        }
+        while self.wake_sender.get_value() == 0 {
+            self.wake_sender.post();
         }
+        self.wake_tasks();
+        true
+    }
+
+    #[inline]
+    fn is_unbuffered(&self) -> bool {
+        self.buffer_limit == 0
+    }
+
+    // a message can be sent without blocking, for an unbuffered channel
+    // there must be a blocked receiver that is not served yet
+    #[inline]
+    fn has_room(&self) -> bool {
+        let limit = match self.is_unbuffered() {
+            true => self.recv_waiting.load(Ordering::SeqCst),
+            false => self.buffer_limit,
+        };
+        self.buffer.len() < limit
+    }
+
+    // take a message, wake up its sender for an unbuffered channel
+    fn pop(&self) -> Option<T> {
+        loop {
+            match self.buffer.pop()? {
+                Msg::Value(t) => {
+                    self.received.inc();
+                    return Some(t);
+                }
+                Msg::Handoff(h) => {
+                    // the canceled sender has taken the wakeup of it
+                    if let Some(t) = h.value.take() {
+                        self.received.inc();
+                        h.done.fire();
+                        return Some(t);
+                    }
+                }
+            }
+        }
+    }
+
+    // push a message that can be sent without blocking
+    fn push(&self, t: T) {
+        self.buffer.push(Msg::Value(t));
+        self.sent.inc();
+        self.wake_recv.post();
+        self.wake_task();
+    }
+
+    // drop all the messages, the blocked senders get their values back
+    fn clear(&self) {
+        while let Some(msg) = self.buffer.pop() {
+            if let Msg::Handoff(h) = msg {
+                h.done.fire();
+            }
+        }
+    }
+
+    // mark the receiver as blocked for a message until the guard is dropped,
+    // the senders that wait for it are woken up for an unbuffered channel
+    fn recv_waiting(&self) -> Waiting<'_> {
+        let w = Waiting::new(&self.recv_waiting);
+        // at most one pending wakeup, the woken sender re-checks the room
+        if self.is_unbuffered() && self.wake_sender.get_value() == 0 {
+            self.wake_sender.post();
+        }
+        w
+    }
+
+    // hand off the message to a receiver and wait until it's taken
+    fn send_unbuffered(&self, t: T) -> Result<(), SendError<T>> {
+        let h = {
+            let mut handoffs = self.handoffs.lock();
+            // checked under the lock, or `close` may miss the message
+            if self.is_closed() {
+                return Err(SendError::Closed(t));
+            }
+            let h = Arc::new(Handoff {
+                value: AtomicOption::some(t),
+                done: SyncFlag::new(),
+            });
+            handoffs.push(h.clone());
+            h
+        };
+        self.buffer.push(Msg::Handoff(h.clone()));
+        // the last receiver may be dropped before the push
+        if self.receiver_num.load(Ordering::Acquire) == 0 {
+            self.clear();
+        }
+        self.wake_recv.post();
+        self.wake_task();
+        {
+            let _w = wait_for(WaitReason::ChanSend);
+            let _s = Waiting::new(&self.send_waiting);
+            let retract = Retract { chan: self, h: &h };
+            h.done.wait();
+            std::mem::forget(retract);
+        }
+        self.remove_handoff(&h);
+        match h.value.take() {
+            None => {
+                self.sent.inc();
+                Ok(())
+            }
+            Some(t) if self.is_closed() => Err(SendError::Closed(t)),
+            Some(t) => Err(SendError::Disconnected(t)),
+        }
+    }
+
+    // the sender is not blocked for the message any more
+    fn remove_handoff(&self, h: &Handoff<T>) {
+        self.handoffs.lock().retain(|p| !std::ptr::eq(&**p, h));
     }
 
     /// send one message. If the length limit is exceeded or chan closed, wait for the message to be consumed
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        if self.is_closed() {
+            return Err(SendError::Closed(t));
+        }
         if self.receiver_num.load(Ordering::Acquire) == 0 {
-            return Err(SendError(t));
+            return Err(SendError::Disconnected(t));
+        }
+        if self.is_unbuffered() {
+            return self.send_unbuffered(t);
         }
         loop {
             if self.buffer.len() >= self.buffer_limit {
+                let _w = wait_for(WaitReason::ChanSend);
+                let _s = Waiting::new(&self.send_waiting);
                 self.wake_sender.wait();
+                if self.is_closed() {
+                    // pass the wakeup to the next blocked sender
+                    self.wake_sender.post();
+                    return Err(SendError::Closed(t));
+                }
             } else {
                 break;
             }
         }
-        self.buffer.push(t);
-        self.wake_recv.post();
+        self.push(t);
         Ok(())
     }
 
     /// try send one message.If the length limit is exceeded or chan closed, return a error
-    pub fn try_send(&self, t: T) -> Result<(), SendError<T>> {
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        if self.is_closed() {
+            return Err(TrySendError::Closed(t));
+        }
         if self.receiver_num.load(Ordering::Acquire) == 0 {
-            return Err(SendError(t));
+            return Err(TrySendError::Disconnected(t));
         }
-        if self.buffer.len() >= self.buffer_limit {
-            return Err(SendError(t));
+        if !self.has_room() {
+            return Err(TrySendError::Full(t));
         }
-        self.buffer.push(t);
-        self.wake_recv.post();
+        self.push(t);
         Ok(())
     }
 
-    /// wake one sender
+    /// wait until the message can be sent or the channel is closed, then send
+    /// it only if `claim` returns true, the message is dropped otherwise
+    fn send_claim(&self, t: T, claim: impl FnOnce() -> bool) -> Option<Result<(), SendError<T>>> {
+        let mut woken = false;
+        loop {
+            if self.is_closed() {
+                return claim().then(|| Err(SendError::Closed(t)));
+            }
+            if self.receiver_num.load(Ordering::Acquire) == 0 {
+                return claim().then(|| Err(SendError::Disconnected(t)));
+            }
+            if self.has_room() {
+                if !claim() {
+                    // pass the wakeup to the next blocked sender
+                    if woken {
+                        self.wake_sender.post();
+                    }
+                    return None;
+                }
+                self.push(t);
+                return Some(Ok(()));
+            }
+            let _w = wait_for(WaitReason::ChanSend);
+            let _s = Waiting::new(&self.send_waiting);
+            self.wake_sender.wait();
+            woken = true;
+        }
+    }
+
+    /// wake all the awaiting tasks, they would try to receive again
+    fn wake_tasks(&self) {
+        let wakers = std::mem::take(&mut *self.wakers.lock());
+        for (_, w) in wakers {
+            w.wake();
+        }
+    }
+
+    /// wake the first awaiting task for a new message
+    fn wake_task(&self) {
+        let waker = self.wakers.lock().pop_front();
+        if let Some((_, w)) = waker {
+            w.wake();
+        }
+    }
+
+    // return false if the waker is not there, it's already woken up
+    fn remove_waker(&self, id: usize) -> bool {
+        let mut wakers = self.wakers.lock();
+        match wakers.iter().position(|(i, _)| *i == id) {
+            Some(pos) => wakers.remove(pos).is_some(),
+            None => false,
+        }
+    }
+
+    fn poll_recv(&self, cx: &mut Context<'_>, id: usize) -> Poll<Result<T, RecvError>> {
+        match self.try_recv() {
+            Ok(data) => return Poll::Ready(Ok(data)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => {}
+        }
+        // register the waker first, a re-poll only replaces it
+        {
+            let mut wakers = self.wakers.lock();
+            match wakers.iter_mut().find(|(i, _)| *i == id) {
+                Some((_, w)) => w.clone_from(cx.waker()),
+                None => wakers.push_back((id, cx.waker().clone())),
+            }
+        }
+        // re-check the data
+        let ret = match self.try_recv() {
+            Ok(data) => Ok(data),
+            Err(TryRecvError::Disconnected) => Err(RecvError),
+            Err(TryRecvError::Empty) => return Poll::Pending,
+        };
+        self.remove_waker(id);
+        Poll::Ready(ret)
+    }
+
+    /// wake one sender, the unbuffered senders are woken up by the receivers
     #[inline]
     fn wake_sender(&self) {
-        self.wake_sender.post();
+        if !self.is_unbuffered() {
+            self.wake_sender.post();
+        }
     }
 
     /// received a message. If the message is empty, a wait is entered, and an error is returned if the channel is closed
@@ -137,6 +560,8 @@ impl<T> MPMCBuffer<T> {
             Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
         }
 
+        let _w = wait_for(WaitReason::ChanRecv);
+        let _r = self.recv_waiting();
         match dur {
             None => self.wake_recv.wait(),
             Some(t) => {
@@ -146,35 +571,64 @@ impl<T> MPMCBuffer<T> {
             }
         }
 
-        match self.buffer.pop() {
+        match self.pop() {
             Some(data) => {
                 self.wake_sender();
                 Ok(data)
             }
-            None => match self.sender_num.load(Ordering::Acquire) {
-                0 => Err(RecvTimeoutError::Disconnected),
-                _n => unreachable!("mpmc recv found no data"),
-            },
+            None if self.is_finished() => {
+                // pass the wakeup to the next blocked receiver
+                self.wake_recv.post();
+                Err(RecvTimeoutError::Disconnected)
+            }
+            None => unreachable!("mpmc recv found no data"),
+        }
+    }
+
+    /// wait until a message is ready or the channel is finished, then take it
+    /// only if `claim` returns true, used by `Select` so that the message is
+    /// left in the channel when another operation is selected
+    fn recv_claim(&self, claim: impl FnOnce() -> bool) -> Option<Result<T, RecvError>> {
+        {
+            let _w = wait_for(WaitReason::ChanRecv);
+            let _r = self.recv_waiting();
+            self.wake_recv.wait();
+        }
+        if !claim() {
+            self.wake_recv.post();
+            return None;
+        }
+        match self.pop() {
+            Some(data) => {
+                self.wake_sender();
+                Some(Ok(data))
+            }
+            None if self.is_finished() => {
+                self.wake_recv.post();
+                Some(Err(RecvError))
+            }
+            None => unreachable!("mpmc recv found no data"),
         }
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         if !self.wake_recv.try_wait() {
-            return match self.sender_num.load(Ordering::Acquire) {
-                0 => Err(TryRecvError::Disconnected),
-                _ => Err(TryRecvError::Empty),
+            return match self.is_finished() {
+                true => Err(TryRecvError::Disconnected),
+                false => Err(TryRecvError::Empty),
             };
         }
 
-        match self.buffer.pop() {
+        match self.pop() {
             Some(data) => {
                 self.wake_sender();
                 Ok(data)
             }
-            None => match self.sender_num.load(Ordering::Acquire) {
-                0 => Err(TryRecvError::Disconnected),
-                _ => unreachable!("mpmc try_recv found no data"),
-            },
+            None if self.is_finished() => {
+                self.wake_recv.post();
+                Err(TryRecvError::Disconnected)
+            }
+            None => unreachable!("mpmc try_recv found no data"),
         }
     }
 
@@ -190,6 +644,7 @@ impl<T> MPMCBuffer<T> {
                 while self.wake_recv.get_value() == 0 {
                     self.wake_recv.post();
                 }
+                self.wake_tasks();
             }
             n if n > 1 => {}
             n => panic!("bad number of send_ports left {}", n),
@@ -204,7 +659,7 @@ impl<T> MPMCBuffer<T> {
         match self.receiver_num.fetch_sub(1, Ordering::SeqCst) {
             1 => {
                 // there is no receiver any more, clear the data
-                while self.buffer.pop().is_some() {}
+                self.clear();
             }
             n if n > 1 => {}
             n => panic!("bad number of recv_ports left {}", n),
@@ -225,6 +680,63 @@ impl<T> MPMCBuffer<T> {
     }
 }
 
+impl<T: Send> ChannelStat for MPMCBuffer<T> {
+    fn metrics(&self) -> ChannelMetrics {
+        self.snapshot()
+    }
+}
+
+impl<T> MPMCBuffer<T> {
+    fn snapshot(&self) -> ChannelMetrics {
+        ChannelMetrics {
+            name: self.name.clone(),
+            capacity: (self.buffer_limit != usize::MAX).then_some(self.buffer_limit),
+            len: self.buffer.len(),
+            sent: self.sent.get(),
+            received: self.received.get(),
+            blocked_senders: self.send_waiting.load(Ordering::Relaxed),
+            blocked_receivers: self.recv_waiting.load(Ordering::Relaxed),
+            senders: self.sender_num(),
+            receivers: self.receiver_num(),
+            closed: self.is_closed(),
+        }
+    }
+}
+
+// count a blocked sender or receiver until it's dropped
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(cnt: &'a AtomicUsize) -> Self {
+        cnt.fetch_add(1, Ordering::SeqCst);
+        Waiting(cnt)
+    }
+}
+
+// take back the message of an unbuffered sender that is canceled, like a
+// `send` arm of `select!` that is not selected. only possible when its
+// wakeup is not taken by a receiver yet
+struct Retract<'a, T> {
+    chan: &'a MPMCBuffer<T>,
+    h: &'a Handoff<T>,
+}
+
+impl<'a, T> Drop for Retract<'a, T> {
+    fn drop(&mut self) {
+        self.chan.remove_handoff(self.h);
+        if self.chan.wake_recv.try_wait() && self.h.value.take().is_none() {
+            // already received, the wakeup belongs to another message
+            self.chan.wake_recv.post();
+        }
+    }
+}
+
+impl<'a> Drop for Waiting<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 impl<T> Drop for MPMCBuffer<T> {
     fn drop(&mut self) {
         assert_eq!(self.sender_num.load(Ordering::Acquire), 0);
@@ -242,6 +754,16 @@ impl<T> Receiver<T> {
         self.inner.remain()
     }
 
+    /// the name given by `named`
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name.as_deref()
+    }
+
+    /// a snapshot of the counters of the channel
+    pub fn metrics(&self) -> ChannelMetrics {
+        self.inner.snapshot()
+    }
+
     /// Number of channel senders
     pub fn sender_num(&self) -> usize {
         self.inner.sender_num()
@@ -251,6 +773,18 @@ impl<T> Receiver<T> {
     pub fn receiver_num(&self) -> usize {
         self.inner.receiver_num()
     }
+
+    /// close the channel, see `Sender::close`
+    pub fn close(&self) -> bool {
+        self.inner.close()
+    }
+
+    /// return true if the channel is closed by `close`
+    ///
+    /// the receivers can still get the buffered messages after closed
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
 }
 
 unsafe impl<T: Send> Send for Receiver<T> {}
@@ -268,6 +802,41 @@ pub struct IntoIter<T> {
     inner: Receiver<T>,
 }
 
+/// The future returned by `Receiver::recv_async`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct RecvFuture<'a, T: 'a> {
+    inner: &'a Receiver<T>,
+    // the key of its waker in the channel
+    id: usize,
+    // set when it's pending, the waker is registered in the channel
+    registered: bool,
+}
+
+impl<'a, T> Future for RecvFuture<'a, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let chan = &self.inner.inner;
+        let ret = chan.poll_recv(cx, self.id);
+        // the waker of an earlier poll may be left if it's not woken up
+        if ret.is_ready() && self.registered {
+            chan.remove_waker(self.id);
+        }
+        self.registered = ret.is_pending();
+        ret
+    }
+}
+
+impl<'a, T> Drop for RecvFuture<'a, T> {
+    fn drop(&mut self) {
+        let chan = &self.inner.inner;
+        // woken up for a message but dropped before taking it, pass it on
+        if self.registered && !chan.remove_waker(self.id) {
+            chan.wake_task();
+        }
+    }
+}
+
 pub struct Sender<T> {
     inner: Arc<MPMCBuffer<T>>,
 }
@@ -287,6 +856,37 @@ impl<T> Sender<T> {
     pub fn receiver_num(&self) -> usize {
         self.inner.receiver_num()
     }
+
+    /// close the channel like the go `close(ch)`, return false if it's already closed
+    ///
+    /// the following sends return `SendError::Closed`, the blocked senders
+    /// are woken up with the same error. the receivers get the buffered
+    /// messages first, after that `recv` returns an error and the iterators
+    /// stop. unlike go closing twice doesn't panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::std::sync::channel::SendError;
+    ///
+    /// let (tx, rx) = mco::chan!();
+    /// let tx2 = tx.clone();
+    /// let h = mco::co!(move || rx.iter().sum::<i32>());
+    /// tx.send(1).unwrap();
+    /// tx2.send(2).unwrap();
+    /// assert!(tx.close());
+    /// assert_eq!(tx2.send(3), Err(SendError::Closed(3)));
+    /// // the receiver stops while the senders are still alive
+    /// assert_eq!(h.join().unwrap(), 3);
+    /// ```
+    pub fn close(&self) -> bool {
+        self.inner.close()
+    }
+
+    /// return true if the channel is closed by `close`
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
 }
 
 /// /////////////////////////////////////////////////////////////////////////////
@@ -299,12 +899,19 @@ impl<T> Sender<T> {
     }
 
     /// send one message. If the length limit is exceeded or chan closed, wait for the message to be consumed
+    ///
+    /// for an unbuffered channel, `chan!(0)`, it waits until a receiver takes
+    /// the message like go, `close` wakes it up with `SendError::Closed` if
+    /// the message is not taken yet. the messages sent before `close` are
+    /// still delivered
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
         self.inner.send(t)
     }
 
     /// try send one message.If the length limit is exceeded or chan closed, return a error
-    pub fn try_send(&self, t: T) -> Result<(), SendError<T>> {
+    ///
+    /// for an unbuffered channel it only succeeds when a receiver is blocked
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
         self.inner.try_send(t)
     }
 
@@ -312,6 +919,27 @@ impl<T> Sender<T> {
     pub fn pressure(&self) -> usize {
         self.inner.wake_recv.get_value()
     }
+
+    /// the name given by `named`
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name.as_deref()
+    }
+
+    /// a snapshot of the counters of the channel
+    pub fn metrics(&self) -> ChannelMetrics {
+        self.inner.snapshot()
+    }
+
+    /// wait until the message can be sent or the channel is closed, then send
+    /// it only if `claim` returns true, used by `select!` and `Select`
+    #[doc(hidden)]
+    pub fn send_claim(
+        &self,
+        t: T,
+        claim: impl FnOnce() -> bool,
+    ) -> Option<Result<(), SendError<T>>> {
+        self.inner.send_claim(t, claim)
+    }
 }
 
 impl<T> Clone for Sender<T> {
@@ -359,6 +987,30 @@ impl<T> Receiver<T> {
         self.inner.recv(Some(timeout))
     }
 
+    /// receive a message in async code, it returns an error when the channel is closed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mco::coroutine::block_on;
+    ///
+    /// let (tx, rx) = mco::chan!();
+    /// mco::co!(move || tx.send(1).unwrap());
+    /// let v = block_on(async { rx.recv_async().await });
+    /// assert_eq!(v, Ok(1));
+    /// ```
+    pub fn recv_async(&self) -> RecvFuture<'_, T> {
+        RecvFuture {
+            inner: self,
+            id: self.inner.next_waker.fetch_add(1, Ordering::Relaxed),
+            registered: false,
+        }
+    }
+
+    pub(crate) fn recv_claim(&self, claim: impl FnOnce() -> bool) -> Option<Result<T, RecvError>> {
+        self.inner.recv_claim(claim)
+    }
+
     pub fn iter(&self) -> Iter<T> {
         Iter { inner: self }
     }
@@ -452,7 +1104,7 @@ mod tests {
         let wg_clone = wg.clone();
         let result = Arc::new(std::sync::Mutex::new(Duration::from_secs(0)));
         let result1 = result.clone();
-        co!(move || {
+        let h = co!(move || {
             tx.send(1);
             drop(wg_clone);
             let now = std::time::Instant::now();
@@ -464,6 +1116,7 @@ mod tests {
         sleep(Duration::from_secs(2));
         rx.recv().unwrap();
         rx.recv().unwrap();
+        h.join().unwrap();
         let d = result.lock().unwrap();
         assert_eq!(d.as_secs() >= 2, true);
     }
@@ -1197,4 +1850,167 @@ mod tests {
         }
         assert_eq!(rx1.try_recv().is_err(), true);
     }
+
+    #[test]
+    fn close_drain() {
+        let (tx, rx) = channel::<i32>();
+        let _tx2 = tx.clone();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert!(!tx.is_closed());
+        assert!(tx.close());
+        assert!(!rx.close());
+        assert!(rx.is_closed());
+        assert_eq!(tx.send(3), Err(SendError::Closed(3)));
+        assert_eq!(tx.try_send(4), Err(TrySendError::Closed(4)));
+        // the buffered messages are still received
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn close_wakeup() {
+        let (tx, rx) = bounded::<i32>(1);
+        tx.send(0).unwrap();
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+        let senders: Vec<_> = (0..3)
+            .map(|i| {
+                let tx = tx.clone();
+                co!(move || tx.send(i))
+            })
+            .collect();
+        sleep(Duration::from_millis(50));
+        rx.close();
+        for h in senders {
+            assert!(h.join().unwrap().unwrap_err().is_closed());
+        }
+
+        // the senders are still alive, only close stops the receivers
+        let (tx, rx) = channel::<i32>();
+        let hs: Vec<_> = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+                co!(move || rx.recv())
+            })
+            .collect();
+        sleep(Duration::from_millis(50));
+        tx.close();
+        for h in hs {
+            assert_eq!(h.join().unwrap(), Err(RecvError));
+        }
+    }
+
+    #[test]
+    fn channel_metrics() {
+        let (tx, rx) = named::<i32>("test_channel_metrics", 1);
+        assert_eq!(tx.name(), Some("test_channel_metrics"));
+        tx.send(0).unwrap();
+        let senders: Vec<_> = (1..3)
+            .map(|i| {
+                let tx = tx.clone();
+                co!(move || tx.send(i))
+            })
+            .collect();
+        sleep(Duration::from_millis(50));
+        let m = rx.metrics();
+        assert_eq!(m.capacity, Some(1));
+        assert_eq!((m.len, m.sent, m.received), (1, 1, 0));
+        assert_eq!((m.blocked_senders, m.blocked_receivers), (2, 0));
+        assert_eq!((m.senders, m.receivers), (3, 1));
+        let listed = metrics::channels();
+        assert!(listed.contains(&m));
+
+        for _ in 0..3 {
+            rx.recv().unwrap();
+        }
+        for h in senders {
+            h.join().unwrap().unwrap();
+        }
+        let h = co!(move || rx.recv());
+        sleep(Duration::from_millis(50));
+        let m = tx.metrics();
+        assert_eq!((m.len, m.sent, m.received), (0, 3, 3));
+        assert_eq!((m.blocked_senders, m.blocked_receivers), (0, 1));
+        drop(tx);
+        assert!(h.join().unwrap().is_err());
+
+        // the dropped channel is removed from the registry
+        let listed = metrics::channels();
+        assert!(listed
+            .iter()
+            .all(|c| c.name.as_deref() != Some("test_channel_metrics")));
+        assert_eq!(channel::<i32>().0.metrics().capacity, None);
+    }
+
+    #[test]
+    fn test_recv_async_repoll() {
+        use std::future::Future;
+        use std::sync::atomic::AtomicUsize;
+        use std::task::Wake;
+
+        struct CountWaker(AtomicUsize);
+
+        impl Wake for CountWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let (tx, rx) = channel::<i32>();
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = rx.recv_async();
+        for _ in 0..100 {
+            assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        }
+        // a re-poll replaces the waker instead of queuing another one
+        assert_eq!(rx.inner.wakers.lock().len(), 1);
+
+        tx.send(1).unwrap();
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert!(rx.inner.wakers.lock().is_empty());
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(Ok(1)));
+    }
+
+    #[test]
+    fn test_recv_async_drop() {
+        use std::future::Future;
+        use std::sync::atomic::AtomicUsize;
+        use std::task::Wake;
+
+        struct CountWaker(AtomicUsize);
+
+        impl Wake for CountWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let (tx, rx) = channel::<i32>();
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+        // a dropped future removes its waker
+        for _ in 0..100 {
+            let mut fut = rx.recv_async();
+            assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        }
+        assert!(rx.inner.wakers.lock().is_empty());
+
+        // one task is woken up for each message
+        let mut fut1 = rx.recv_async();
+        let mut fut2 = rx.recv_async();
+        assert!(Pin::new(&mut fut1).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut fut2).poll(&mut cx).is_pending());
+        tx.send(1).unwrap();
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+
+        // the woken one is dropped, the wakeup is passed to the other
+        drop(fut1);
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert_eq!(Pin::new(&mut fut2).poll(&mut cx), Poll::Ready(Ok(1)));
+        assert!(rx.inner.wakers.lock().is_empty());
+    }
 }
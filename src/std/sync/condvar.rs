@@ -5,6 +5,8 @@ use std::sync::{Arc, LockResult, PoisonError};
 use std::time::Duration;
 
 use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
 use crate::park::ParkError;
 use crate::std::queue::seg_queue::SegQueue;
 
@@ -45,6 +47,7 @@ impl Condvar {
         } else {
             None
         };
+        let _w = wait_for(WaitReason::Condvar);
         // enqueue the blocker
         let cur = SyncBlocker::current();
 
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::coroutine_impl::{current, spawn, Coroutine};
+use crate::join::JoinHandle;
+use crate::std::context::{self, CancelFunc, Context};
+use crate::std::errors::{Error, Result};
+use crate::std::sync::Semphore;
+
+struct Inner {
+    // the first error returned by the coroutines
+    err: Mutex<Option<Error>>,
+    // the running coroutines, cancelled when one of them fails
+    live: Mutex<HashMap<usize, Coroutine>>,
+    cancel: Option<CancelFunc>,
+}
+
+impl Inner {
+    fn is_failed(&self) -> bool {
+        self.err.lock().unwrap().is_some()
+    }
+
+    fn fail(&self, err: Error, id: usize) {
+        {
+            let mut first = self.err.lock().unwrap();
+            if first.is_some() {
+                return;
+            }
+            *first = Some(err);
+        }
+        if let Some(cancel) = &self.cancel {
+            cancel.cancel();
+        }
+        // the coroutine that failed is returning, no need to cancel it
+        let live: Vec<_> = self
+            .live
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(k, _)| **k != id)
+            .map(|(_, co)| co.clone())
+            .collect();
+        for co in live {
+            co.cancel();
+        }
+    }
+}
+
+// stop tracking the coroutine and release the limit even if it's cancelled
+struct Finish {
+    inner: Arc<Inner>,
+    limit: Option<Arc<Semphore>>,
+    id: usize,
+}
+
+impl Drop for Finish {
+    fn drop(&mut self) {
+        self.inner.live.lock().unwrap().remove(&self.id);
+        if let Some(limit) = &self.limit {
+            limit.post();
+        }
+    }
+}
+
+/// A group of coroutines working on subtasks of a common task, like the go `errgroup`
+///
+/// the first coroutine that returns an error cancels all the other running
+/// coroutines of the group by `Coroutine::cancel`, and the error is returned
+/// by `wait`. the coroutines spawned after that are not started at all.
+///
+/// # Examples
+///
+/// ```
+/// use mco::err;
+/// use mco::std::sync::ErrGroup;
+/// use std::time::Duration;
+///
+/// let group = ErrGroup::new();
+/// group.spawn(|| {
+///     mco::coroutine::sleep(Duration::from_secs(10));
+///     Ok(1)
+/// });
+/// group.spawn(|| Err(err!("failed")));
+/// assert_eq!(group.wait().unwrap_err().to_string(), "failed");
+///
+/// let group = ErrGroup::new();
+/// for i in 0..3 {
+///     group.spawn(move || Ok(i * 2));
+/// }
+/// assert_eq!(group.wait().unwrap(), vec![0, 2, 4]);
+/// ```
+pub struct ErrGroup<T = ()> {
+    inner: Arc<Inner>,
+    handles: Mutex<Vec<JoinHandle<Option<T>>>>,
+    limit: Option<Arc<Semphore>>,
+}
+
+impl<T: Send + 'static> ErrGroup<T> {
+    /// create a new group without a limit
+    pub fn new() -> Self {
+        Self::new_impl(None)
+    }
+
+    /// create a new group and a context derived from `parent`
+    ///
+    /// the context is cancelled when the first coroutine fails or `wait` returns
+    pub fn with_context(parent: &Context) -> (Self, Context) {
+        let (ctx, cancel) = context::with_cancel(parent);
+        (Self::new_impl(Some(cancel)), ctx)
+    }
+
+    fn new_impl(cancel: Option<CancelFunc>) -> Self {
+        ErrGroup {
+            inner: Arc::new(Inner {
+                err: Mutex::new(None),
+                live: Mutex::new(HashMap::new()),
+                cancel,
+            }),
+            handles: Mutex::new(Vec::new()),
+            limit: None,
+        }
+    }
+
+    /// limit the number of running coroutines of the group to `n`
+    ///
+    /// `spawn` blocks until there is a free slot when the limit is reached.
+    /// it must not be changed when there are running coroutines
+    pub fn set_limit(&mut self, n: usize) {
+        assert!(n > 0, "the limit of ErrGroup must be positive");
+        self.limit = Some(Arc::new(Semphore::new(n)));
+    }
+
+    /// spawn a coroutine in the group
+    ///
+    /// it does nothing if the group has failed already
+    #[track_caller]
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        if let Some(limit) = &self.limit {
+            limit.wait();
+        }
+        if self.inner.is_failed() {
+            if let Some(limit) = &self.limit {
+                limit.post();
+            }
+            return;
+        }
+        let inner = self.inner.clone();
+        let limit = self.limit.clone();
+        let h = spawn(move || {
+            let id = current().id();
+            inner.live.lock().unwrap().insert(id, current());
+            let _finish = Finish {
+                inner: inner.clone(),
+                limit,
+                id,
+            };
+            // re-check after registered so that it would not miss the cancel
+            if inner.is_failed() {
+                return None;
+            }
+            match f() {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    inner.fail(e, id);
+                    None
+                }
+            }
+        });
+        self.handles.lock().unwrap().push(h);
+    }
+
+    /// wait for all the coroutines of the group to finish
+    ///
+    /// return the first error, or the values of all the coroutines in the
+    /// order they are spawned. if a coroutine panics, the panic is propagated
+    /// after all the others are finished.
+    pub fn wait(&self) -> Result<Vec<T>> {
+        let mut values = Vec::new();
+        let mut panic = None;
+        loop {
+            // the coroutines may spawn more into the group
+            let handles = std::mem::take(&mut *self.handles.lock().unwrap());
+            if handles.is_empty() {
+                break;
+            }
+            for h in handles {
+                match h.join() {
+                    Ok(Some(v)) => values.push(v),
+                    Ok(None) => {}
+                    Err(p) => match p.downcast_ref::<mco_gen::Error>() {
+                        // cancelled by the group
+                        Some(mco_gen::Error::Cancel) => {}
+                        _ => {
+                            panic.get_or_insert(p);
+                        }
+                    },
+                }
+            }
+        }
+        if let Some(cancel) = &self.inner.cancel {
+            cancel.cancel();
+        }
+        if let Some(p) = panic {
+            std::panic::resume_unwind(p);
+        }
+        match self.inner.err.lock().unwrap().clone() {
+            Some(e) => Err(e),
+            None => Ok(values),
+        }
+    }
+}
+
+impl<T: Send + 'static> Default for ErrGroup<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ErrGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrGroup")
+            .field("running", &self.inner.live.lock().unwrap().len())
+            .field("err", &self.inner.err.lock().unwrap())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutine::sleep;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_err_group_cancel() {
+        let (group, ctx) = ErrGroup::with_context(&context::background());
+        let finished = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let finished = finished.clone();
+            group.spawn(move || {
+                sleep(Duration::from_secs(10));
+                finished.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            });
+        }
+        group.spawn(|| {
+            sleep(Duration::from_millis(10));
+            Err(crate::err!("first"))
+        });
+        group.spawn(|| {
+            sleep(Duration::from_millis(100));
+            Err(crate::err!("second"))
+        });
+        assert_eq!(group.wait().unwrap_err().to_string(), "first");
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+        assert_eq!(ctx.err(), Some(context::ContextError::Canceled));
+        // not started after failed
+        group.spawn(|| panic!("should not run"));
+        assert!(group.wait().is_err());
+    }
+
+    #[test]
+    fn test_err_group_limit() {
+        let mut group = ErrGroup::new();
+        group.set_limit(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        for i in 0..10 {
+            let running = running.clone();
+            let max = max.clone();
+            group.spawn(move || {
+                let n = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(n, Ordering::SeqCst);
+                sleep(Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(i)
+            });
+        }
+        assert_eq!(group.wait().unwrap(), (0..10).collect::<Vec<_>>());
+        assert!(max.load(Ordering::SeqCst) <= 2);
+    }
+}
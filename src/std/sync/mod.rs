@@ -2,10 +2,12 @@
 mod atomic_option;
 mod blocking;
 mod condvar;
+mod err_group;
 mod mutex;
 mod once;
 mod poison;
 mod rwlock;
+mod select;
 mod semphore;
 mod sync_array_queue;
 mod sync_btree_map;
@@ -18,6 +20,9 @@ mod wait_group;
 pub(crate) mod atomic_dur;
 #[cfg(not(unix))]
 pub(crate) mod delay_drop;
+pub mod broadcast;
+pub mod oneshot;
+pub mod watch;
 #[macro_use]
 pub mod channel;
 
@@ -25,9 +30,11 @@ pub use self::atomic_option::*;
 pub use self::blocking::*;
 pub use self::channel::*;
 pub use self::condvar::*;
+pub use self::err_group::*;
 pub use self::mutex::*;
 pub use self::once::*;
 pub use self::rwlock::*;
+pub use self::select::*;
 pub use self::semphore::*;
 pub use self::sync_array_queue::*;
 pub use self::sync_btree_map::*;
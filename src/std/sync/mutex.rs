@@ -12,6 +12,8 @@ use std::sync::{LockResult, TryLockError, TryLockResult};
 use super::blocking::SyncBlocker;
 use super::poison;
 use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
 use crate::park::ParkError;
 
 pub struct Mutex<T: ?Sized> {
@@ -66,6 +68,7 @@ impl<T: ?Sized> Mutex<T> {
             Err(TryLockError::Poisoned(e)) => return Err(e),
         }
 
+        let _w = wait_for(WaitReason::Mutex);
         let cur = SyncBlocker::current();
         // register blocker first
         self.to_wake.push(cur.clone());
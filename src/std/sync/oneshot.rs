@@ -0,0 +1,364 @@
+//! oneshot channel, sending a single value
+//!
+//! the value is kept in a single slot guarded by an atomic state, and at
+//! most one receiver is parked on it. it's much lighter than a `bounded(1)`
+//! channel for the request/response pattern, where each request creates a
+//! new channel just to return one reply.
+
+use std::cell::UnsafeCell;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::blocking::Blocker;
+use super::AtomicOption;
+use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
+use crate::park::ParkError;
+
+// the value is written into the slot
+const SENT: usize = 1;
+// the sender is dropped
+const TX_CLOSED: usize = 2;
+// the receiver is dropped
+const RX_CLOSED: usize = 4;
+// the value is taken by the receiver
+const TAKEN: usize = 8;
+
+/// create a oneshot channel
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::sync::oneshot;
+///
+/// let (tx, rx) = oneshot::channel();
+/// mco::co!(move || tx.send(1).unwrap());
+/// assert_eq!(rx.recv(), Ok(1));
+///
+/// // the sender is dropped without sending
+/// let (tx, rx) = oneshot::channel::<i32>();
+/// drop(tx);
+/// assert_eq!(rx.recv(), Err(oneshot::RecvError));
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        state: AtomicUsize::new(0),
+        value: UnsafeCell::new(None),
+        waiter: AtomicOption::none(),
+    });
+    let rx = Receiver {
+        inner: inner.clone(),
+    };
+    (Sender { inner }, rx)
+}
+
+/// An error returned from `Receiver::recv`, the sender is dropped without
+/// sending or the value is already received
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+/// An error returned from `Receiver::try_recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// the value is not sent yet
+    Empty,
+    /// the sender is dropped without sending or the value is already received
+    Closed,
+}
+
+/// An error returned from `Receiver::recv_timeout`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    /// the value is not sent within the timeout
+    Timeout,
+    /// the sender is dropped without sending or the value is already received
+    Closed,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("oneshot sender dropped without sending")
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("oneshot value is not sent yet"),
+            TryRecvError::Closed => RecvError.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => f.write_str("timed out waiting on oneshot channel"),
+            RecvTimeoutError::Closed => RecvError.fmt(f),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+impl Error for TryRecvError {}
+
+impl Error for RecvTimeoutError {}
+
+// the parked receiver, a blocked coroutine or thread, or an async task
+enum Waiter {
+    Blocker(Arc<Blocker>),
+    Task(Waker),
+}
+
+impl Waiter {
+    fn wake(self) {
+        match self {
+            Waiter::Blocker(b) => {
+                let _ = b.unpark();
+            }
+            Waiter::Task(w) => w.wake(),
+        }
+    }
+}
+
+struct Inner<T> {
+    state: AtomicUsize,
+    // only written by the sender before `SENT` is set, and only read by
+    // the receiver that sets `TAKEN` after `SENT` is set
+    value: UnsafeCell<Option<T>>,
+    waiter: AtomicOption<Waiter>,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let state = self.state.load(Ordering::Acquire);
+        if state & SENT != 0 {
+            // only the first receive takes the value
+            if self.state.fetch_or(TAKEN, Ordering::AcqRel) & TAKEN == 0 {
+                let value = unsafe { (*self.value.get()).take() };
+                return value.ok_or(TryRecvError::Closed);
+            }
+            return Err(TryRecvError::Closed);
+        }
+        if state & TX_CLOSED != 0 {
+            return Err(TryRecvError::Closed);
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    // register the waiter, return the value if it's ready after that
+    fn register(&self, waiter: Waiter) -> Result<T, TryRecvError> {
+        self.waiter.swap(waiter);
+        let ret = self.try_recv();
+        if !matches!(ret, Err(TryRecvError::Empty)) {
+            self.waiter.take();
+        }
+        ret
+    }
+
+    fn wake(&self) {
+        if let Some(w) = self.waiter.take() {
+            w.wake();
+        }
+    }
+
+    fn recv(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        match self.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            ret => return ret.map_err(|_| RecvTimeoutError::Closed),
+        }
+
+        let _w = wait_for(WaitReason::ChanRecv);
+        loop {
+            let cur = Blocker::current();
+            match self.register(Waiter::Blocker(cur.clone())) {
+                Err(TryRecvError::Empty) => {}
+                ret => return ret.map_err(|_| RecvTimeoutError::Closed),
+            }
+            match cur.park(dur) {
+                Ok(_) => match self.try_recv() {
+                    // the blocker may be unparked by a replaced waiter
+                    Err(TryRecvError::Empty) => continue,
+                    ret => return ret.map_err(|_| RecvTimeoutError::Closed),
+                },
+                Err(err) => {
+                    self.waiter.take();
+                    if err == ParkError::Canceled {
+                        trigger_cancel_panic();
+                    }
+                    return match self.try_recv() {
+                        Err(TryRecvError::Empty) => Err(RecvTimeoutError::Timeout),
+                        ret => ret.map_err(|_| RecvTimeoutError::Closed),
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// The sending half of a oneshot channel
+pub struct Sender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Sender<T> {
+    /// send the value and wake up the receiver
+    ///
+    /// the value is returned back if the receiver is dropped
+    pub fn send(self, t: T) -> Result<(), T> {
+        unsafe { *self.inner.value.get() = Some(t) };
+        let state = self.inner.state.fetch_or(SENT, Ordering::AcqRel);
+        if state & RX_CLOSED != 0 {
+            // the receiver never reads the value after it's dropped
+            let t = unsafe { (*self.inner.value.get()).take() };
+            return Err(t.expect("oneshot value is lost"));
+        }
+        // the receiver is woken up by the drop
+        Ok(())
+    }
+
+    /// return true if the receiver is dropped
+    pub fn is_closed(&self) -> bool {
+        self.inner.state.load(Ordering::Acquire) & RX_CLOSED != 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.inner.state.fetch_or(TX_CLOSED, Ordering::AcqRel);
+        self.inner.wake();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("oneshot::Sender { .. }")
+    }
+}
+
+/// The receiving half of a oneshot channel
+///
+/// `recv` blocks the coroutine or thread, so it can be used in `select!`,
+/// the receiver can also be awaited in async code. only one receive would
+/// get the value, the following ones return an error.
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// receive the value without blocking
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// block the coroutine or thread until the value is sent
+    ///
+    /// return an error if the sender is dropped without sending
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.inner.recv(None).map_err(|_| RecvError)
+    }
+
+    /// same as `recv` except that with an extra timeout value
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, RecvTimeoutError> {
+        self.inner.recv(Some(dur))
+    }
+
+    /// return true if `recv` would not block
+    pub fn is_ready(&self) -> bool {
+        self.inner.state.load(Ordering::Acquire) & (SENT | TX_CLOSED) != 0
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ret = match self.inner.try_recv() {
+            Err(TryRecvError::Empty) => self.inner.register(Waiter::Task(cx.waker().clone())),
+            ret => ret,
+        };
+        match ret {
+            Ok(t) => Poll::Ready(Ok(t)),
+            Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.state.fetch_or(RX_CLOSED, Ordering::AcqRel);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("oneshot::Receiver { .. }")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutine::block_on;
+    use std::thread;
+
+    #[test]
+    fn test_oneshot_send() {
+        let (tx, rx) = channel();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert!(!rx.is_ready());
+        tx.send(1).unwrap();
+        assert!(rx.is_ready());
+        assert_eq!(rx.try_recv(), Ok(1));
+        // the value is taken only once
+        assert_eq!(rx.recv(), Err(RecvError));
+
+        let (tx, rx) = channel();
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(2), Err(2));
+    }
+
+    #[test]
+    fn test_oneshot_blocking() {
+        let (tx, rx) = channel::<i32>();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(tx);
+        });
+        let h = crate::coroutine::spawn(move || rx.recv());
+        assert_eq!(h.join().unwrap(), Err(RecvError));
+
+        let (tx, rx) = channel();
+        let h = crate::coroutine::spawn(move || rx.recv());
+        thread::sleep(Duration::from_millis(10));
+        tx.send(3).unwrap();
+        assert_eq!(h.join().unwrap(), Ok(3));
+    }
+
+    #[test]
+    fn test_oneshot_await() {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(4).unwrap();
+        });
+        assert_eq!(block_on(rx), Ok(4));
+    }
+}
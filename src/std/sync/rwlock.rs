@@ -13,6 +13,8 @@ use super::blocking::SyncBlocker;
 use super::mutex::{self, Mutex};
 use super::poison;
 use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
 use crate::park::ParkError;
 
 /// A reader-writer lock
@@ -78,6 +80,7 @@ impl<T: ?Sized> RwLock<T> {
             Err(TryLockError::Poisoned(_)) => return Err(ParkError::Timeout),
         }
 
+        let _w = wait_for(WaitReason::RwLock);
         let cur = SyncBlocker::current();
         // register blocker first
         self.to_wake.push(cur.clone());
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvError;
+use std::time::Duration;
+
+use crate::cqueue;
+use crate::sleep::sleep;
+use crate::std::sync::channel::{Receiver, SendError, Sender};
+use crate::std::sync::AtomicOption;
+
+enum Op<'a, T> {
+    Recv(&'a Receiver<T>),
+    Send(&'a Sender<T>, T),
+    After(Duration),
+}
+
+/// The result of the selected operation
+#[derive(Debug, PartialEq, Eq)]
+pub enum Selected<T> {
+    /// a message is received, or an error if the channel is closed and drained
+    Recv(Result<T, RecvError>),
+    /// the message is sent, or the channel is closed
+    Send(Result<(), SendError<T>>),
+    /// the timer is expired
+    Timeout,
+}
+
+/// A select over a set of channel operations decided at runtime
+///
+/// each operation is added by `recv`, `send` or `after` that returns its
+/// index, then `select` blocks until one of them is ready and returns the
+/// index and the result of it. it's built on `Cqueue`, each operation is
+/// waited by a select coroutine.
+///
+/// only the selected operation takes effect, the messages are left in the
+/// channels for the other receive operations and the values of the other
+/// send operations are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::sync::{Select, Selected};
+/// use std::time::Duration;
+///
+/// let chans: Vec<_> = (0..4).map(|_| mco::chan!()).collect();
+/// chans[2].0.send(2).unwrap();
+///
+/// let mut sel = Select::new();
+/// for (_, rx) in chans.iter() {
+///     sel.recv(rx);
+/// }
+/// sel.after(Duration::from_secs(1));
+/// assert_eq!(sel.select(), (2, Selected::Recv(Ok(2))));
+/// ```
+pub struct Select<'a, T> {
+    ops: Vec<Op<'a, T>>,
+}
+
+impl<'a, T: Send> Select<'a, T> {
+    /// create an empty select
+    pub fn new() -> Self {
+        Select { ops: Vec::new() }
+    }
+
+    /// add an operation that receives a message from the channel, return its index
+    pub fn recv(&mut self, rx: &'a Receiver<T>) -> usize {
+        self.push(Op::Recv(rx))
+    }
+
+    /// add an operation that sends the value to the channel, return its index
+    pub fn send(&mut self, tx: &'a Sender<T>, value: T) -> usize {
+        self.push(Op::Send(tx, value))
+    }
+
+    /// add a timer that is expired after the duration, return its index
+    pub fn after(&mut self, dur: Duration) -> usize {
+        self.push(Op::After(dur))
+    }
+
+    fn push(&mut self, op: Op<'a, T>) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    /// the number of operations
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// return true if there is no operation
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// block until one of the operations is ready, return its index and result
+    ///
+    /// # Panics
+    ///
+    /// panics if there is no operation, it would block forever
+    pub fn select(self) -> (usize, Selected<T>) {
+        assert!(!self.ops.is_empty(), "select without any operation");
+        self.run(false).expect("select error")
+    }
+
+    /// return the first ready operation without blocking, or `None` if
+    /// none of them is ready, like a select with a `default` arm
+    pub fn try_select(self) -> Option<(usize, Selected<T>)> {
+        self.run(true)
+    }
+
+    fn run(self, non_blocking: bool) -> Option<(usize, Selected<T>)> {
+        // only the first operation that claims it would take effect
+        let selected = AtomicBool::new(false);
+        let claim = || {
+            selected
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        };
+        let result = AtomicOption::none();
+        cqueue::scope(|cqueue| {
+            for (i, op) in self.ops.into_iter().enumerate() {
+                let result = &result;
+                cqueue.add(i, move |es| {
+                    let r = match op {
+                        Op::Recv(rx) => rx.recv_claim(claim).map(Selected::Recv),
+                        Op::Send(tx, v) => tx.send_claim(v, claim).map(Selected::Send),
+                        Op::After(dur) => {
+                            sleep(dur);
+                            claim().then(|| Selected::Timeout)
+                        }
+                    };
+                    if let Some(r) = r {
+                        result.swap(r);
+                        es.send(0);
+                    }
+                });
+            }
+
+            let ev = if non_blocking {
+                match cqueue.try_poll() {
+                    Ok(ev) => ev,
+                    // stop the operations, unless one is just selected
+                    Err(_) if claim() => return None,
+                    Err(_) => cqueue.poll(None).ok()?,
+                }
+            } else {
+                cqueue.poll(None).ok()?
+            };
+            result.take().map(|r| (ev.token, r))
+        })
+    }
+}
+
+impl<'a, T: Send> Default for Select<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> fmt::Debug for Select<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Select").field("ops", &self.ops.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::sync::channel::{bounded, channel};
+
+    #[test]
+    fn test_select_recv() {
+        let chans: Vec<_> = (0..8).map(|_| channel::<usize>()).collect();
+        let tx = chans[5].0.clone();
+        crate::coroutine::spawn(move || {
+            sleep(Duration::from_millis(20));
+            tx.send(5).unwrap();
+        });
+        let mut sel = Select::new();
+        for (_, rx) in chans.iter() {
+            sel.recv(rx);
+        }
+        assert_eq!(sel.select(), (5, Selected::Recv(Ok(5))));
+
+        // the messages of the other channels are not consumed
+        chans[1].0.send(1).unwrap();
+        chans[3].0.send(3).unwrap();
+        let mut sel = Select::new();
+        for (_, rx) in chans.iter() {
+            sel.recv(rx);
+        }
+        let (i, r) = sel.select();
+        assert!(i == 1 || i == 3);
+        assert_eq!(r, Selected::Recv(Ok(i)));
+        let other = 4 - i;
+        assert_eq!(chans[other].1.try_recv(), Ok(other));
+    }
+
+    #[test]
+    fn test_select_send_timeout() {
+        let (tx, rx) = bounded::<i32>(1);
+        tx.send(1).unwrap();
+        let mut sel = Select::new();
+        sel.send(&tx, 2);
+        sel.after(Duration::from_millis(10));
+        assert_eq!(sel.select(), (1, Selected::Timeout));
+        // the value is not sent
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert!(rx.try_recv().is_err());
+
+        let mut sel = Select::new();
+        sel.recv(&rx);
+        let i = sel.send(&tx, 3);
+        assert_eq!(sel.select(), (i, Selected::Send(Ok(()))));
+        assert_eq!(rx.recv(), Ok(3));
+
+        rx.close();
+        let mut sel = Select::new();
+        sel.send(&tx, 4);
+        assert_eq!(sel.select(), (0, Selected::Send(Err(SendError::Closed(4)))));
+    }
+
+    #[test]
+    fn test_try_select() {
+        let (tx, rx) = channel::<i32>();
+        let mut sel = Select::new();
+        sel.recv(&rx);
+        sel.after(Duration::from_secs(10));
+        assert_eq!(sel.try_select(), None);
+
+        tx.send(1).unwrap();
+        let mut sel = Select::new();
+        sel.after(Duration::from_secs(10));
+        sel.recv(&rx);
+        assert_eq!(sel.try_select(), Some((1, Selected::Recv(Ok(1)))));
+        assert_eq!(Select::<i32>::new().try_select(), None);
+    }
+}
@@ -5,6 +5,8 @@ use std::time::Duration;
 
 use super::blocking::SyncBlocker;
 use crate::cancel::trigger_cancel_panic;
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
 use crate::park::ParkError;
 use crate::std::queue::seg_queue::SegQueue as WaitList;
 
@@ -77,6 +79,7 @@ impl Semphore {
             return true;
         }
 
+        let _w = wait_for(WaitReason::Semphore);
         let cur = SyncBlocker::current();
         // register blocker first
         self.to_wake.push(cur.clone());
@@ -1,6 +1,8 @@
 // Necessary for using `Mutex<usize>` for conditional variables
 #![allow(clippy::mutex_atomic)]
 
+use crate::coroutine_impl::wait_for;
+use crate::dump::WaitReason;
 use crate::std::sync::{Condvar, Mutex};
 use std::fmt;
 use std::sync::Arc;
@@ -121,6 +123,7 @@ impl WaitGroup {
         let inner = self.inner.clone();
         drop(self);
 
+        let _w = wait_for(WaitReason::WaitGroup);
         let mut count = inner.count.lock().unwrap();
         while *count > 0 {
             count = inner.cvar.wait(count).unwrap();
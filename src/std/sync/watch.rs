@@ -0,0 +1,330 @@
+//! watch channel, holding only the latest value
+//!
+//! the sender replaces the value and bumps its version, each receiver keeps
+//! the version it has seen, so a change is never missed while the skipped
+//! values are not buffered. every version has its own `SyncFlag`, which is
+//! fired when the value is replaced, to wake up the waiting receivers.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::RecvError;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+
+use super::channel::SendError;
+use super::SyncFlag;
+
+/// create a watch channel with the initial value
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::sync::watch;
+///
+/// let (tx, mut rx) = watch::channel("v1");
+/// let h = mco::co!(move || {
+///     rx.changed().unwrap();
+///     *rx.borrow()
+/// });
+/// tx.send("v2").unwrap();
+/// assert_eq!(h.join().unwrap(), "v2");
+/// ```
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: RwLock::new(State {
+            value: init,
+            version: 0,
+            changed: Arc::new(SyncFlag::new()),
+            closed: false,
+        }),
+        receivers: AtomicUsize::new(1),
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+        seen: 0,
+    };
+    (Sender { shared }, rx)
+}
+
+struct State<T> {
+    value: T,
+    // bumped each time the value is replaced
+    version: u64,
+    // fired when the current version is replaced or the sender is dropped
+    changed: Arc<SyncFlag>,
+    closed: bool,
+}
+
+struct Shared<T> {
+    state: RwLock<State<T>>,
+    receivers: AtomicUsize,
+}
+
+impl<T> Shared<T> {
+    // the lock is never held by user code that could panic while writing
+    fn read(&self) -> RwLockReadGuard<'_, State<T>> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, State<T>> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A reference to the value in a watch channel
+///
+/// it holds a read lock of the value, the sender is blocked until it's
+/// dropped, so don't keep it across blocking calls.
+pub struct Ref<'a, T> {
+    guard: RwLockReadGuard<'a, State<T>>,
+}
+
+impl<'a, T> Deref for Ref<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard.value
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for Ref<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.guard.value.fmt(f)
+    }
+}
+
+/// The sending half of a watch channel
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// replace the value and notify all the receivers
+    ///
+    /// the value is returned back if there is no receiver
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.receiver_count() == 0 {
+            return Err(SendError::Disconnected(value));
+        }
+        self.send_replace(value);
+        Ok(())
+    }
+
+    /// replace the value even if there is no receiver, return the old value
+    pub fn send_replace(&self, value: T) -> T {
+        let (old, changed) = {
+            let mut state = self.shared.write();
+            state.version += 1;
+            let old = std::mem::replace(&mut state.value, value);
+            let changed = std::mem::replace(&mut state.changed, Arc::new(SyncFlag::new()));
+            (old, changed)
+        };
+        changed.fire();
+        old
+    }
+
+    /// borrow the current value
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.shared.read(),
+        }
+    }
+
+    /// create a receiver that has seen the current value
+    pub fn subscribe(&self) -> Receiver<T> {
+        let seen = self.shared.read().version;
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+            seen,
+        }
+    }
+
+    /// the number of active receivers
+    pub fn receiver_count(&self) -> usize {
+        self.shared.receivers.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let changed = {
+            let mut state = self.shared.write();
+            state.closed = true;
+            state.changed.clone()
+        };
+        changed.fire();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("watch::Sender { .. }")
+    }
+}
+
+/// The receiving half of a watch channel
+///
+/// a cloned receiver has seen the same version as the original one
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the version of the value seen by the receiver
+    seen: u64,
+}
+
+impl<T> Receiver<T> {
+    /// borrow the current value, it's not marked as seen
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            guard: self.shared.read(),
+        }
+    }
+
+    /// borrow the current value and mark it as seen
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let guard = self.shared.read();
+        self.seen = guard.version;
+        Ref { guard }
+    }
+
+    /// return true if the value is changed since it's seen
+    ///
+    /// return an error if the sender is dropped
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = self.shared.read();
+        if state.closed {
+            return Err(RecvError);
+        }
+        Ok(state.version != self.seen)
+    }
+
+    // return the flag to wait on if the value is not changed
+    fn check_changed(&mut self) -> Result<Option<Arc<SyncFlag>>, RecvError> {
+        let state = self.shared.read();
+        if state.version != self.seen {
+            self.seen = state.version;
+            return Ok(None);
+        }
+        if state.closed {
+            return Err(RecvError);
+        }
+        Ok(Some(state.changed.clone()))
+    }
+
+    /// block the coroutine or thread until the value is changed since it's
+    /// seen, and mark the new value as seen
+    ///
+    /// return an error if the sender is dropped without a new value
+    pub fn changed(&mut self) -> Result<(), RecvError> {
+        while let Some(flag) = self.check_changed()? {
+            flag.wait();
+        }
+        Ok(())
+    }
+
+    /// same as `changed` except that with an extra timeout value
+    ///
+    /// return `Ok(false)` if timeout happened
+    pub fn changed_timeout(&mut self, dur: Duration) -> Result<bool, RecvError> {
+        match self.check_changed()? {
+            None => Ok(true),
+            // the flag is fired for a new version or the drop of the sender
+            Some(flag) if flag.wait_timeout(dur) => self.check_changed().map(|f| f.is_none()),
+            Some(_) => Ok(false),
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("watch::Receiver")
+            .field("seen", &self.seen)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_watch_latest() {
+        let (tx, mut rx) = channel(0);
+        assert_eq!(rx.has_changed(), Ok(false));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.has_changed(), Ok(true));
+        // the skipped values are not buffered
+        rx.changed().unwrap();
+        assert_eq!(*rx.borrow(), 2);
+        assert_eq!(rx.changed_timeout(Duration::from_millis(10)), Ok(false));
+
+        let mut rx2 = tx.subscribe();
+        assert_eq!(tx.receiver_count(), 2);
+        assert_eq!(tx.send_replace(3), 2);
+        assert_eq!(*rx2.borrow_and_update(), 3);
+        assert_eq!(rx2.has_changed(), Ok(false));
+
+        drop(tx);
+        // the last change is still reported before the close
+        rx.changed().unwrap();
+        assert_eq!(rx.changed(), Err(RecvError));
+        assert_eq!(rx2.has_changed(), Err(RecvError));
+        assert_eq!(*rx2.borrow(), 3);
+    }
+
+    #[test]
+    fn test_watch_no_receiver() {
+        let (tx, rx) = channel(0);
+        drop(rx);
+        assert_eq!(tx.send(1), Err(SendError::Disconnected(1)));
+        assert_eq!(tx.send_replace(2), 0);
+        assert_eq!(*tx.borrow(), 2);
+    }
+
+    #[test]
+    fn test_watch_wakeup() {
+        let (tx, rx) = channel(0);
+        let hs: Vec<_> = (0..4)
+            .map(|_| {
+                let mut rx = rx.clone();
+                crate::coroutine::spawn(move || {
+                    let mut last = 0;
+                    while rx.changed().is_ok() {
+                        let v = *rx.borrow();
+                        assert!(v > last);
+                        last = v;
+                    }
+                    last
+                })
+            })
+            .collect();
+        thread::spawn(move || {
+            for i in 1..=5 {
+                thread::sleep(Duration::from_millis(5));
+                tx.send(i).unwrap();
+            }
+        });
+        for h in hs {
+            assert_eq!(h.join().unwrap(), 5);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! the clock behind the timers and `Time::now`
+//!
+//! the timer thread, `sleep`, `park_timeout`, `Ticker`, `Timer` and the IO
+//! timeouts of the coroutines read the monotonic time from the installed
+//! clock, and `Time::now` reads the wall clock from it. a test can install
+//! a `MockClock` and advance it manually, the due timers are fired at once
+//! without waiting for the real time.
+//!
+//! the blocking calls on a normal thread, such as `sleep` outside of a
+//! coroutine, are not affected by the clock.
+//!
+//! the clock is process wide, but only the timers of the current runtime are
+//! driven when it's changed, that is the runtime of the calling thread or
+//! the default one. the timers of the other `Runtime` instances fire when
+//! their timer thread or event loop wakes up next time.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+use time::OffsetDateTime;
+
+use crate::scheduler::get_scheduler;
+use crate::std::lazy::sync::Lazy;
+use crate::timeout_list::{self, START_TIME};
+
+/// A source of the current time
+pub trait Clock: Send + Sync {
+    /// the monotonic time elapsed since the process started, it must never
+    /// go backwards. a clock installed later should continue from the value
+    /// of `SystemClock` so that the pending timers are not shifted
+    fn elapsed(&self) -> Duration;
+
+    /// the current wall clock time
+    fn now_utc(&self) -> OffsetDateTime;
+}
+
+/// The real clock of the system, it's used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        START_TIME.elapsed()
+    }
+
+    fn now_utc(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+// set when a clock other than the system clock is installed, so the system
+// clock is read without the lock
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static CLOCK: Lazy<RwLock<Arc<dyn Clock>>> = Lazy::new(|| RwLock::new(Arc::new(SystemClock)));
+
+/// install the clock for the whole process
+///
+/// the timer thread of the current runtime is woken up to fire the timers
+/// that are due with the new clock
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    *CLOCK.write() = clock;
+    INSTALLED.store(true, Ordering::Release);
+    get_scheduler().wake_timer();
+}
+
+/// restore the system clock
+///
+/// the monotonic time of the system clock may be behind the replaced one,
+/// the timers added in between would fire late by the difference
+pub fn reset_clock() {
+    INSTALLED.store(false, Ordering::Release);
+    *CLOCK.write() = Arc::new(SystemClock);
+    get_scheduler().wake_timer();
+}
+
+/// return the installed clock
+pub fn clock() -> Arc<dyn Clock> {
+    CLOCK.read().clone()
+}
+
+/// the monotonic time of the installed clock
+#[inline]
+pub(crate) fn elapsed() -> Duration {
+    if !INSTALLED.load(Ordering::Acquire) {
+        return START_TIME.elapsed();
+    }
+    CLOCK.read().elapsed()
+}
+
+/// the monotonic time of the installed clock as an `Instant`, it's the same
+/// as `Instant::now()` with the system clock
+#[inline]
+pub(crate) fn instant() -> Instant {
+    *START_TIME + elapsed()
+}
+
+/// the wall clock time of the installed clock
+#[inline]
+pub(crate) fn now_utc() -> OffsetDateTime {
+    if !INSTALLED.load(Ordering::Acquire) {
+        return OffsetDateTime::now_utc();
+    }
+    CLOCK.read().now_utc()
+}
+
+/// A virtual clock that only moves when it's advanced
+///
+/// it starts from the current time of the system clock.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::time::clock::{reset_clock, set_clock, MockClock};
+/// use mco::std::time::Ticker;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let clock = Arc::new(MockClock::new());
+/// set_clock(clock.clone());
+/// let ticker = Ticker::new(Duration::from_secs(3600));
+/// clock.advance(Duration::from_secs(3600));
+/// assert!(ticker.recv.recv().is_ok());
+/// reset_clock();
+/// ```
+pub struct MockClock {
+    // the monotonic time and the wall clock time
+    now: Mutex<(Duration, OffsetDateTime)>,
+}
+
+impl MockClock {
+    /// create a clock at the current time of the system clock
+    pub fn new() -> Self {
+        MockClock {
+            now: Mutex::new((SystemClock.elapsed(), SystemClock.now_utc())),
+        }
+    }
+
+    /// move the clock forward, the timers of the current runtime that are
+    /// due at the new time are fired before it returns
+    ///
+    /// the expired IO timeouts are fired by the event loops of the runtime,
+    /// they are woken up but not waited for, joining the coroutine is enough
+    /// to see the timeout. the clock should be installed by `set_clock` before
+    pub fn advance(&self, d: Duration) {
+        let elapsed = {
+            let mut now = self.now.lock();
+            now.0 += d;
+            now.1 += d;
+            now.0
+        };
+        if INSTALLED.load(Ordering::Acquire) {
+            get_scheduler().fire_timers(timeout_list::dur_to_ns(elapsed));
+        }
+    }
+
+    /// set the wall clock time, the monotonic time is not changed
+    pub fn set_now_utc(&self, t: OffsetDateTime) {
+        self.now.lock().1 = t;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn elapsed(&self) -> Duration {
+        self.now.lock().0
+    }
+
+    fn now_utc(&self) -> OffsetDateTime {
+        self.now.lock().1
+    }
+}
+
+impl fmt::Debug for MockClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let now = self.now.lock();
+        f.debug_struct("MockClock")
+            .field("elapsed", &now.0)
+            .field("now_utc", &now.1)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        // the clock is not installed, other tests are not affected
+        let clock = MockClock::new();
+        let (elapsed, now) = (clock.elapsed(), clock.now_utc());
+        assert!(elapsed <= SystemClock.elapsed());
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(clock.elapsed(), elapsed + Duration::from_secs(10));
+        assert_eq!(clock.now_utc(), now + Duration::from_secs(10));
+
+        clock.set_now_utc(OffsetDateTime::UNIX_EPOCH);
+        assert_eq!(clock.elapsed(), elapsed + Duration::from_secs(10));
+        assert_eq!(clock.now_utc(), OffsetDateTime::UNIX_EPOCH);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::std::errors::Result;
+use std::time::Duration;
+
+const NANOSECOND: u64 = 1;
+const MICROSECOND: u64 = 1000 * NANOSECOND;
+const MILLISECOND: u64 = 1000 * MICROSECOND;
+const SECOND: u64 = 1000 * MILLISECOND;
+const MINUTE: u64 = 60 * SECOND;
+const HOUR: u64 = 60 * MINUTE;
+
+// the largest duration of go, the same limit is kept for the parsing
+const MAX_NANOS: u64 = 1 << 63;
+
+fn unit(u: &str) -> Option<u64> {
+    match u {
+        "ns" => Some(NANOSECOND),
+        // U+00B5 micro sign and U+03BC greek letter mu
+        "us" | "µs" | "μs" => Some(MICROSECOND),
+        "ms" => Some(MILLISECOND),
+        "s" => Some(SECOND),
+        "m" => Some(MINUTE),
+        "h" => Some(HOUR),
+        _ => None,
+    }
+}
+
+// consume the leading [0-9]*, return None if it overflows
+fn leading_int(s: &str) -> Option<(u64, &str)> {
+    let mut x: u64 = 0;
+    let mut i = 0;
+    for c in s.bytes() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        if x > MAX_NANOS / 10 {
+            return None;
+        }
+        x = x * 10 + (c - b'0') as u64;
+        if x > MAX_NANOS {
+            return None;
+        }
+        i += 1;
+    }
+    Some((x, &s[i..]))
+}
+
+// consume the leading [0-9]* after the dot, the digits that overflow are
+// dropped. return the fraction and the scale of it
+fn leading_fraction(s: &str) -> (u64, f64, &str) {
+    let mut x: u64 = 0;
+    let mut scale = 1.0;
+    let mut overflow = false;
+    let mut i = 0;
+    for c in s.bytes() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        i += 1;
+        if overflow {
+            continue;
+        }
+        if x > (MAX_NANOS - 1) / 10 {
+            overflow = true;
+            continue;
+        }
+        let y = x * 10 + (c - b'0') as u64;
+        if y > MAX_NANOS {
+            overflow = true;
+            continue;
+        }
+        x = y;
+        scale *= 10.0;
+    }
+    (x, scale, &s[i..])
+}
+
+/// parse a duration string like go's `time.ParseDuration`
+///
+/// a duration string is a possibly signed sequence of decimal numbers, each
+/// with optional fraction and a unit suffix, such as "300ms", "1.5h" or
+/// "2h45m". valid time units are "ns", "us" (or "µs"), "ms", "s", "m", "h".
+/// a negative duration can't be represented, only "-0" is accepted.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::time::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+/// assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+/// assert!(parse_duration("10").is_err());
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let orig = s;
+    let mut s = s;
+    let mut neg = false;
+    if let Some(c) = s.bytes().next() {
+        if c == b'-' || c == b'+' {
+            neg = c == b'-';
+            s = &s[1..];
+        }
+    }
+    // special case: if all that is left is "0", this is zero
+    if s == "0" {
+        return Ok(Duration::from_secs(0));
+    }
+    let invalid = || err!("time: invalid duration {:?}", orig);
+    if s.is_empty() {
+        return Err(invalid());
+    }
+    let mut d: u64 = 0;
+    while !s.is_empty() {
+        // the next character must be [0-9.]
+        let c = s.as_bytes()[0];
+        if !(c == b'.' || c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        // consume [0-9]*
+        let pl = s.len();
+        let (mut v, rest) = leading_int(s).ok_or_else(invalid)?;
+        s = rest;
+        let pre = pl != s.len();
+
+        // consume (\.[0-9]*)?
+        let mut post = false;
+        let mut f = 0;
+        let mut scale = 1.0;
+        if let Some(rest) = s.strip_prefix('.') {
+            let pl = rest.len();
+            let (x, sc, rest) = leading_fraction(rest);
+            f = x;
+            scale = sc;
+            s = rest;
+            post = pl != s.len();
+        }
+        if !pre && !post {
+            // no digits (e.g. ".s" or "-.s")
+            return Err(invalid());
+        }
+
+        // consume unit
+        let i = s
+            .bytes()
+            .position(|c| c == b'.' || c.is_ascii_digit())
+            .unwrap_or(s.len());
+        if i == 0 {
+            return Err(err!("time: missing unit in duration {:?}", orig));
+        }
+        let u = &s[..i];
+        s = &s[i..];
+        let unit =
+            unit(u).ok_or_else(|| err!("time: unknown unit {:?} in duration {:?}", u, orig))?;
+        if v > MAX_NANOS / unit {
+            // overflow
+            return Err(invalid());
+        }
+        v *= unit;
+        if f > 0 {
+            // f64 is needed to be nanosecond accurate for fractions of hours.
+            // v >= 0 && (f*unit/scale) <= 3.6e+12 (ns/h, h is the largest unit)
+            v += (f as f64 * (unit as f64 / scale)) as u64;
+            if v > MAX_NANOS {
+                return Err(invalid());
+            }
+        }
+        d = d.checked_add(v).ok_or_else(invalid)?;
+        if d > MAX_NANOS {
+            return Err(invalid());
+        }
+    }
+    if neg {
+        if d != 0 {
+            return Err(err!("time: negative duration {:?}", orig));
+        }
+        return Ok(Duration::from_secs(0));
+    }
+    if d > MAX_NANOS - 1 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_nanos(d))
+}
+
+/// format a duration like go's `Duration.String`, such as "72h3m0.5s"
+///
+/// the leading zero units are omitted. as a special case, the durations
+/// less than one second use a smaller unit to make sure the leading digit
+/// is non-zero, and the zero duration formats as "0s".
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::time::format_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m0s");
+/// assert_eq!(format_duration(Duration::from_micros(1500)), "1.5ms");
+/// assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+/// ```
+pub fn format_duration(d: Duration) -> String {
+    format_nanos(d.as_nanos() as i128)
+}
+
+// the digits are written backwards, then reversed
+pub(crate) fn format_nanos(d: i128) -> String {
+    let mut buf: Vec<char> = Vec::with_capacity(32);
+    let neg = d < 0;
+    let mut u = d.unsigned_abs();
+    if u < SECOND as u128 {
+        // special case: if duration is smaller than a second,
+        // use smaller units, like 1.2ms
+        buf.push('s');
+        let prec = if u == 0 {
+            return "0s".to_string();
+        } else if u < MICROSECOND as u128 {
+            buf.push('n');
+            0
+        } else if u < MILLISECOND as u128 {
+            buf.push('µ');
+            3
+        } else {
+            buf.push('m');
+            6
+        };
+        u = fmt_frac(&mut buf, u, prec);
+        fmt_int(&mut buf, u);
+    } else {
+        buf.push('s');
+        u = fmt_frac(&mut buf, u, 9);
+        // u is now integer seconds
+        fmt_int(&mut buf, u % 60);
+        u /= 60;
+        // u is now integer minutes
+        if u > 0 {
+            buf.push('m');
+            fmt_int(&mut buf, u % 60);
+            u /= 60;
+            // u is now integer hours
+            if u > 0 {
+                buf.push('h');
+                fmt_int(&mut buf, u);
+            }
+        }
+    }
+    if neg {
+        buf.push('-');
+    }
+    buf.iter().rev().collect()
+}
+
+// write the fraction of v / 10^prec, omitting the trailing zeros. it omits
+// the decimal point too when the fraction is 0. return v / 10^prec
+fn fmt_frac(buf: &mut Vec<char>, mut v: u128, prec: usize) -> u128 {
+    let mut print = false;
+    for _ in 0..prec {
+        let digit = (v % 10) as u8;
+        print = print || digit != 0;
+        if print {
+            buf.push((b'0' + digit) as char);
+        }
+        v /= 10;
+    }
+    if print {
+        buf.push('.');
+    }
+    v
+}
+
+fn fmt_int(buf: &mut Vec<char>, mut v: u128) {
+    if v == 0 {
+        buf.push('0');
+        return;
+    }
+    while v > 0 {
+        buf.push((b'0' + (v % 10) as u8) as char);
+        v /= 10;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let cases: &[(&str, u64)] = &[
+            ("0", 0),
+            ("5s", 5 * SECOND),
+            ("30s", 30 * SECOND),
+            ("1478s", 1478 * SECOND),
+            ("-0", 0),
+            ("+5s", 5 * SECOND),
+            ("-0s", 0),
+            ("5.0s", 5 * SECOND),
+            ("5.6s", 5 * SECOND + 600 * MILLISECOND),
+            ("5.s", 5 * SECOND),
+            (".5s", 500 * MILLISECOND),
+            ("1.0s", SECOND),
+            ("1.00s", SECOND),
+            ("1.004s", SECOND + 4 * MILLISECOND),
+            ("1.0040s", SECOND + 4 * MILLISECOND),
+            ("100.00100s", 100 * SECOND + MILLISECOND),
+            ("10ns", 10 * NANOSECOND),
+            ("11us", 11 * MICROSECOND),
+            ("12µs", 12 * MICROSECOND),
+            ("12μs", 12 * MICROSECOND),
+            ("13ms", 13 * MILLISECOND),
+            ("14s", 14 * SECOND),
+            ("15m", 15 * MINUTE),
+            ("16h", 16 * HOUR),
+            ("3h30m", 3 * HOUR + 30 * MINUTE),
+            ("10.5s4m", 4 * MINUTE + 10 * SECOND + 500 * MILLISECOND),
+            (
+                "1h2m3s4ms5us6ns",
+                HOUR + 2 * MINUTE + 3 * SECOND + 4 * MILLISECOND + 5 * MICROSECOND + 6,
+            ),
+            (
+                "39h9m14.425s",
+                39 * HOUR + 9 * MINUTE + 14 * SECOND + 425 * MILLISECOND,
+            ),
+            // large value
+            ("52763797000ns", 52763797000 * NANOSECOND),
+            // more than 9 digits after decimal point
+            ("0.3333333333333333333h", 20 * MINUTE),
+            // 9007199254740993 = 1<<53+1 cannot be stored precisely in a float64
+            ("9007199254740993ns", (1 << 53) + 1),
+            // largest duration that can be represented by go
+            ("9223372036854775807ns", (1 << 63) - 1),
+            ("9223372036854775.807us", (1 << 63) - 1),
+            ("9223372036s854ms775us807ns", (1 << 63) - 1),
+            // huge string, we can handle that
+            ("0.100000000000000000000h", 6 * MINUTE),
+            // this value tests the first overflow check in leading_fraction
+            (
+                "0.830103483285477580700h",
+                49 * MINUTE + 48 * SECOND + 372539827 * NANOSECOND,
+            ),
+        ];
+        for (s, d) in cases {
+            assert_eq!(
+                parse_duration(s).unwrap(),
+                Duration::from_nanos(*d),
+                "{}",
+                s
+            );
+        }
+
+        let errors = &[
+            "",
+            "3",
+            "-",
+            "s",
+            ".",
+            "-.",
+            ".s",
+            "+.s",
+            "1d",
+            "\u{85}\u{85}",
+            "\u{ffff}",
+            "hello \u{ffff} world",
+            "9223372036854775808ns",
+            "9223372036854775.808us",
+            "9223372036854ms775us808ns",
+            "9223372036854775808ns9223372036854775808ns",
+            "-1s",
+        ];
+        for s in errors {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+        assert_eq!(
+            parse_duration("1d").unwrap_err().to_string(),
+            r#"time: unknown unit "d" in duration "1d""#
+        );
+        assert_eq!(
+            parse_duration("3").unwrap_err().to_string(),
+            r#"time: missing unit in duration "3""#
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let cases: &[(&str, i128)] = &[
+            ("0s", 0),
+            ("1ns", 1),
+            ("1.1µs", 1100),
+            ("2.2ms", 2200 * MICROSECOND as i128),
+            ("3.3s", 3300 * MILLISECOND as i128),
+            ("4m5s", (4 * MINUTE + 5 * SECOND) as i128),
+            ("4m5.001s", (4 * MINUTE + 5001 * MILLISECOND) as i128),
+            (
+                "5h6m7.001s",
+                (5 * HOUR + 6 * MINUTE + 7001 * MILLISECOND) as i128,
+            ),
+            ("8m0.000000001s", (8 * MINUTE + 1) as i128),
+            ("2562047h47m16.854775807s", (1 << 63) - 1),
+            ("-2562047h47m16.854775808s", -(1 << 63)),
+            ("-1.5µs", -1500),
+        ];
+        for (s, d) in cases {
+            assert_eq!(format_nanos(*d), *s);
+        }
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        // the durations beyond go are formatted in hours too
+        assert_eq!(
+            format_duration(Duration::from_secs(1 << 40)),
+            "305419896h36m16s"
+        );
+        for s in &["1h30m0s", "250ms", "1.5s", "9223372036.854775807s"] {
+            let d = parse_duration(s).unwrap();
+            assert_eq!(parse_duration(&format_duration(d)).unwrap(), d);
+        }
+    }
+}
@@ -1,4 +1,8 @@
+use crate::std::errors::Result;
 use crate::std::lazy::sync::Lazy;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
 
 pub static LONG_DAY_NAMES: Lazy<Vec<&str>> = Lazy::new(|| {
     vec![
@@ -37,3 +41,788 @@ pub static LONG_MONTH_NAMES: Lazy<Vec<&str>> = Lazy::new(|| {
         "December",
     ]
 });
+
+// the elements of a go reference layout, "Mon Jan 2 15:04:05 MST 2006"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Std {
+    LongMonth,             // "January"
+    Month,                 // "Jan"
+    NumMonth,              // "1"
+    ZeroMonth,             // "01"
+    LongWeekDay,           // "Monday"
+    WeekDay,               // "Mon"
+    Day,                   // "2"
+    UnderDay,              // "_2"
+    ZeroDay,               // "02"
+    UnderYearDay,          // "__2"
+    ZeroYearDay,           // "002"
+    Hour,                  // "15"
+    Hour12,                // "3"
+    ZeroHour12,            // "03"
+    Minute,                // "4"
+    ZeroMinute,            // "04"
+    Second,                // "5"
+    ZeroSecond,            // "05"
+    LongYear,              // "2006"
+    Year,                  // "06"
+    PM,                    // "PM"
+    Pm,                    // "pm"
+    TZ,                    // "MST"
+    ISO8601TZ,             // "Z0700", Z is used for UTC
+    ISO8601SecondsTZ,      // "Z070000"
+    ISO8601ShortTZ,        // "Z07"
+    ISO8601ColonTZ,        // "Z07:00"
+    ISO8601ColonSecondsTZ, // "Z07:00:00"
+    NumTZ,                 // "-0700"
+    NumSecondsTz,          // "-070000"
+    NumShortTZ,            // "-07"
+    NumColonTZ,            // "-07:00"
+    NumColonSecondsTZ,     // "-07:00:00"
+    // ".000" or ",000", the trailing zeros are kept
+    FracSecond0(usize, u8),
+    // ".999" or ",999", the trailing zeros are trimmed
+    FracSecond9(usize, u8),
+}
+
+impl Std {
+    fn is_iso8601(self) -> bool {
+        matches!(
+            self,
+            Std::ISO8601TZ
+                | Std::ISO8601SecondsTZ
+                | Std::ISO8601ShortTZ
+                | Std::ISO8601ColonTZ
+                | Std::ISO8601ColonSecondsTZ
+        )
+    }
+}
+
+fn starts_with_lower_case(s: &[u8]) -> bool {
+    matches!(s.first(), Some(b'a'..=b'z'))
+}
+
+fn is_digit(s: &[u8], i: usize) -> bool {
+    matches!(s.get(i), Some(b'0'..=b'9'))
+}
+
+// find the first element in the layout, return the literal prefix before
+// it, the element and the remaining layout
+fn next_std_chunk(layout: &[u8]) -> (&[u8], Option<Std>, &[u8]) {
+    let at = |i: usize, s: &str| layout[i..].starts_with(s.as_bytes());
+    for i in 0..layout.len() {
+        let (std, len) = match layout[i] {
+            // January, Jan
+            b'J' if at(i, "Jan") => {
+                if at(i, "January") {
+                    (Std::LongMonth, 7)
+                } else if !starts_with_lower_case(&layout[i + 3..]) {
+                    (Std::Month, 3)
+                } else {
+                    continue;
+                }
+            }
+            // Monday, Mon, MST
+            b'M' if at(i, "Mon") => {
+                if at(i, "Monday") {
+                    (Std::LongWeekDay, 6)
+                } else if !starts_with_lower_case(&layout[i + 3..]) {
+                    (Std::WeekDay, 3)
+                } else {
+                    continue;
+                }
+            }
+            b'M' if at(i, "MST") => (Std::TZ, 3),
+            // 01, 02, 03, 04, 05, 06, 002
+            b'0' => match layout.get(i + 1) {
+                Some(b'1') => (Std::ZeroMonth, 2),
+                Some(b'2') => (Std::ZeroDay, 2),
+                Some(b'3') => (Std::ZeroHour12, 2),
+                Some(b'4') => (Std::ZeroMinute, 2),
+                Some(b'5') => (Std::ZeroSecond, 2),
+                Some(b'6') => (Std::Year, 2),
+                _ if at(i, "002") => (Std::ZeroYearDay, 3),
+                _ => continue,
+            },
+            // 15, 1
+            b'1' if at(i, "15") => (Std::Hour, 2),
+            b'1' => (Std::NumMonth, 1),
+            // 2006, 2
+            b'2' if at(i, "2006") => (Std::LongYear, 4),
+            b'2' => (Std::Day, 1),
+            // _2, _2006, __2
+            b'_' if at(i, "_2006") => {
+                // a literal _ followed by the long year
+                return (&layout[..i + 1], Some(Std::LongYear), &layout[i + 5..]);
+            }
+            b'_' if at(i, "_2") => (Std::UnderDay, 2),
+            b'_' if at(i, "__2") => (Std::UnderYearDay, 3),
+            b'3' => (Std::Hour12, 1),
+            b'4' => (Std::Minute, 1),
+            b'5' => (Std::Second, 1),
+            b'P' if at(i, "PM") => (Std::PM, 2),
+            b'p' if at(i, "pm") => (Std::Pm, 2),
+            b'-' if at(i, "-070000") => (Std::NumSecondsTz, 7),
+            b'-' if at(i, "-07:00:00") => (Std::NumColonSecondsTZ, 9),
+            b'-' if at(i, "-0700") => (Std::NumTZ, 5),
+            b'-' if at(i, "-07:00") => (Std::NumColonTZ, 6),
+            b'-' if at(i, "-07") => (Std::NumShortTZ, 3),
+            b'Z' if at(i, "Z070000") => (Std::ISO8601SecondsTZ, 7),
+            b'Z' if at(i, "Z07:00:00") => (Std::ISO8601ColonSecondsTZ, 9),
+            b'Z' if at(i, "Z0700") => (Std::ISO8601TZ, 5),
+            b'Z' if at(i, "Z07:00") => (Std::ISO8601ColonTZ, 6),
+            b'Z' if at(i, "Z07") => (Std::ISO8601ShortTZ, 3),
+            // .000, .999, ,000, ,999, repeated digits for fractional seconds
+            sep @ (b'.' | b',') => match layout.get(i + 1) {
+                Some(&ch @ (b'0' | b'9')) => {
+                    let mut j = i + 1;
+                    while j < layout.len() && layout[j] == ch {
+                        j += 1;
+                    }
+                    // only fractional second if all the digits match
+                    if is_digit(layout, j) {
+                        continue;
+                    }
+                    let n = j - (i + 1);
+                    let std = if ch == b'0' {
+                        Std::FracSecond0(n, sep)
+                    } else {
+                        Std::FracSecond9(n, sep)
+                    };
+                    (std, n + 1)
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        return (&layout[..i], Some(std), &layout[i + len..]);
+    }
+    (layout, None, &[])
+}
+
+// append the integer zero padded to the width
+fn append_int(b: &mut String, x: i64, width: usize) {
+    if x < 0 {
+        b.push('-');
+    }
+    let _ = write!(b, "{:01$}", x.unsigned_abs(), width);
+}
+
+fn append_nano(b: &mut String, nanosec: u32, std: Std) {
+    let (n, sep, trim) = match std {
+        Std::FracSecond0(n, sep) => (n, sep, false),
+        Std::FracSecond9(n, sep) => (n, sep, true),
+        _ => return,
+    };
+    if trim && (n == 0 || nanosec == 0) {
+        return;
+    }
+    let mut digits = format!("{:09}", nanosec);
+    digits.truncate(n.min(9));
+    if trim {
+        digits.truncate(digits.trim_end_matches('0').len());
+        if digits.is_empty() {
+            return;
+        }
+    }
+    b.push(sep as char);
+    b.push_str(&digits);
+}
+
+/// format the time by a go reference layout, like go's `Time.Format`
+///
+/// `zone` is the abbreviated zone name printed for "MST", the numeric
+/// offset is printed if it's unknown.
+pub(crate) fn format(t: &OffsetDateTime, zone: Option<&str>, layout: &str) -> String {
+    let mut b = String::with_capacity(layout.len() + 10);
+    let mut layout = layout.as_bytes();
+    let offset = t.offset().whole_seconds() as i64;
+    loop {
+        let (prefix, std, suffix) = next_std_chunk(layout);
+        b.push_str(&String::from_utf8_lossy(prefix));
+        let std = match std {
+            Some(std) => std,
+            None => break,
+        };
+        layout = suffix;
+        let hour = t.hour() as i64;
+        match std {
+            Std::Year => append_int(&mut b, (t.year() as i64).rem_euclid(100), 2),
+            Std::LongYear => append_int(&mut b, t.year() as i64, 4),
+            Std::Month => b.push_str(SHORT_MONTH_NAMES[t.month() as usize - 1]),
+            Std::LongMonth => b.push_str(LONG_MONTH_NAMES[t.month() as usize - 1]),
+            Std::NumMonth => append_int(&mut b, t.month() as i64, 0),
+            Std::ZeroMonth => append_int(&mut b, t.month() as i64, 2),
+            Std::WeekDay => {
+                b.push_str(SHORT_DAY_NAMES[t.weekday().number_days_from_sunday() as usize])
+            }
+            Std::LongWeekDay => {
+                b.push_str(LONG_DAY_NAMES[t.weekday().number_days_from_sunday() as usize])
+            }
+            Std::Day => append_int(&mut b, t.day() as i64, 0),
+            Std::UnderDay => {
+                if t.day() < 10 {
+                    b.push(' ');
+                }
+                append_int(&mut b, t.day() as i64, 0);
+            }
+            Std::ZeroDay => append_int(&mut b, t.day() as i64, 2),
+            Std::UnderYearDay => {
+                let _ = write!(b, "{:>3}", t.ordinal());
+            }
+            Std::ZeroYearDay => append_int(&mut b, t.ordinal() as i64, 3),
+            Std::Hour => append_int(&mut b, hour, 2),
+            Std::Hour12 | Std::ZeroHour12 => {
+                // noon is 12PM, midnight is 12AM
+                let hr = match hour % 12 {
+                    0 => 12,
+                    hr => hr,
+                };
+                let width = if std == Std::ZeroHour12 { 2 } else { 0 };
+                append_int(&mut b, hr, width);
+            }
+            Std::Minute => append_int(&mut b, t.minute() as i64, 0),
+            Std::ZeroMinute => append_int(&mut b, t.minute() as i64, 2),
+            Std::Second => append_int(&mut b, t.second() as i64, 0),
+            Std::ZeroSecond => append_int(&mut b, t.second() as i64, 2),
+            Std::PM => b.push_str(if hour >= 12 { "PM" } else { "AM" }),
+            Std::Pm => b.push_str(if hour >= 12 { "pm" } else { "am" }),
+            Std::TZ => match zone {
+                Some(name) if !name.is_empty() => b.push_str(name),
+                _ if offset == 0 => b.push_str("UTC"),
+                // no zone name for the time, but we must print one
+                _ => {
+                    let zone = offset / 60;
+                    b.push(if zone < 0 { '-' } else { '+' });
+                    append_int(&mut b, zone.abs() / 60, 2);
+                    append_int(&mut b, zone.abs() % 60, 2);
+                }
+            },
+            Std::FracSecond0(..) | Std::FracSecond9(..) => append_nano(&mut b, t.nanosecond(), std),
+            // the time zone offsets
+            _ => {
+                if offset == 0 && std.is_iso8601() {
+                    b.push('Z');
+                    continue;
+                }
+                let abs = offset.abs();
+                b.push(if offset < 0 { '-' } else { '+' });
+                append_int(&mut b, abs / 3600, 2);
+                let colon = matches!(
+                    std,
+                    Std::ISO8601ColonTZ
+                        | Std::NumColonTZ
+                        | Std::ISO8601ColonSecondsTZ
+                        | Std::NumColonSecondsTZ
+                );
+                if !matches!(std, Std::NumShortTZ | Std::ISO8601ShortTZ) {
+                    if colon {
+                        b.push(':');
+                    }
+                    append_int(&mut b, abs / 60 % 60, 2);
+                }
+                if matches!(
+                    std,
+                    Std::ISO8601SecondsTZ
+                        | Std::NumSecondsTz
+                        | Std::ISO8601ColonSecondsTZ
+                        | Std::NumColonSecondsTZ
+                ) {
+                    if colon {
+                        b.push(':');
+                    }
+                    append_int(&mut b, abs % 60, 2);
+                }
+            }
+        }
+    }
+    b
+}
+
+// a parse failure, before it's turned into the error message
+enum ParseErr {
+    // the value doesn't match the element of the layout
+    Bad,
+    // the value is parsed but out of range
+    Range(&'static str),
+}
+
+// skip the literal prefix of the layout, the spaces match any runs of spaces
+fn skip<'a>(mut value: &'a [u8], mut prefix: &[u8]) -> Option<&'a [u8]> {
+    fn cut_space(s: &[u8]) -> &[u8] {
+        let n = s.iter().take_while(|c| **c == b' ').count();
+        &s[n..]
+    }
+    while let Some(&c) = prefix.first() {
+        if c == b' ' {
+            if !value.is_empty() && value[0] != b' ' {
+                return None;
+            }
+            prefix = cut_space(prefix);
+            value = cut_space(value);
+            continue;
+        }
+        if value.first() != Some(&c) {
+            return None;
+        }
+        prefix = &prefix[1..];
+        value = &value[1..];
+    }
+    Some(value)
+}
+
+// parse one or two digits, exactly two if fixed
+fn getnum(s: &[u8], fixed: bool) -> std::result::Result<(i64, &[u8]), ParseErr> {
+    if !is_digit(s, 0) {
+        return Err(ParseErr::Bad);
+    }
+    if !is_digit(s, 1) {
+        if fixed {
+            return Err(ParseErr::Bad);
+        }
+        return Ok(((s[0] - b'0') as i64, &s[1..]));
+    }
+    Ok((((s[0] - b'0') * 10 + (s[1] - b'0')) as i64, &s[2..]))
+}
+
+// parse one to three digits, exactly three if fixed
+fn getnum3(s: &[u8], fixed: bool) -> std::result::Result<(i64, &[u8]), ParseErr> {
+    let n = s.iter().take(3).take_while(|c| c.is_ascii_digit()).count();
+    if n == 0 || (fixed && n != 3) {
+        return Err(ParseErr::Bad);
+    }
+    let x = s[..n].iter().fold(0, |x, c| x * 10 + (c - b'0') as i64);
+    Ok((x, &s[n..]))
+}
+
+// match the names case insensitively, return the index
+fn lookup<'a>(names: &[&str], value: &'a [u8]) -> std::result::Result<(usize, &'a [u8]), ParseErr> {
+    for (i, name) in names.iter().enumerate() {
+        let n = name.len();
+        if value.len() >= n && value[..n].eq_ignore_ascii_case(name.as_bytes()) {
+            return Ok((i, &value[n..]));
+        }
+    }
+    Err(ParseErr::Bad)
+}
+
+// parse the fractional second with the separator in the first byte
+fn parse_nanoseconds(value: &[u8], nbytes: usize) -> std::result::Result<u32, ParseErr> {
+    if !matches!(value[0], b'.' | b',') {
+        return Err(ParseErr::Bad);
+    }
+    // the digits after the nanoseconds are truncated
+    let digits = &value[1..nbytes.min(10)];
+    if digits.is_empty() || !digits.iter().all(|c| c.is_ascii_digit()) {
+        return Err(ParseErr::Bad);
+    }
+    let ns = digits.iter().fold(0u32, |x, c| x * 10 + (c - b'0') as u32);
+    Ok(ns * 10u32.pow(9 - digits.len() as u32))
+}
+
+// the length of "+hh" or "-hh" with the hours at most 12, 0 if it's not
+fn parse_signed_offset(value: &[u8]) -> usize {
+    if !matches!(value.first(), Some(b'+' | b'-')) {
+        return 0;
+    }
+    let n = value[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+    if n == 0 {
+        return 0;
+    }
+    match std::str::from_utf8(&value[1..1 + n])
+        .ok()
+        .and_then(|s| s.parse::<u32>().ok())
+    {
+        Some(x) if x <= 12 => 1 + n,
+        _ => 0,
+    }
+}
+
+// the length of the time zone abbreviation at the start of the value
+fn parse_time_zone(value: &[u8]) -> Option<usize> {
+    if value.len() < 3 {
+        return None;
+    }
+    // special case 1: ChST and MeST
+    if value.starts_with(b"ChST") || value.starts_with(b"MeST") {
+        return Some(4);
+    }
+    // special case 2: GMT may have an hour offset
+    if value.starts_with(b"GMT") {
+        return Some(3 + parse_signed_offset(&value[3..]));
+    }
+    // special case 3: some time zones are not named, but have +/-00 format
+    if value[0] == b'+' || value[0] == b'-' {
+        return match parse_signed_offset(value) {
+            0 => None,
+            n => Some(n),
+        };
+    }
+    // three to five upper case letters
+    let upper = value
+        .iter()
+        .take(6)
+        .take_while(|c| c.is_ascii_uppercase())
+        .count();
+    match upper {
+        // must end in T to match
+        5 if value[4] == b'T' => Some(5),
+        // must end in T, except one special case
+        4 if value[3] == b'T' || value.starts_with(b"WITA") => Some(4),
+        3 => Some(3),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+// the days before the month in a non-leap year
+pub(crate) const DAYS_BEFORE: [i64; 13] = [
+    0,
+    31,
+    31 + 28,
+    31 + 28 + 31,
+    31 + 28 + 31 + 30,
+    31 + 28 + 31 + 30 + 31,
+    31 + 28 + 31 + 30 + 31 + 30,
+    31 + 28 + 31 + 30 + 31 + 30 + 31,
+    31 + 28 + 31 + 30 + 31 + 30 + 31 + 31,
+    31 + 28 + 31 + 30 + 31 + 30 + 31 + 31 + 30,
+    31 + 28 + 31 + 30 + 31 + 30 + 31 + 31 + 30 + 31,
+    31 + 28 + 31 + 30 + 31 + 30 + 31 + 31 + 30 + 31 + 30,
+    31 + 28 + 31 + 30 + 31 + 30 + 31 + 31 + 30 + 31 + 30 + 31,
+];
+
+pub(crate) fn days_in(month: i64, year: i64) -> i64 {
+    if month == 2 && is_leap(year) {
+        return 29;
+    }
+    DAYS_BEFORE[month as usize] - DAYS_BEFORE[month as usize - 1]
+}
+
+/// The fields parsed from a value by a go reference layout
+pub(crate) struct Parsed {
+    /// the date and time without the zone
+    pub datetime: PrimitiveDateTime,
+    /// the offset in the value, "Z" or a numeric one
+    pub offset: Option<UtcOffset>,
+    /// the zone abbreviation in the value, it's "UTC" with a zero offset for
+    /// "UTC" and "Z"
+    pub zone: Option<String>,
+}
+
+/// parse the value by a go reference layout, like go's `time.Parse`
+///
+/// the zone is not resolved here, the caller decides what a zone name or
+/// a missing zone means.
+pub(crate) fn parse(layout: &str, value: &str) -> Result<Parsed> {
+    let (alayout, avalue) = (layout, value);
+    let mut layout = layout.as_bytes();
+    let mut value = value.as_bytes();
+    let err = |elem: &[u8], hold: &[u8]| {
+        err!(
+            "parsing time {:?} as {:?}: cannot parse {:?} as {:?}",
+            avalue,
+            alayout,
+            String::from_utf8_lossy(hold),
+            String::from_utf8_lossy(elem)
+        )
+    };
+    let range_err = |what: &str| err!("parsing time {:?}: {} out of range", avalue, what);
+
+    let (mut am_set, mut pm_set) = (false, false);
+    let (mut year, mut month, mut day, mut yday) = (0i64, -1i64, -1i64, -1i64);
+    let (mut hour, mut min, mut sec, mut nsec) = (0i64, 0i64, 0i64, 0u32);
+    let mut offset = None;
+    let mut zone = None;
+    loop {
+        let (prefix, std, suffix) = next_std_chunk(layout);
+        let elem = &layout[prefix.len()..layout.len() - suffix.len()];
+        value = match skip(value, prefix) {
+            Some(v) => v,
+            None => return Err(err(prefix, value)),
+        };
+        let std = match std {
+            Some(std) => std,
+            None if value.is_empty() => break,
+            None => {
+                return Err(err!(
+                    "parsing time {:?}: extra text: {:?}",
+                    avalue,
+                    String::from_utf8_lossy(value)
+                ))
+            }
+        };
+        layout = suffix;
+        let hold = value;
+        let ret: std::result::Result<(), ParseErr> = (|| {
+            match std {
+                Std::Year => {
+                    let (y, rest) = getnum(value, true)?;
+                    value = rest;
+                    year = if y >= 69 { y + 1900 } else { y + 2000 };
+                }
+                Std::LongYear => {
+                    if value.len() < 4 || !value[..4].iter().all(|c| c.is_ascii_digit()) {
+                        return Err(ParseErr::Bad);
+                    }
+                    year = value[..4].iter().fold(0, |x, c| x * 10 + (c - b'0') as i64);
+                    value = &value[4..];
+                }
+                Std::Month | Std::LongMonth => {
+                    let names = if std == Std::Month {
+                        &SHORT_MONTH_NAMES
+                    } else {
+                        &LONG_MONTH_NAMES
+                    };
+                    let (m, rest) = lookup(names, value)?;
+                    month = m as i64 + 1;
+                    value = rest;
+                }
+                Std::NumMonth | Std::ZeroMonth => {
+                    let (m, rest) = getnum(value, std == Std::ZeroMonth)?;
+                    value = rest;
+                    month = m;
+                    if !(1..=12).contains(&month) {
+                        return Err(ParseErr::Range("month"));
+                    }
+                }
+                Std::WeekDay | Std::LongWeekDay => {
+                    // the weekday is checked by nothing, like go
+                    let names = if std == Std::WeekDay {
+                        &SHORT_DAY_NAMES
+                    } else {
+                        &LONG_DAY_NAMES
+                    };
+                    value = lookup(names, value)?.1;
+                }
+                Std::Day | Std::UnderDay | Std::ZeroDay => {
+                    if std == Std::UnderDay && value.first() == Some(&b' ') {
+                        value = &value[1..];
+                    }
+                    // the day is validated with the month and year at last
+                    let (d, rest) = getnum(value, std == Std::ZeroDay)?;
+                    day = d;
+                    value = rest;
+                }
+                Std::UnderYearDay | Std::ZeroYearDay => {
+                    for _ in 0..2 {
+                        if std == Std::UnderYearDay && value.first() == Some(&b' ') {
+                            value = &value[1..];
+                        }
+                    }
+                    let (d, rest) = getnum3(value, std == Std::ZeroYearDay)?;
+                    yday = d;
+                    value = rest;
+                }
+                Std::Hour => {
+                    let (h, rest) = getnum(value, false)?;
+                    hour = h;
+                    value = rest;
+                    if !(0..24).contains(&hour) {
+                        return Err(ParseErr::Range("hour"));
+                    }
+                }
+                Std::Hour12 | Std::ZeroHour12 => {
+                    let (h, rest) = getnum(value, std == Std::ZeroHour12)?;
+                    hour = h;
+                    value = rest;
+                    if !(0..=12).contains(&hour) {
+                        return Err(ParseErr::Range("hour"));
+                    }
+                }
+                Std::Minute | Std::ZeroMinute => {
+                    let (m, rest) = getnum(value, std == Std::ZeroMinute)?;
+                    min = m;
+                    value = rest;
+                    if !(0..60).contains(&min) {
+                        return Err(ParseErr::Range("minute"));
+                    }
+                }
+                Std::Second | Std::ZeroSecond => {
+                    let (s, rest) = getnum(value, std == Std::ZeroSecond)?;
+                    sec = s;
+                    value = rest;
+                    if !(0..60).contains(&sec) {
+                        return Err(ParseErr::Range("second"));
+                    }
+                    // a fractional second in the value but not in the layout
+                    if value.len() >= 2 && matches!(value[0], b'.' | b',') && is_digit(value, 1) {
+                        if let (_, Some(Std::FracSecond0(..) | Std::FracSecond9(..)), _) =
+                            next_std_chunk(layout)
+                        {
+                            return Ok(());
+                        }
+                        let n = 1 + value[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+                        nsec = parse_nanoseconds(value, n)?;
+                        value = &value[n..];
+                    }
+                }
+                Std::PM | Std::Pm => {
+                    if value.len() < 2 {
+                        return Err(ParseErr::Bad);
+                    }
+                    let (am, pm) = if std == Std::PM {
+                        (&b"AM"[..], &b"PM"[..])
+                    } else {
+                        (&b"am"[..], &b"pm"[..])
+                    };
+                    match &value[..2] {
+                        p if p == pm => pm_set = true,
+                        p if p == am => am_set = true,
+                        _ => return Err(ParseErr::Bad),
+                    }
+                    value = &value[2..];
+                }
+                Std::TZ => {
+                    if value.starts_with(b"UTC") {
+                        offset = Some(UtcOffset::UTC);
+                        zone = Some("UTC".to_string());
+                        value = &value[3..];
+                        return Ok(());
+                    }
+                    let n = parse_time_zone(value).ok_or(ParseErr::Bad)?;
+                    zone = Some(String::from_utf8_lossy(&value[..n]).into_owned());
+                    value = &value[n..];
+                }
+                Std::FracSecond0(n, _) => {
+                    // the exact number of digits in the layout is required
+                    if value.len() < n + 1 {
+                        return Err(ParseErr::Bad);
+                    }
+                    nsec = parse_nanoseconds(value, n + 1)?;
+                    value = &value[n + 1..];
+                }
+                Std::FracSecond9(..) => {
+                    // the fractional second is omitted
+                    if value.len() < 2 || !matches!(value[0], b'.' | b',') || !is_digit(value, 1) {
+                        return Ok(());
+                    }
+                    // take any number of digits, even more than asked for
+                    let n = 1 + value[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+                    nsec = parse_nanoseconds(value, n)?;
+                    value = &value[n..];
+                }
+                // the time zone offsets
+                _ => {
+                    if std.is_iso8601() && value.first() == Some(&b'Z') {
+                        value = &value[1..];
+                        offset = Some(UtcOffset::UTC);
+                        zone = Some("UTC".to_string());
+                        return Ok(());
+                    }
+                    // the positions of hour, minute and second, and the length
+                    let (h, m, s, n) = match std {
+                        Std::ISO8601ColonTZ | Std::NumColonTZ => (1, Some(4), None, 6),
+                        Std::ISO8601ShortTZ | Std::NumShortTZ => (1, None, None, 3),
+                        Std::ISO8601ColonSecondsTZ | Std::NumColonSecondsTZ => {
+                            (1, Some(4), Some(7), 9)
+                        }
+                        Std::ISO8601SecondsTZ | Std::NumSecondsTz => (1, Some(3), Some(5), 7),
+                        _ => (1, Some(3), None, 5),
+                    };
+                    if value.len() < n {
+                        return Err(ParseErr::Bad);
+                    }
+                    let colon = matches!(
+                        std,
+                        Std::ISO8601ColonTZ
+                            | Std::NumColonTZ
+                            | Std::ISO8601ColonSecondsTZ
+                            | Std::NumColonSecondsTZ
+                    );
+                    if colon && (value[3] != b':' || (s.is_some() && value[6] != b':')) {
+                        return Err(ParseErr::Bad);
+                    }
+                    let num = |i: Option<usize>| match i {
+                        Some(i) => getnum(&value[i..i + 2], true).map(|(x, _)| x),
+                        None => Ok(0),
+                    };
+                    let secs = (num(Some(h))? * 60 + num(m)?) * 60 + num(s)?;
+                    let secs = match value[0] {
+                        b'+' => secs,
+                        b'-' => -secs,
+                        _ => return Err(ParseErr::Bad),
+                    };
+                    let secs = UtcOffset::from_whole_seconds(secs as i32);
+                    offset = Some(secs.map_err(|_| ParseErr::Bad)?);
+                    value = &value[n..];
+                }
+            }
+            Ok(())
+        })();
+        match ret {
+            Ok(()) => {}
+            Err(ParseErr::Bad) => return Err(err(elem, hold)),
+            Err(ParseErr::Range(what)) => return Err(range_err(what)),
+        }
+    }
+    if pm_set && hour < 12 {
+        hour += 12;
+    } else if am_set && hour == 12 {
+        hour = 0;
+    }
+
+    // convert the day of year to the month and day
+    if yday >= 0 {
+        let (mut m, mut d) = (0, 0);
+        if is_leap(year) {
+            if yday == 31 + 29 {
+                m = 2;
+                d = 29;
+            } else if yday > 31 + 29 {
+                yday -= 1;
+            }
+        }
+        if !(1..=365).contains(&yday) {
+            return Err(range_err("day-of-year"));
+        }
+        if m == 0 {
+            m = (yday - 1) / 31 + 1;
+            if DAYS_BEFORE[m as usize] < yday {
+                m += 1;
+            }
+            d = yday - DAYS_BEFORE[m as usize - 1];
+        }
+        // the month and day seen must match the day of year
+        if month >= 0 && month != m {
+            return Err(err!(
+                "parsing time {:?}: day-of-year does not match month",
+                avalue
+            ));
+        }
+        month = m;
+        if day >= 0 && day != d {
+            return Err(err!(
+                "parsing time {:?}: day-of-year does not match day",
+                avalue
+            ));
+        }
+        day = d;
+    } else {
+        if month < 0 {
+            month = 1;
+        }
+        if day < 0 {
+            day = 1;
+        }
+    }
+    if day < 1 || day > days_in(month, year) {
+        return Err(range_err("day"));
+    }
+
+    let date = Date::from_calendar_date(
+        year as i32,
+        time::Month::try_from(month as u8).map_err(|_| range_err("month"))?,
+        day as u8,
+    )
+    .map_err(|_| range_err("year"))?;
+    let t = time::Time::from_hms_nano(hour as u8, min as u8, sec as u8, nsec)
+        .map_err(|_| range_err("time"))?;
+    Ok(Parsed {
+        datetime: PrimitiveDateTime::new(date, t),
+        offset,
+        zone,
+    })
+}
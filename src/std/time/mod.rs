@@ -1,8 +1,16 @@
+pub mod clock;
+pub mod duration;
 pub mod format;
 pub mod sys;
 pub mod tick;
 pub mod time;
+pub mod timer;
+pub mod zoneinfo;
 
+pub use self::clock::*;
+pub use self::duration::*;
 pub use self::format::*;
 pub use self::tick::*;
 pub use self::time::*;
+pub use self::timer::*;
+pub use self::zoneinfo::*;
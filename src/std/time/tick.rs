@@ -1,36 +1,39 @@
-use crate::coroutine::sleep;
-use crate::std::errors::Result;
-use crate::std::sync::channel::Receiver;
-use crate::std::sync::Mutex;
+use crate::std::sync::channel::{bounded, Receiver};
 use crate::std::time::time::Time;
-use std::ops::Deref;
+use crate::std::time::timer::{Action, Inner};
+use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
 
 /// A Ticker holds a channel that delivers ``ticks'' of a clock
 /// at intervals.
+///
+/// the ticks are scheduled against absolute deadlines by the timer thread,
+/// so they don't drift. the channel holds only one tick, if the receiver is
+/// slow the ticks are dropped instead of piling up, like go's `time.Ticker`.
+/// the channel is not closed by `stop`, iterating a stopped ticker blocks.
+///
 /// for example:
 /// ```
-///         use mco::coroutine::sleep;
 ///         use mco::std::time::tick::Ticker;
 ///         use std::sync::Arc;
 ///         use std::time::Duration;
 ///         use mco::co;
 ///
-///         let mut t = Arc::new(Ticker::new(Duration::from_secs(1)));
+///         let t = Arc::new(Ticker::new(Duration::from_millis(100)));
 ///         let tclone = t.clone();
-///         co!(move ||{
-///              for x in tclone.as_ref() {
+///         let h = co!(move ||{
+///              for x in tclone.as_ref().take(3) {
 ///                println!("tick {}", x);
 ///             }
 ///         });
-///         sleep(Duration::from_secs(3));
+///         h.join().unwrap();
 ///         t.stop();
 ///
 /// ```
 pub struct Ticker {
-    pub d: Arc<Mutex<Duration>>,
     pub recv: Receiver<Time>,
+    inner: Arc<Inner>,
 }
 
 impl Ticker {
@@ -38,52 +41,51 @@ impl Ticker {
         Arc::new(Self::new(d))
     }
 
+    /// create a ticker that sends the current time every `d`
+    ///
+    /// # Panics
+    ///
+    /// panics if the duration is zero
     pub fn new(d: Duration) -> Self {
-        let d = Arc::new(Mutex::new(d));
-        let (s, r) = chan!();
-        let ticker = Self { d: d, recv: r };
-        let d = ticker.d.clone();
-        let tick = move || loop {
-            match d.lock() {
-                Ok(d) => {
-                    if d.is_zero() {
-                        break;
-                    }
-                    sleep(d.deref().clone());
-                    let _ = s.send(Time::now());
-                }
-                Err(_) => {
-                    break;
-                }
-            }
-        };
-        co!(tick);
-        ticker
+        assert!(!d.is_zero(), "non-positive interval for Ticker::new");
+        let (s, r) = bounded(1);
+        let inner = Inner::new(Action::Send(s), d, Some(d));
+        Self { recv: r, inner }
     }
 
     /// Stop turns off a ticker. After Stop, no more ticks will be sent.
     /// Stop does not close the channel, to prevent a concurrent goroutine
     /// reading from the channel from seeing an erroneous "tick".
-    pub fn stop(&self) -> Result<()> {
-        match self.d.lock() {
-            Ok(mut d) => {
-                *d = Duration::from_secs(0);
-                Ok(())
-            }
-            Err(e) => Err(err!("lock fail: {}", e)),
-        }
+    ///
+    /// the tick not received yet is dropped too.
+    pub fn stop(&self) {
+        self.inner.stop(&self.recv);
     }
 
     /// Reset stops a ticker and resets its period to the specified duration.
     /// The next tick will arrive after the new period elapses.
-    pub fn reset(&self, d: Duration) -> Result<()> {
-        match self.d.lock() {
-            Ok(mut dur) => {
-                *dur = d;
-                Ok(())
-            }
-            Err(e) => Err(err!("lock fail: {}", e)),
-        }
+    ///
+    /// # Panics
+    ///
+    /// panics if the duration is zero
+    pub fn reset(&self, d: Duration) {
+        assert!(!d.is_zero(), "non-positive interval for Ticker::reset");
+        self.inner.reset(d, Some(d), &self.recv);
+    }
+}
+
+impl Drop for Ticker {
+    // the pending tick holds the ticker, stop it or it would run forever
+    fn drop(&mut self) {
+        self.inner.stop(&self.recv);
+    }
+}
+
+impl fmt::Debug for Ticker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Ticker")
+            .field("active", &self.inner.is_active())
+            .finish()
     }
 }
 
@@ -129,4 +131,22 @@ mod test {
         sleep(Duration::from_secs(3));
         t.stop();
     }
+
+    #[test]
+    fn test_tick_drop_missed() {
+        let t = Ticker::new(Duration::from_millis(20));
+        // a slow receiver gets only one buffered tick
+        sleep(Duration::from_millis(70));
+        assert_eq!(t.recv.remain(), 1);
+        t.recv.recv().unwrap();
+        assert!(t.recv.try_recv().is_err());
+
+        t.stop();
+        sleep(Duration::from_millis(30));
+        assert!(t.recv.try_recv().is_err());
+
+        t.reset(Duration::from_millis(10));
+        t.recv.recv().unwrap();
+        t.stop();
+    }
 }
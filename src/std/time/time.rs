@@ -1,59 +1,132 @@
 use crate::std::errors::Result;
 use crate::std::lazy::sync::Lazy;
-use crate::std::time::format::{LONG_DAY_NAMES, LONG_MONTH_NAMES};
+use crate::std::time::clock;
+use crate::std::time::format::{self, LONG_DAY_NAMES, LONG_MONTH_NAMES};
 use crate::std::time::sys::Timespec;
+use crate::std::time::zoneinfo::Location;
 use serde::de::Error;
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::{Add, Deref, Sub};
+use std::ops::{Add, Sub};
 use std::str::FromStr;
-use time::{format_description, OffsetDateTime};
+use time::{format_description, OffsetDateTime, PrimitiveDateTime};
 
 pub use time::UtcOffset;
 
-/// "Mon, 02 Jan 2006 15:04:05 GMT"
-pub const TIME_FORMAT: &'static str =
-    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT";
-/// "2006-01-02T15:04:05"
-pub const RFC3339_SECOND: &'static str = "[year]-[month]-[day]T[hour]:[minute]:[second]";
-/// "2006-01-02T15:04:05Z07:00"
-pub const RFC3339: &'static str =
-    "[year]-[month]-[day]T[hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]";
-///"2006-01-02T15:04:05.999999999Z07:00"
-pub const RFC3339_NANO: &'static str = "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond][offset_hour sign:mandatory]:[offset_minute]";
-///"Mon, 02 Jan 2006 15:04:05 MST"
-pub const RFC1123: &'static str =
-    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] MST";
+// the seconds from the zero time of go, January 1, year 1, 00:00:00 UTC,
+// to the unix epoch
+const ZERO_TO_UNIX: i128 = 62135596800;
+
+// the reference time used in the layouts is
+//	Mon Jan 2 15:04:05 MST 2006
+// that is the unix time 1136239445, the elements of it are replaced by the
+// values of the time when it's formatted, like go's time package
+
+/// "01/02 03:04:05PM '06 -0700", the reference time in a layout
+pub const LAYOUT: &str = "01/02 03:04:05PM '06 -0700";
+pub const ANSIC: &str = "Mon Jan _2 15:04:05 2006";
+pub const UNIX_DATE: &str = "Mon Jan _2 15:04:05 MST 2006";
+pub const RUBY_DATE: &str = "Mon Jan 02 15:04:05 -0700 2006";
+pub const RFC822: &str = "02 Jan 06 15:04 MST";
+/// RFC822 with numeric zone
+pub const RFC822Z: &str = "02 Jan 06 15:04 -0700";
+pub const RFC850: &str = "Monday, 02-Jan-06 15:04:05 MST";
+pub const RFC1123: &str = "Mon, 02 Jan 2006 15:04:05 MST";
+/// RFC1123 with numeric zone
+pub const RFC1123Z: &str = "Mon, 02 Jan 2006 15:04:05 -0700";
+pub const RFC3339: &str = "2006-01-02T15:04:05Z07:00";
+pub const RFC3339_NANO: &str = "2006-01-02T15:04:05.999999999Z07:00";
+/// RFC3339 without the zone
+pub const RFC3339_SECOND: &str = "2006-01-02T15:04:05";
+/// the http date, the time should be in utc
+pub const TIME_FORMAT: &str = "Mon, 02 Jan 2006 15:04:05 GMT";
+pub const KITCHEN: &str = "3:04PM";
+pub const STAMP: &str = "Jan _2 15:04:05";
+pub const STAMP_MILLI: &str = "Jan _2 15:04:05.000";
+pub const STAMP_MICRO: &str = "Jan _2 15:04:05.000000";
+pub const STAMP_NANO: &str = "Jan _2 15:04:05.000000000";
+pub const DATE_TIME: &str = "2006-01-02 15:04:05";
+pub const DATE_ONLY: &str = "2006-01-02";
+pub const TIME_ONLY: &str = "15:04:05";
 
 /// Obtain the offset of Utc time and Local time in seconds, using Lazy only once to improve performance
+///
+/// it's the offset at the startup, `Location::local()` follows the daylight
+/// saving time changes.
 pub static GLOBAL_OFFSET: Lazy<UtcOffset> =
     Lazy::new(|| UtcOffset::from_whole_seconds(Timespec::now().local().tm_utcoff).unwrap());
 
 /// a time wrapper just like golang
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone)]
+///
+/// the times are compared by the instants, the locations are ignored.
+#[derive(Clone)]
 pub struct Time {
     pub inner: OffsetDateTime,
+    // the location to find the offset and the zone name, the offset of
+    // `inner` is a fixed zone if it's none
+    loc: Option<Location>,
 }
 
 impl Time {
     /// convert self to utc time
     pub fn utc(&self) -> Self {
-        Self {
-            inner: self.inner.to_offset(UtcOffset::UTC),
-        }
+        self.in_location(&Location::utc())
     }
 
-    /// convert self to utc time
+    /// convert self to local time
     pub fn local(&self) -> Self {
+        self.in_location(&Location::local())
+    }
+
+    /// convert self to the time in the location, like go's `Time.In`
+    ///
+    /// for example:
+    /// ```rust
+    ///     use mco::std::time::{Location, Time, RFC3339};
+    ///
+    ///     let tokyo = Location::fixed("JST", 9 * 3600);
+    ///     let t = Time::parse(RFC3339, "2022-02-03T05:04:05Z").unwrap();
+    ///     assert_eq!(t.in_location(&tokyo).format(RFC3339), "2022-02-03T14:04:05+09:00");
+    /// ```
+    pub fn in_location(&self, loc: &Location) -> Self {
+        let (_, offset) = loc.lookup(self.inner.unix_timestamp());
         Self {
-            inner: self.inner.to_offset(GLOBAL_OFFSET.deref().clone()),
+            inner: self.inner.to_offset(to_utc_offset(offset)),
+            loc: Some(loc.clone()),
+        }
+    }
+
+    /// the location of the time, a fixed zone without name if it has only
+    /// an offset
+    pub fn location(&self) -> Location {
+        match &self.loc {
+            Some(loc) => loc.clone(),
+            None => Location::fixed("", self.inner.offset().whole_seconds()),
+        }
+    }
+
+    /// return the zone name and its offset in seconds east of UTC in use at
+    /// the time, like go's `Time.Zone`
+    pub fn zone(&self) -> (String, i32) {
+        let offset = self.inner.offset().whole_seconds();
+        match &self.loc {
+            Some(loc) => (loc.lookup(self.unix()).0.to_string(), offset),
+            None => (String::new(), offset),
+        }
+    }
+
+    // the time with the same location at another instant
+    fn with_inner(&self, inner: OffsetDateTime) -> Self {
+        match &self.loc {
+            // the offset may change, such as at the daylight saving time
+            Some(loc) => Time::from(inner).in_location(loc),
+            None => Time::from(inner),
         }
     }
 
     /// return new offset
     pub fn to_offset(self, offset: UtcOffset) -> Time {
-        Self {
-            inner: self.inner.to_offset(offset),
-        }
+        Time::from(self.inner.to_offset(offset))
     }
 
     pub fn unix_timestamp(&self) -> i64 {
@@ -74,26 +147,140 @@ impl Time {
         self.inner.unix_timestamp_nanos() as i64
     }
 
-    pub fn add(mut self, d: std::time::Duration) -> Self {
-        self.inner = self.inner.add(d);
-        self
+    pub fn add(self, d: std::time::Duration) -> Self {
+        self.with_inner(self.inner.add(d))
     }
 
-    pub fn sub(mut self, d: std::time::Duration) -> Self {
-        self.inner = self.inner.sub(d);
-        self
+    pub fn sub(self, d: std::time::Duration) -> Self {
+        self.with_inner(self.inner.sub(d))
     }
 
     // add_sec adds d seconds to the time.
-    pub fn add_sec(mut self, d: i64) -> Self {
-        self.inner = self.inner.add(time::Duration::seconds(d));
-        self
+    pub fn add_sec(self, d: i64) -> Self {
+        self.with_inner(self.inner.add(time::Duration::seconds(d)))
+    }
+
+    /// return the duration t-u, it's negative if u is after t, like go's
+    /// `Time.Sub`
+    ///
+    /// for example:
+    /// ```rust
+    ///     use mco::std::time::{Time, DATE_TIME};
+    ///
+    ///     let t = Time::parse(DATE_TIME, "2022-02-03 12:00:00").unwrap();
+    ///     let u = Time::parse(DATE_TIME, "2022-02-03 10:30:00").unwrap();
+    ///     assert_eq!(t.sub_time(&u), time::Duration::minutes(90));
+    ///     assert_eq!(u.sub_time(&t), time::Duration::minutes(-90));
+    /// ```
+    pub fn sub_time(&self, u: &Time) -> time::Duration {
+        self.inner - u.inner
+    }
+
+    /// return the time elapsed since t, like go's `time.Since(t)`
+    pub fn since(&self) -> time::Duration {
+        Time::now().sub_time(self)
+    }
+
+    /// return the duration until t, like go's `time.Until(t)`
+    pub fn until(&self) -> time::Duration {
+        self.sub_time(&Time::now())
+    }
+
+    /// return the time corresponding to adding the given number of years,
+    /// months and days to t, like go's `Time.AddDate`
+    ///
+    /// the date is normalized in the same way as go, so adding one month to
+    /// October 31 yields December 1. the clock of the day is kept in the
+    /// location of t, even across a daylight saving time transition.
+    ///
+    /// unlike go the years are limited to the range of the `time` crate,
+    /// -9999 to 9999, `None` is returned if the result is out of it.
+    ///
+    /// for example:
+    /// ```rust
+    ///     use mco::std::time::{Time, DATE_ONLY};
+    ///
+    ///     let t = Time::parse(DATE_ONLY, "2022-10-31").unwrap();
+    ///     assert_eq!(t.add_date(0, 1, 0).unwrap().format(DATE_ONLY), "2022-12-01");
+    ///     assert_eq!(t.add_date(-1, 0, -31).unwrap().format(DATE_ONLY), "2021-09-30");
+    ///     assert!(t.add_date(10000, 0, 0).is_none());
+    /// ```
+    pub fn add_date(&self, years: i32, months: i32, days: i32) -> Option<Self> {
+        let (year, month, day) = self.inner.to_calendar_date();
+        let month = month as i64 - 1 + months as i64;
+        let year = i32::try_from(year as i64 + years as i64 + month.div_euclid(12)).ok()?;
+        let month = time::Month::try_from(month.rem_euclid(12) as u8 + 1).unwrap();
+        let date = time::Date::from_calendar_date(year, month, 1)
+            .ok()?
+            .checked_add(time::Duration::days(day as i64 - 1 + days as i64))?;
+        let datetime = PrimitiveDateTime::new(date, self.inner.time());
+        match &self.loc {
+            Some(loc) => Self::from_wall(datetime, loc),
+            None => Some(Time::from(datetime.assume_offset(self.inner.offset()))),
+        }
+    }
+
+    // the remainder of t divided by d, t is the duration since the zero time
+    // of go, January 1, year 1, 00:00:00 UTC
+    fn rem(&self, d: std::time::Duration) -> i128 {
+        let t = self.inner.unix_timestamp_nanos() + ZERO_TO_UNIX * 1_000_000_000;
+        t.rem_euclid(d.as_nanos() as i128)
+    }
+
+    /// return the result of rounding t down to a multiple of d since the
+    /// zero time, like go's `Time.Truncate`. t is returned unchanged if d is
+    /// zero.
+    ///
+    /// it operates on the time as an absolute duration since the zero time,
+    /// not on the presentation form, so truncating to an hour may return a
+    /// time with a non-zero minute in some locations.
+    ///
+    /// for example:
+    /// ```rust
+    ///     use mco::std::time::{Time, RFC3339_NANO};
+    ///     use std::time::Duration;
+    ///
+    ///     let t = Time::parse(RFC3339_NANO, "2022-02-03T12:15:30.918273645Z").unwrap();
+    ///     let t = t.truncate(Duration::from_secs(60));
+    ///     assert_eq!(t.format(RFC3339_NANO), "2022-02-03T12:15:00Z");
+    /// ```
+    pub fn truncate(&self, d: std::time::Duration) -> Self {
+        if d.is_zero() {
+            return self.clone();
+        }
+        let r = self.rem(d);
+        self.with_inner(self.inner - nanos(r))
+    }
+
+    /// return the result of rounding t to the nearest multiple of d since
+    /// the zero time, like go's `Time.Round`. the halfway values round up.
+    /// t is returned unchanged if d is zero.
+    ///
+    /// for example:
+    /// ```rust
+    ///     use mco::std::time::{Time, RFC3339_NANO};
+    ///     use std::time::Duration;
+    ///
+    ///     let t = Time::parse(RFC3339_NANO, "2022-02-03T12:15:30.918273645Z").unwrap();
+    ///     let t = t.round(Duration::from_millis(10));
+    ///     assert_eq!(t.format(RFC3339_NANO), "2022-02-03T12:15:30.92Z");
+    /// ```
+    pub fn round(&self, d: std::time::Duration) -> Self {
+        if d.is_zero() {
+            return self.clone();
+        }
+        let r = self.rem(d);
+        let d = d.as_nanos() as i128;
+        if r + r < d {
+            self.with_inner(self.inner - nanos(r))
+        } else {
+            self.with_inner(self.inner + nanos(d - r))
+        }
     }
 
     /// set_loc sets the location associated with the time.
-    pub fn set_loc(mut self, loc: time::UtcOffset) -> Self {
-        self.inner = self.inner.to_offset(loc);
-        self
+    pub fn set_loc(self, loc: time::UtcOffset) -> Self {
+        self.to_offset(loc)
     }
 
     /// after reports whether the time instant t is after u.
@@ -180,93 +367,193 @@ impl Time {
         self.inner.nanosecond() as i32
     }
 
-    /// format a time to string
-    /// [year]
-    /// [month]
-    /// [ordinal]
-    /// [weekday]
-    /// [week_number]
-    /// [day]
-    /// [hour]
-    /// [minute]
-    /// [period]
-    /// [second]
-    /// [subsecond]
-    /// [offset_hour sign:mandatory]
-    /// [offset_minute]
-    /// [offset_second]
+    /// format the time by a go reference layout, such as `RFC3339` or
+    /// "Mon Jan _2 15:04:05 MST 2006"
+    ///
+    /// the elements of the reference time are
+    /// - year: "2006" "06"
+    /// - month: "Jan" "January" "01" "1"
+    /// - day of the week: "Mon" "Monday"
+    /// - day of the month: "2" "_2" "02"
+    /// - day of the year: "__2" "002"
+    /// - hour: "15" "3" "03" (PM or AM)
+    /// - minute: "4" "04"
+    /// - second: "5" "05"
+    /// - AM/PM mark: "PM" "pm"
+    /// - fractional second: ".000" ".999", or with a comma, the trailing
+    ///   zeros of ".999" are removed
+    /// - zone: "MST" "-0700" "-07" "-07:00" "-070000" "-07:00:00", the
+    ///   "Z" variants print Z for UTC instead of the offset
+    ///
+    /// the `time` crate format descriptions like "[year]-[month]-[day]" are
+    /// still accepted for compatibility.
     ///
     /// for example:
     /// ```rust
-    ///     use mco::std::time::Time;
+    ///     use mco::std::time::{Time, KITCHEN, RFC3339};
     ///
-    ///     let mut t = Time::now();
-    ///     let formatted = t.format(
-    ///     "[year]-[month] [ordinal] [weekday] [week_number]-[day] [hour]:[minute] [period]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
-    ///     );
-    ///     println!("formatted: {}", formatted);
+    ///     let t = Time::parse(RFC3339, "2022-02-03T13:04:05+08:00").unwrap();
+    ///     assert_eq!(t.format("Mon Jan _2 2006 .000"), "Thu Feb  3 2022 .000");
+    ///     assert_eq!(t.format(KITCHEN), "1:04PM");
+    ///     assert_eq!(t.utc().format(RFC3339), "2022-02-03T05:04:05Z");
     /// ```
     pub fn format(&self, layout: &str) -> String {
-        let f = {
-            match format_description::parse(layout) {
-                Ok(v) => v,
-                Err(_) => {
-                    vec![]
-                }
-            }
-        };
-        if f.is_empty() {
-            return String::new();
+        if let Some(f) = description(layout) {
+            return self.inner.format(&f).unwrap_or_default();
+        }
+        match &self.loc {
+            Some(loc) => format::format(&self.inner, Some(loc.lookup(self.unix()).0), layout),
+            None => format::format(&self.inner, None, layout),
         }
-        self.inner.format(&f).unwrap_or_default()
-    }
-
-    /// parse a string value to Time。
-    /// [year]
-    /// [month]
-    /// [ordinal]
-    /// [weekday]
-    /// [week_number]
-    /// [day]
-    /// [hour]
-    /// [minute]
-    /// [period]
-    /// [second]
-    /// [subsecond]
-    /// [offset_hour sign:mandatory]
-    /// [offset_minute]
-    /// [offset_second]
+    }
+
+    /// parse a string value to Time by a go reference layout, see `format`
+    ///
+    /// the time is in UTC if the value has no zone. a zone abbreviation is
+    /// looked up in the local location, an unknown one is given a zero
+    /// offset unless it's "GMT+hh", like go's `time.Parse`.
     ///
     /// for example:
     /// ```rust
-    ///     use mco::std::time::{RFC3339_NANO,Time};
-    ///
-    ///     let parsed = Time::parse("[year]-[month] [ordinal] [weekday] [week_number]-[day] [hour]:[minute] [period]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]","2022-02 033 Wednesday 05-02 01:49 AM:22.1210536 +08:00:00").unwrap();
+    ///     use mco::std::time::{Time, RFC3339_NANO, DATE_TIME};
     ///
     ///     let parsed = Time::parse(RFC3339_NANO, "2022-02-03T01:51:00.9335458+08:00").unwrap();
+    ///     assert_eq!(parsed.nanosecond(), 933545800);
+    ///
+    ///     let parsed = Time::parse(DATE_TIME, "2022-02-03 01:51:00").unwrap();
+    ///     assert_eq!(parsed.unix(), 1643853060);
+    ///
+    ///     let err = Time::parse(DATE_TIME, "2022-02-30 01:51:00").unwrap_err();
+    ///     assert_eq!(err.to_string(), r#"parsing time "2022-02-30 01:51:00": day out of range"#);
     /// ```
     pub fn parse(layout: &str, value: &str) -> Result<Self> {
-        match format_description::parse(layout) {
-            Ok(v) => Ok(Self {
-                inner: time::OffsetDateTime::parse(value, &v)?,
-            }),
-            Err(e) => {
-                return Err(e.into());
+        if let Some(f) = description(layout) {
+            return Ok(Time::from(time::OffsetDateTime::parse(value, &f)?));
+        }
+        Self::parse_with(layout, value, &Location::utc(), &Location::local())
+    }
+
+    /// same as `parse`, except that the time without zone is in the location,
+    /// and a zone abbreviation is looked up in it, like go's
+    /// `time.ParseInLocation`
+    ///
+    /// for example:
+    /// ```rust
+    ///     use mco::std::time::{Location, Time, DATE_TIME, RFC3339};
+    ///
+    ///     let loc = Location::fixed("CST", 8 * 3600);
+    ///     let t = Time::parse_in_location(DATE_TIME, "2022-02-03 01:51:00", &loc).unwrap();
+    ///     assert_eq!(t.format(RFC3339), "2022-02-03T01:51:00+08:00");
+    /// ```
+    pub fn parse_in_location(layout: &str, value: &str, loc: &Location) -> Result<Self> {
+        Self::parse_with(layout, value, loc, loc)
+    }
+
+    fn parse_with(layout: &str, value: &str, default: &Location, local: &Location) -> Result<Self> {
+        let parsed = format::parse(layout, value)?;
+        let utc = parsed.datetime.assume_utc();
+        if let Some(offset) = parsed.offset {
+            let t = Time::from(parsed.datetime.assume_offset(offset));
+            let zone = parsed.zone.as_deref();
+            if zone == Some("UTC") {
+                return Ok(t.in_location(&Location::utc()));
+            }
+            // keep the location if the zone is the one in use in it
+            let (name, local_offset) = local.lookup(t.unix());
+            if local_offset == offset.whole_seconds() && zone.map_or(true, |z| z == name) {
+                return Ok(t.in_location(local));
             }
+            return Ok(match zone {
+                Some(zone) => t.in_location(&Location::fixed(zone, offset.whole_seconds())),
+                None => t,
+            });
         }
+        if let Some(zone) = parsed.zone {
+            if let Some(offset) = local.lookup_name(&zone, utc.unix_timestamp()) {
+                let t = utc - time::Duration::seconds(offset as i64);
+                return Ok(Time::from(t).in_location(local));
+            }
+            // otherwise create a fake zone with the unknown offset
+            let offset = zone
+                .strip_prefix("GMT")
+                .and_then(|h| h.parse::<i32>().ok())
+                .map_or(0, |h| h * 3600);
+            let t = parsed.datetime.assume_offset(to_utc_offset(offset));
+            return Ok(Time::from(t).in_location(&Location::fixed(&zone, offset)));
+        }
+        Self::from_wall(parsed.datetime, default)
+            .ok_or_else(|| err!("parsing time {:?}: out of range", value))
+    }
+
+    // the time of the wall clock in the location
+    // None if the time is out of range after the offset is applied
+    fn from_wall(datetime: PrimitiveDateTime, loc: &Location) -> Option<Self> {
+        // the offset at the local time, it's looked up again by the utc time
+        // in case it's near a transition
+        let utc = datetime.assume_utc();
+        let local_sec = utc.unix_timestamp();
+        let (_, offset) = loc.lookup(local_sec);
+        let (_, offset) = loc.lookup(local_sec - offset as i64);
+        let t = utc.checked_sub(time::Duration::seconds(offset as i64))?;
+        let (_, offset) = loc.lookup(t.unix_timestamp());
+        Some(Self {
+            inner: t.checked_to_offset(to_utc_offset(offset))?,
+            loc: Some(loc.clone()),
+        })
     }
 
     /// now returns the current local time.
     pub fn now() -> Time {
-        let mut now = time::OffsetDateTime::now_utc();
-        now = now.to_offset(GLOBAL_OFFSET.clone());
-        return Time { inner: now };
+        Time::now_utc().local()
     }
 
-    /// current utc time
+    /// current utc time of the installed clock, see `clock::set_clock`
     pub fn now_utc() -> Time {
-        let now = time::OffsetDateTime::now_utc();
-        return Time { inner: now };
+        let now = clock::now_utc();
+        return Time::from(now);
+    }
+}
+
+fn nanos(n: i128) -> time::Duration {
+    time::Duration::new((n / 1_000_000_000) as i64, (n % 1_000_000_000) as i32)
+}
+
+fn to_utc_offset(offset: i32) -> UtcOffset {
+    // the offsets of the zones are always in the range
+    UtcOffset::from_whole_seconds(offset).unwrap_or(UtcOffset::UTC)
+}
+
+// parse the layout as a `time` crate format description if it looks like one
+fn description(layout: &str) -> Option<Vec<format_description::FormatItem<'_>>> {
+    if !layout.contains('[') {
+        return None;
+    }
+    format_description::parse(layout).ok()
+}
+
+impl From<OffsetDateTime> for Time {
+    fn from(inner: OffsetDateTime) -> Self {
+        Time { inner, loc: None }
+    }
+}
+
+impl PartialEq for Time {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for Time {}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Time {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.inner.cmp(&other.inner)
     }
 }
 
@@ -531,7 +818,8 @@ impl Weekday {
 #[cfg(test)]
 mod test {
     use crate::coroutine::sleep;
-    use crate::std::time::time::{Month, Time, RFC3339, RFC3339_NANO, TIME_FORMAT};
+    use crate::std::time::time::*;
+    use crate::std::time::zoneinfo::{load_location, Location};
     use std::time::Duration;
 
     #[test]
@@ -582,4 +870,230 @@ mod test {
         println!("default(): {}", Time::default());
         assert_eq!(true, Time::default().is_zero());
     }
+
+    #[test]
+    fn test_layout_format() {
+        let t = Time::parse(RFC3339_NANO, "2009-02-04T21:00:57.0123456-08:00").unwrap();
+        assert_eq!(t.format(ANSIC), "Wed Feb  4 21:00:57 2009");
+        assert_eq!(t.format(UNIX_DATE), "Wed Feb  4 21:00:57 -0800 2009");
+        assert_eq!(t.format(RFC822Z), "04 Feb 09 21:00 -0800");
+        assert_eq!(t.format(RFC850), "Wednesday, 04-Feb-09 21:00:57 -0800");
+        assert_eq!(t.format(KITCHEN), "9:00PM");
+        assert_eq!(t.format(LAYOUT), "02/04 09:00:57PM '09 -0800");
+        assert_eq!(t.format(STAMP_MILLI), "Feb  4 21:00:57.012");
+        assert_eq!(t.format(STAMP_NANO), "Feb  4 21:00:57.012345600");
+        assert_eq!(t.format(RFC3339_NANO), "2009-02-04T21:00:57.0123456-08:00");
+        assert_eq!(t.format("__2 002 ,999 .00 pm"), " 35 035 ,012 .01 pm");
+        assert_eq!(t.format("05.999999999"), "57.0123456");
+        assert_eq!(t.format("Z07:00:00 -07 -070000"), "-08:00:00 -08 -080000");
+        // not an element if it's followed by a lower case letter
+        assert_eq!(t.format("Janet Monty"), "Janet Monty");
+
+        let t = t.utc();
+        assert_eq!(t.format(RFC3339), "2009-02-05T05:00:57Z");
+        assert_eq!(t.format(RFC1123), "Thu, 05 Feb 2009 05:00:57 UTC");
+        assert_eq!(
+            t.format("_2006 January 1 3 03 4 5 Z0700 -0700"),
+            "_2009 February 2 5 05 0 57 Z +0000"
+        );
+    }
+
+    #[test]
+    fn test_layout_parse() {
+        let t = Time::parse(RFC1123, "Wed, 04 Feb 2009 21:00:57 UTC").unwrap();
+        assert_eq!(t.unix(), 1233781257);
+        // a zone abbreviation is not known, like go
+        let utc = Location::utc();
+        let t = Time::parse_in_location(RFC1123, "Wed, 04 Feb 2009 21:00:57 PST", &utc).unwrap();
+        assert_eq!(t.unix(), 1233781257);
+        assert_eq!(t.format(RFC1123), "Wed, 04 Feb 2009 21:00:57 PST");
+        let t = Time::parse("15:04 MST", "10:00 GMT+3").unwrap();
+        assert_eq!(t.inner.offset().whole_hours(), 3);
+
+        let t = Time::parse(ANSIC, "Wed Feb  4 21:00:57 2009").unwrap();
+        assert_eq!(t.format(RFC3339), "2009-02-04T21:00:57Z");
+        let t = Time::parse(KITCHEN, "12:30AM").unwrap();
+        assert_eq!((t.year(), t.hour(), t.minute()), (0, 0, 30));
+        let t = Time::parse("2006 __2", "2009  35").unwrap();
+        assert_eq!((t.month(), t.day()), (Month::February, 4));
+        let t = Time::parse("Jan _2 06", "feb  4 09").unwrap();
+        assert_eq!(t.format(DATE_ONLY), "2009-02-04");
+        // a fractional second not in the layout
+        let t = Time::parse(RFC3339, "2009-02-04T21:00:57.5+01:00").unwrap();
+        assert_eq!(t.nanosecond(), 500_000_000);
+        assert_eq!(t.inner.offset().whole_hours(), 1);
+
+        for layout in &[
+            ANSIC,
+            RUBY_DATE,
+            RFC822Z,
+            RFC1123Z,
+            RFC3339_NANO,
+            STAMP_MICRO,
+            DATE_TIME,
+        ] {
+            let t = Time::parse(RFC3339, "2021-12-31T23:59:58+00:00").unwrap();
+            let v = t.format(layout);
+            assert_eq!(Time::parse(layout, &v).unwrap().format(layout), v);
+        }
+    }
+
+    #[test]
+    fn test_layout_parse_error() {
+        let err = |layout, value| Time::parse(layout, value).unwrap_err().to_string();
+        assert_eq!(
+            err(RFC3339, "2009-02-04"),
+            r#"parsing time "2009-02-04" as "2006-01-02T15:04:05Z07:00": cannot parse "" as "T""#
+        );
+        assert_eq!(
+            err(DATE_ONLY, "2009-13-01"),
+            r#"parsing time "2009-13-01": month out of range"#
+        );
+        assert_eq!(
+            err(DATE_ONLY, "2009-02-04x"),
+            r#"parsing time "2009-02-04x": extra text: "x""#
+        );
+        assert_eq!(
+            err(STAMP_MILLI, "Feb  4 21:00:57.01"),
+            r#"parsing time "Feb  4 21:00:57.01" as "Jan _2 15:04:05.000": cannot parse ".01" as ".000""#
+        );
+        assert_eq!(
+            err("2006 002 Jan", "2009 035 Mar"),
+            r#"parsing time "2009 035 Mar": day-of-year does not match month"#
+        );
+    }
+
+    #[test]
+    fn test_location() {
+        let berlin = load_location("Europe/Berlin").unwrap();
+        let t = Time::parse(RFC3339, "2022-03-27T00:30:00Z").unwrap();
+        let t = t.in_location(&berlin);
+        assert_eq!(t.format("15:04 MST -07:00"), "01:30 CET +01:00");
+        assert_eq!(t.zone(), ("CET".to_string(), 3600));
+        assert_eq!(t.location(), berlin);
+        // the offset follows the daylight saving time
+        let t = t.add(Duration::from_secs(3600));
+        assert_eq!(t.format("15:04 MST -07:00"), "03:30 CEST +02:00");
+        assert_eq!(t.utc().format(RFC3339), "2022-03-27T01:30:00Z");
+
+        // the time without zone is in the location
+        let t = Time::parse_in_location(DATE_TIME, "2022-07-01 12:00:00", &berlin).unwrap();
+        assert_eq!(t.utc().format(DATE_TIME), "2022-07-01 10:00:00");
+        let t = Time::parse_in_location(DATE_TIME, "2022-01-01 12:00:00", &berlin).unwrap();
+        assert_eq!(t.utc().format(DATE_TIME), "2022-01-01 11:00:00");
+        // the zone abbreviation is looked up in the location
+        let t =
+            Time::parse_in_location(RFC1123, "Fri, 01 Jul 2022 12:00:00 CEST", &berlin).unwrap();
+        assert_eq!(t.utc().format(DATE_TIME), "2022-07-01 10:00:00");
+        assert_eq!(t.location(), berlin);
+        let t =
+            Time::parse_in_location(RFC1123Z, "Fri, 01 Jul 2022 12:00:00 +0200", &berlin).unwrap();
+        assert_eq!(t.format(RFC1123), "Fri, 01 Jul 2022 12:00:00 CEST");
+
+        let t = Time::parse(RFC3339, "2022-07-01T12:00:00Z").unwrap();
+        assert_eq!(t.format(RFC1123), "Fri, 01 Jul 2022 12:00:00 UTC");
+        assert_eq!(t, t.in_location(&berlin));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let t = Time::parse(RFC3339_NANO, "2022-02-03T12:15:30.5Z").unwrap();
+        let u = Time::parse(RFC3339, "2022-02-03T10:00:00Z").unwrap();
+        assert_eq!(t.sub_time(&u), time::Duration::new(8130, 500_000_000));
+        assert_eq!(u.sub_time(&t), time::Duration::new(-8130, -500_000_000));
+        assert!(u.since().is_positive());
+        assert!(u.until().is_negative());
+
+        // the date is normalized like go
+        let t = Time::parse(DATE_TIME, "2011-01-31 10:00:00").unwrap();
+        assert_eq!(
+            t.add_date(0, 1, 0).unwrap().format(DATE_TIME),
+            "2011-03-03 10:00:00"
+        );
+        assert_eq!(
+            t.add_date(0, -13, 0).unwrap().format(DATE_TIME),
+            "2009-12-31 10:00:00"
+        );
+        assert_eq!(
+            t.add_date(1, 0, 366).unwrap().format(DATE_TIME),
+            "2013-01-31 10:00:00"
+        );
+        let t = Time::parse(DATE_ONLY, "2020-02-29").unwrap();
+        assert_eq!(t.add_date(1, 0, 0).unwrap().format(DATE_ONLY), "2021-03-01");
+        // the offset without location is kept
+        let t = Time::parse(RFC3339, "2022-01-01T10:00:00+08:00").unwrap();
+        assert_eq!(
+            t.add_date(0, 6, 0).unwrap().format(RFC3339),
+            "2022-07-01T10:00:00+08:00"
+        );
+        // the clock is kept across the daylight saving time
+        let berlin = load_location("Europe/Berlin").unwrap();
+        let t = Time::parse_in_location(DATE_TIME, "2022-03-26 12:00:00", &berlin).unwrap();
+        let t = t.add_date(0, 0, 1).unwrap();
+        assert_eq!(
+            t.format("2006-01-02 15:04:05 MST"),
+            "2022-03-27 12:00:00 CEST"
+        );
+        // out of the range of the years
+        assert!(t.add_date(10000, 0, 0).is_none());
+        assert!(t.add_date(0, -12 * 20000, 0).is_none());
+        assert!(t.add_date(i32::MAX, i32::MAX, i32::MAX).is_none());
+
+        let t = Time::parse(RFC3339_NANO, "2022-02-03T12:15:30.918273645Z").unwrap();
+        let cases: &[(Duration, &str, &str)] = &[
+            (
+                Duration::from_nanos(0),
+                "12:15:30.918273645",
+                "12:15:30.918273645",
+            ),
+            (
+                Duration::from_nanos(1),
+                "12:15:30.918273645",
+                "12:15:30.918273645",
+            ),
+            (
+                Duration::from_micros(1),
+                "12:15:30.918273",
+                "12:15:30.918274",
+            ),
+            (Duration::from_millis(1), "12:15:30.918", "12:15:30.918"),
+            (Duration::from_secs(1), "12:15:30", "12:15:31"),
+            (Duration::from_secs(2), "12:15:30", "12:15:30"),
+            (Duration::from_secs(60), "12:15:00", "12:16:00"),
+            (Duration::from_secs(600), "12:10:00", "12:20:00"),
+            (Duration::from_secs(3600), "12:00:00", "12:00:00"),
+            (Duration::from_secs(86400), "00:00:00", "00:00:00"),
+        ];
+        for (d, truncated, rounded) in cases {
+            assert_eq!(
+                t.truncate(*d).format("15:04:05.999999999"),
+                *truncated,
+                "{:?}",
+                d
+            );
+            assert_eq!(
+                t.round(*d).format("15:04:05.999999999"),
+                *rounded,
+                "{:?}",
+                d
+            );
+        }
+        // the halfway value rounds up
+        let t = Time::parse(RFC3339_NANO, "2022-02-03T12:15:30.5Z").unwrap();
+        assert_eq!(
+            t.round(Duration::from_secs(1)).format(TIME_ONLY),
+            "12:15:31"
+        );
+        // the week is counted from the zero time, which is a monday
+        let t = Time::parse(DATE_TIME, "2022-02-03 12:00:00").unwrap();
+        let week = t.truncate(Duration::from_secs(7 * 86400));
+        assert_eq!(
+            week.format("Mon 2006-01-02 15:04:05"),
+            "Mon 2022-01-31 00:00:00"
+        );
+        // the location is kept
+        let t = Time::parse_in_location(DATE_TIME, "2022-07-01 12:34:56", &berlin).unwrap();
+        let t = t.truncate(Duration::from_secs(3600));
+        assert_eq!(t.format("15:04:05 MST"), "12:00:00 CEST");
+    }
 }
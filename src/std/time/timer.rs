@@ -0,0 +1,320 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::scheduler::{get_scheduler, SchedRef, TimerData};
+use crate::std::sync::channel::{bounded, Receiver, Sender};
+use crate::std::time::time::Time;
+use crate::timeout_list::{self, dur_to_ns, ns_to_dur, TimerHandle};
+
+// what to do when the timer expires
+pub(crate) enum Action {
+    // send the current time without blocking
+    Send(Sender<Time>),
+    // run the function in a new coroutine, the sender is kept so that
+    // `recv` just blocks like a nil channel in go
+    Func {
+        f: Arc<dyn Fn() + Send + Sync>,
+        _tx: Sender<Time>,
+    },
+}
+
+struct Pending {
+    // the handle in the timer thread, it's only dropped on the timer thread,
+    // either by `del_timer` or after it's fired
+    handle: Option<TimerHandle<TimerData>>,
+    // the deadline of the pending run in ns of the clock
+    when: u64,
+    // the interval of a ticker, the next run is scheduled when it's fired
+    period: Option<Duration>,
+}
+
+pub(crate) struct Inner {
+    sched: SchedRef,
+    action: Action,
+    // the id of the pending run, 0 if the timer is expired or stopped
+    active: AtomicUsize,
+    next_id: AtomicUsize,
+    pending: Mutex<Pending>,
+}
+
+impl Inner {
+    pub(crate) fn new(action: Action, d: Duration, period: Option<Duration>) -> Arc<Self> {
+        let inner = Arc::new(Inner {
+            sched: SchedRef::new(get_scheduler()),
+            action,
+            active: AtomicUsize::new(0),
+            next_id: AtomicUsize::new(0),
+            pending: Mutex::new(Pending {
+                handle: None,
+                when: 0,
+                period,
+            }),
+        });
+        inner.start(&mut inner.pending.lock(), d);
+        inner
+    }
+
+    fn start(self: &Arc<Self>, p: &mut Pending, d: Duration) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.active.store(id, Ordering::Release);
+        self.schedule(p, id, timeout_list::now() + dur_to_ns(d));
+    }
+
+    fn schedule(self: &Arc<Self>, p: &mut Pending, id: usize, when: u64) {
+        p.when = when;
+        let inner = self.clone();
+        let fire = Box::new(move || inner.fire(id));
+        let d = ns_to_dur(when.saturating_sub(timeout_list::now()));
+        p.handle = Some(self.sched.add_timer_callback(d, fire));
+    }
+
+    // return true if the pending run is stopped
+    fn stop_locked(&self, p: &mut Pending, recv: &Receiver<Time>) -> bool {
+        let active = self.active.swap(0, Ordering::AcqRel) != 0;
+        if let Some(h) = p.handle.take() {
+            self.sched.del_timer(h);
+        }
+        // the value sent before is stale, like go 1.23
+        while recv.try_recv().is_ok() {}
+        active
+    }
+
+    /// stop the pending run and drop the time not received yet
+    ///
+    /// return true if the run is not expired yet
+    pub(crate) fn stop(&self, recv: &Receiver<Time>) -> bool {
+        self.stop_locked(&mut self.pending.lock(), recv)
+    }
+
+    /// stop the pending run and start a new one after the duration, the
+    /// period of a ticker is replaced if it's given
+    pub(crate) fn reset(
+        self: &Arc<Self>,
+        d: Duration,
+        period: Option<Duration>,
+        recv: &Receiver<Time>,
+    ) -> bool {
+        let mut p = self.pending.lock();
+        let active = self.stop_locked(&mut p, recv);
+        if period.is_some() {
+            p.period = period;
+        }
+        self.start(&mut p, d);
+        active
+    }
+
+    /// return true if there is a pending run
+    pub(crate) fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed) != 0
+    }
+
+    // called on the timer thread
+    fn fire(self: &Arc<Self>, id: usize) {
+        let mut p = self.pending.lock();
+        // stopped, or reset to another run
+        if self.active.load(Ordering::Acquire) != id {
+            return;
+        }
+        p.handle.take();
+        match &self.action {
+            Action::Send(tx) => {
+                // the channel holds one value, a slow receiver misses the
+                // following ones instead of queuing them up
+                let _ = tx.try_send(Time::now());
+            }
+            Action::Func { f, .. } => {
+                let f = f.clone();
+                crate::coroutine::spawn(move || f());
+            }
+        }
+        match p.period {
+            // schedule against the deadline instead of now so the ticks
+            // don't drift, the deadlines already passed are skipped
+            Some(period) => {
+                let now = timeout_list::now();
+                let period = dur_to_ns(period);
+                let mut when = p.when + period;
+                if when <= now {
+                    let missed = (now - when) / period + 1;
+                    when += period * missed;
+                }
+                self.schedule(&mut p, id, when);
+            }
+            None => self.active.store(0, Ordering::Release),
+        }
+    }
+}
+
+/// A Timer sends the current time to its channel once after the duration,
+/// like go's `time.Timer`
+///
+/// the timers are run by the timer thread of the scheduler, there is no
+/// coroutine waiting for each of them. a dropped timer still fires.
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::time::Timer;
+/// use std::time::Duration;
+///
+/// let timer = Timer::new(Duration::from_millis(10));
+/// let t = timer.recv.recv().unwrap();
+/// println!("fired at {}", t);
+///
+/// let timer = Timer::new(Duration::from_secs(10));
+/// assert!(timer.stop());
+/// assert!(timer.recv.try_recv().is_err());
+/// ```
+pub struct Timer {
+    /// the channel to receive the time when the timer expires, the timer of
+    /// `after_func` never sends to it
+    pub recv: Receiver<Time>,
+    inner: Arc<Inner>,
+}
+
+impl Timer {
+    fn with_action(d: Duration, action: Action, recv: Receiver<Time>) -> Self {
+        let inner = Inner::new(action, d, None);
+        Timer { recv, inner }
+    }
+
+    /// create a timer that sends the current time to `recv` after the duration
+    pub fn new(d: Duration) -> Self {
+        let (tx, rx) = bounded(1);
+        Self::with_action(d, Action::Send(tx), rx)
+    }
+
+    /// stop the timer, return false if it's already expired or stopped
+    ///
+    /// the time not received yet is dropped, so `recv` never gets a stale
+    /// value after `stop` returns
+    pub fn stop(&self) -> bool {
+        self.inner.stop(&self.recv)
+    }
+
+    /// restart the timer to expire after the duration, return false if it's
+    /// already expired or stopped before
+    ///
+    /// the time not received yet is dropped like `stop`
+    pub fn reset(&self, d: Duration) -> bool {
+        self.inner.reset(d, None, &self.recv)
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("active", &self.inner.is_active())
+            .finish()
+    }
+}
+
+/// return a channel that receives the current time after the duration
+///
+/// it's the same as `Timer::new(d).recv`, handy as a `select!` arm
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::time::after;
+/// use std::time::Duration;
+///
+/// let (_tx, rx) = mco::chan!(i32, 1);
+/// let id = mco::select! {
+///     _ = rx.recv() => {},
+///     _ = after(Duration::from_millis(10)).recv() => {},
+/// };
+/// assert_eq!(id, 1);
+/// ```
+pub fn after(d: Duration) -> Receiver<Time> {
+    Timer::new(d).recv
+}
+
+/// run the function in a new coroutine after the duration
+///
+/// the returned timer can stop it, or reset it to run again
+///
+/// # Examples
+///
+/// ```
+/// use mco::std::time::after_func;
+/// use std::time::Duration;
+///
+/// let (tx, rx) = mco::chan!();
+/// after_func(Duration::from_millis(10), move || tx.send(1).unwrap());
+/// assert_eq!(rx.recv(), Ok(1));
+/// ```
+pub fn after_func<F>(d: Duration, f: F) -> Timer
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let (tx, rx) = bounded(1);
+    Timer::with_action(
+        d,
+        Action::Func {
+            f: Arc::new(f),
+            _tx: tx,
+        },
+        rx,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+
+    #[test]
+    fn test_timer() {
+        let now = Instant::now();
+        let timer = Timer::new(Duration::from_millis(20));
+        timer.recv.recv().unwrap();
+        assert!(now.elapsed() >= Duration::from_millis(20));
+        assert!(!timer.stop());
+
+        // reset after expired, the stale value is dropped
+        crate::coroutine::sleep(Duration::from_millis(10));
+        assert!(!timer.reset(Duration::from_millis(10)));
+        assert!(timer.recv.try_recv().is_err());
+        assert!(timer.reset(Duration::from_millis(10)));
+        timer.recv.recv().unwrap();
+        crate::coroutine::sleep(Duration::from_millis(20));
+        assert!(timer.recv.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_timer_stop() {
+        let timer = Timer::new(Duration::from_millis(10));
+        assert!(timer.stop());
+        assert!(!timer.stop());
+        crate::coroutine::sleep(Duration::from_millis(30));
+        assert!(timer.recv.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_after_func() {
+        let cnt = Arc::new(AtomicUsize::new(0));
+        let c = cnt.clone();
+        let timer = after_func(Duration::from_millis(10), move || {
+            c.fetch_add(1, Ordering::SeqCst);
+        });
+        crate::coroutine::sleep(Duration::from_millis(50));
+        assert_eq!(cnt.load(Ordering::SeqCst), 1);
+        assert!(timer.recv.try_recv().is_err());
+
+        // run again
+        assert!(!timer.reset(Duration::from_millis(10)));
+        crate::coroutine::sleep(Duration::from_millis(50));
+        assert_eq!(cnt.load(Ordering::SeqCst), 2);
+
+        timer.reset(Duration::from_millis(10));
+        assert!(timer.stop());
+        crate::coroutine::sleep(Duration::from_millis(50));
+        assert_eq!(cnt.load(Ordering::SeqCst), 2);
+    }
+}
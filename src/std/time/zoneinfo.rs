@@ -0,0 +1,834 @@
+//! time zones from the IANA database, like go's `time.Location`
+//!
+//! the zones are loaded from the system zoneinfo files in the TZif format,
+//! the transitions after the last one in a file are computed by the POSIX
+//! TZ string in its footer. there is an embedded table of the current rules
+//! of the common zones for the systems without the database.
+
+use crate::std::errors::Result;
+use crate::std::lazy::sync::Lazy;
+use crate::std::time::format::{days_in, is_leap, DAYS_BEFORE};
+use crate::std::time::time::GLOBAL_OFFSET;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use time::{Date, OffsetDateTime};
+
+// the start of the time covered by the first transition
+const ALPHA: i64 = i64::MIN;
+
+const SECONDS_PER_MINUTE: i64 = 60;
+const SECONDS_PER_HOUR: i64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: i64 = 24 * SECONDS_PER_HOUR;
+
+// the directories searched for the zoneinfo files, after $ZONEINFO
+const ZONE_SOURCES: &[&str] = &[
+    "/usr/share/zoneinfo/",
+    "/usr/share/lib/zoneinfo/",
+    "/usr/lib/locale/TZ/",
+    "/etc/zoneinfo/",
+];
+
+// the current rules of the common zones in the POSIX TZ format, used when
+// there is no zoneinfo database, the historical transitions are not kept
+const EMBEDDED_ZONES: &[(&str, &str)] = &[
+    ("Africa/Cairo", "EET-2EEST,M4.5.5/0,M10.5.4/24"),
+    ("Africa/Johannesburg", "SAST-2"),
+    ("Africa/Lagos", "WAT-1"),
+    ("Africa/Nairobi", "EAT-3"),
+    ("America/Anchorage", "AKST9AKDT,M3.2.0,M11.1.0"),
+    ("America/Argentina/Buenos_Aires", "<-03>3"),
+    ("America/Bogota", "<-05>5"),
+    ("America/Chicago", "CST6CDT,M3.2.0,M11.1.0"),
+    ("America/Denver", "MST7MDT,M3.2.0,M11.1.0"),
+    ("America/Halifax", "AST4ADT,M3.2.0,M11.1.0"),
+    ("America/Lima", "<-05>5"),
+    ("America/Los_Angeles", "PST8PDT,M3.2.0,M11.1.0"),
+    ("America/Mexico_City", "CST6"),
+    ("America/New_York", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Phoenix", "MST7"),
+    ("America/Santiago", "<-04>4<-03>,M9.1.6/24,M4.1.6/24"),
+    ("America/Sao_Paulo", "<-03>3"),
+    ("America/Toronto", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Vancouver", "PST8PDT,M3.2.0,M11.1.0"),
+    ("Asia/Bangkok", "<+07>-7"),
+    ("Asia/Dhaka", "<+06>-6"),
+    ("Asia/Dubai", "<+04>-4"),
+    ("Asia/Hong_Kong", "HKT-8"),
+    ("Asia/Jakarta", "WIB-7"),
+    ("Asia/Karachi", "PKT-5"),
+    ("Asia/Kolkata", "IST-5:30"),
+    ("Asia/Seoul", "KST-9"),
+    ("Asia/Shanghai", "CST-8"),
+    ("Asia/Singapore", "<+08>-8"),
+    ("Asia/Taipei", "CST-8"),
+    ("Asia/Tokyo", "JST-9"),
+    ("Australia/Adelaide", "ACST-9:30ACDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Brisbane", "AEST-10"),
+    ("Australia/Melbourne", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Perth", "AWST-8"),
+    ("Australia/Sydney", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Etc/GMT", "GMT0"),
+    ("Etc/UTC", "UTC0"),
+    ("Europe/Amsterdam", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Athens", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Brussels", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Bucharest", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Dublin", "IST-1GMT0,M10.5.0,M3.5.0/1"),
+    ("Europe/Helsinki", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Istanbul", "<+03>-3"),
+    ("Europe/Kyiv", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Lisbon", "WET0WEST,M3.5.0/1,M10.5.0"),
+    ("Europe/London", "GMT0BST,M3.5.0/1,M10.5.0"),
+    ("Europe/Madrid", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Moscow", "MSK-3"),
+    ("Europe/Paris", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Prague", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Rome", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Stockholm", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Vienna", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Warsaw", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Zurich", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Pacific/Auckland", "NZST-12NZDT,M9.5.0,M4.1.0/3"),
+    ("Pacific/Honolulu", "HST10"),
+    ("UTC", "UTC0"),
+];
+
+static UTC: Lazy<Location> = Lazy::new(|| Location::fixed("UTC", 0));
+
+static LOCAL: Lazy<Location> = Lazy::new(init_local);
+
+// a zone, such as CET or CEST
+#[derive(Debug, Clone)]
+struct Zone {
+    name: String,
+    // seconds east of UTC
+    offset: i32,
+    is_dst: bool,
+}
+
+// a transition to the zone
+#[derive(Debug, Clone, Copy)]
+struct ZoneTrans {
+    // the unix time of the transition
+    when: i64,
+    index: usize,
+}
+
+#[derive(Debug)]
+struct Inner {
+    name: String,
+    zones: Vec<Zone>,
+    // never empty, sorted by the time
+    tx: Vec<ZoneTrans>,
+    // the POSIX TZ string for the time after the last transition
+    extend: String,
+}
+
+/// A Location maps time instants to the zone in use at that time, like
+/// go's `time.Location`
+///
+/// the locations are cheap to clone, a loaded one is shared.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mco::std::time::{load_location, Time, RFC3339};
+///
+/// let berlin = load_location("Europe/Berlin").unwrap();
+/// let t = Time::parse(RFC3339, "2022-07-01T12:00:00Z").unwrap();
+/// assert_eq!(t.in_location(&berlin).format("15:04 MST"), "14:00 CEST");
+/// ```
+#[derive(Clone)]
+pub struct Location {
+    inner: Arc<Inner>,
+}
+
+impl Location {
+    /// the UTC location
+    pub fn utc() -> Location {
+        UTC.clone()
+    }
+
+    /// the system's local location
+    ///
+    /// it's loaded once from `$TZ` or `/etc/localtime`, a fixed zone of the
+    /// current local offset is used if it can't be loaded.
+    pub fn local() -> Location {
+        LOCAL.clone()
+    }
+
+    /// a location that always uses the zone name and the offset in seconds
+    /// east of UTC, like go's `time.FixedZone`
+    pub fn fixed(name: &str, offset: i32) -> Location {
+        Location {
+            inner: Arc::new(Inner {
+                name: name.to_string(),
+                zones: vec![Zone {
+                    name: name.to_string(),
+                    offset,
+                    is_dst: false,
+                }],
+                tx: vec![ZoneTrans {
+                    when: ALPHA,
+                    index: 0,
+                }],
+                extend: String::new(),
+            }),
+        }
+    }
+
+    /// the name of the location, such as "Europe/Berlin"
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// return the zone name and its offset in seconds east of UTC in use at
+    /// the unix time
+    pub fn lookup(&self, sec: i64) -> (&str, i32) {
+        let l = &*self.inner;
+        let tx = &l.tx;
+        if sec < tx[0].when {
+            let zone = &l.zones[l.lookup_first_zone()];
+            return (&zone.name, zone.offset);
+        }
+        // binary search for the last transition not after the time
+        let lo = tx.partition_point(|t| t.when <= sec) - 1;
+        let zone = &l.zones[tx[lo].index];
+        // at the end of the transitions, try the extend string
+        if lo == tx.len() - 1 && !l.extend.is_empty() {
+            if let Some(zone) = tzset(&l.extend, sec) {
+                return (zone.name, zone.offset);
+            }
+        }
+        (&zone.name, zone.offset)
+    }
+
+    /// return the offset of the zone with the name in use around the unix
+    /// time, for parsing the zone abbreviations
+    pub(crate) fn lookup_name(&self, name: &str, sec: i64) -> Option<i32> {
+        // first try the zone in effect at the time
+        for zone in &self.inner.zones {
+            if zone.name == name {
+                let (n, offset) = self.lookup(sec - zone.offset as i64);
+                if n == zone.name {
+                    return Some(offset);
+                }
+            }
+        }
+        // otherwise fall back to an ordinary name match
+        self.inner
+            .zones
+            .iter()
+            .find(|z| z.name == name)
+            .map(|z| z.offset)
+            .or_else(|| {
+                let (n, offset) = self.lookup(sec);
+                if n == name {
+                    Some(offset)
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+impl Inner {
+    // the zone used before the first transition
+    fn lookup_first_zone(&self) -> usize {
+        // the first zone is unused by the transitions, it's the one
+        if !self.tx.iter().any(|t| t.index == 0) {
+            return 0;
+        }
+        // the first standard zone before the first transition to dst
+        let first = self.tx[0].index;
+        if self.zones[first].is_dst {
+            if let Some(zi) = (0..first).rev().find(|&zi| !self.zones[zi].is_dst) {
+                return zi;
+            }
+        }
+        // the first standard zone
+        self.zones.iter().position(|z| !z.is_dst).unwrap_or(0)
+    }
+}
+
+impl PartialEq for Location {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner) || self.inner.name == other.inner.name
+    }
+}
+
+impl fmt::Debug for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Location").field(&self.inner.name).finish()
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.inner.name)
+    }
+}
+
+/// return the location with the name, like go's `time.LoadLocation`
+///
+/// "" and "UTC" return UTC, "Local" returns the local location. otherwise
+/// the name is an IANA zone such as "America/New_York", it's looked up in
+/// `$ZONEINFO` and the system zoneinfo directories, then in the embedded
+/// table of the common zones.
+pub fn load_location(name: &str) -> Result<Location> {
+    match name {
+        "" | "UTC" => return Ok(Location::utc()),
+        "Local" => return Ok(Location::local()),
+        _ => {}
+    }
+    if name.split(['/', '\\']).any(|p| p == "..") || name.starts_with(['/', '\\']) {
+        return Err(err!("time: invalid location name {:?}", name));
+    }
+    let mut first_err = None;
+    let dirs = std::env::var("ZONEINFO").ok().into_iter();
+    for dir in dirs.chain(ZONE_SOURCES.iter().map(|s| s.to_string())) {
+        match std::fs::read(Path::new(&dir).join(name)) {
+            Ok(data) => match load_location_from_tzdata(name, &data) {
+                Ok(loc) => return Ok(loc),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                first_err.get_or_insert(err!("time: {}: {}", name, e));
+            }
+        }
+    }
+    if let Some((_, rule)) = EMBEDDED_ZONES.iter().find(|(n, _)| *n == name) {
+        return location_from_tz(name, rule);
+    }
+    Err(first_err.unwrap_or_else(|| err!("unknown time zone {}", name)))
+}
+
+// a location of a POSIX TZ string only
+fn location_from_tz(name: &str, tz: &str) -> Result<Location> {
+    let std = tzset_name(tz).and_then(|(n, s)| tzset_offset(s).map(|(offset, _)| (n, offset)));
+    let (std_name, std_offset) = match std {
+        Some(std) if tzset(tz, 0).is_some() => std,
+        _ => return Err(err!("time: invalid TZ string {:?}", tz)),
+    };
+    Ok(Location {
+        inner: Arc::new(Inner {
+            name: name.to_string(),
+            zones: vec![Zone {
+                name: std_name.to_string(),
+                offset: -std_offset as i32,
+                is_dst: false,
+            }],
+            tx: vec![ZoneTrans {
+                when: ALPHA,
+                index: 0,
+            }],
+            extend: tz.to_string(),
+        }),
+    })
+}
+
+// a reader of the big endian data
+struct DataIO<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> DataIO<'a> {
+    fn read(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (p, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(p)
+    }
+
+    fn big4(&mut self) -> Option<u32> {
+        let p = self.read(4)?;
+        Some(u32::from_be_bytes([p[0], p[1], p[2], p[3]]))
+    }
+
+    fn big8(&mut self) -> Option<u64> {
+        let hi = self.big4()? as u64;
+        let lo = self.big4()? as u64;
+        Some(hi << 32 | lo)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.read(1).map(|p| p[0])
+    }
+}
+
+/// return the location with the name from the TZif data, such as the
+/// content of a zoneinfo file, like go's `time.LoadLocationFromTZData`
+pub fn load_location_from_tzdata(name: &str, data: &[u8]) -> Result<Location> {
+    parse_tzdata(name, data).ok_or_else(|| err!("time: malformed time zone information"))
+}
+
+fn parse_tzdata(name: &str, data: &[u8]) -> Option<Location> {
+    let mut d = DataIO { data };
+    // 4-byte magic "TZif"
+    if d.read(4)? != b"TZif" {
+        return None;
+    }
+    // 1-byte version, then 15 bytes of padding
+    let version = match d.read(16)?[0] {
+        0 => 1,
+        b'2' => 2,
+        b'3' => 3,
+        _ => return None,
+    };
+    // six big-endian 32-bit integers: the number of UTC/local indicators,
+    // standard/wall indicators, leap seconds, transition times, local time
+    // zones and characters of the zone abbreviations
+    const NUTC_LOCAL: usize = 0;
+    const NSTD_WALL: usize = 1;
+    const NLEAP: usize = 2;
+    const NTIME: usize = 3;
+    const NZONE: usize = 4;
+    const NCHAR: usize = 5;
+    let mut n = [0usize; 6];
+    for x in n.iter_mut() {
+        *x = d.big4()? as usize;
+    }
+    // version 2 or 3 data is written in the 32-bit format first, then in
+    // the 64-bit format, skip the first one for the broader range of dates
+    let mut size = 4;
+    if version > 1 {
+        let skip = n[NTIME] * 4
+            + n[NTIME]
+            + n[NZONE] * 6
+            + n[NCHAR]
+            + n[NLEAP] * 8
+            + n[NSTD_WALL]
+            + n[NUTC_LOCAL];
+        // and the version 2 header
+        d.read(skip + 4 + 16)?;
+        for x in n.iter_mut() {
+            *x = d.big4()? as usize;
+        }
+        size = 8;
+    }
+    let mut txtimes = DataIO {
+        data: d.read(n[NTIME] * size)?,
+    };
+    let txzones = d.read(n[NTIME])?;
+    let mut zonedata = DataIO {
+        data: d.read(n[NZONE] * 6)?,
+    };
+    let abbrev = d.read(n[NCHAR])?;
+    // the leap seconds, standard/wall and UTC/local indicators are unused
+    d.read(n[NLEAP] * (size + 4))?;
+    d.read(n[NSTD_WALL])?;
+    d.read(n[NUTC_LOCAL])?;
+    let rest = d.data;
+    let extend = if rest.len() > 2 && rest[0] == b'\n' && rest[rest.len() - 1] == b'\n' {
+        String::from_utf8_lossy(&rest[1..rest.len() - 1]).into_owned()
+    } else {
+        String::new()
+    };
+
+    // utcoff[4] isdst[1] nameindex[1]
+    if n[NZONE] == 0 {
+        return None;
+    }
+    let mut zones = Vec::with_capacity(n[NZONE]);
+    for _ in 0..n[NZONE] {
+        let offset = zonedata.big4()? as i32;
+        let is_dst = zonedata.byte()? != 0;
+        let b = zonedata.byte()? as usize;
+        if b >= abbrev.len() {
+            return None;
+        }
+        let name = &abbrev[b..];
+        let end = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        zones.push(Zone {
+            name: String::from_utf8_lossy(&name[..end]).into_owned(),
+            offset,
+            is_dst,
+        });
+    }
+    let mut tx = Vec::with_capacity(n[NTIME]);
+    for &index in txzones {
+        let when = if size == 4 {
+            txtimes.big4()? as i32 as i64
+        } else {
+            txtimes.big8()? as i64
+        };
+        if index as usize >= zones.len() {
+            return None;
+        }
+        tx.push(ZoneTrans {
+            when,
+            index: index as usize,
+        });
+    }
+    if tx.is_empty() {
+        // a fake transition to cover all the time, for the fixed zones
+        // like "Etc/GMT0"
+        tx.push(ZoneTrans {
+            when: ALPHA,
+            index: 0,
+        });
+    }
+    Some(Location {
+        inner: Arc::new(Inner {
+            name: name.to_string(),
+            zones,
+            tx,
+            extend,
+        }),
+    })
+}
+
+fn init_local() -> Location {
+    // consult $TZ to find the time zone to use.
+    // no $TZ means use the system default /etc/localtime.
+    // $TZ="" means use UTC.
+    // $TZ="foo" or $TZ=":foo" if foo is an absolute path, then the file
+    // pointed by foo will be used, otherwise it's loaded by the name
+    let loaded = match std::env::var("TZ") {
+        Ok(tz) if tz.is_empty() => Some(Location::fixed("UTC", 0)),
+        Ok(tz) => {
+            let tz = tz.strip_prefix(':').unwrap_or(&tz);
+            if tz.starts_with('/') {
+                std::fs::read(tz)
+                    .ok()
+                    .and_then(|data| parse_tzdata("Local", &data))
+            } else {
+                load_location(tz)
+                    .ok()
+                    .or_else(|| location_from_tz(tz, tz).ok())
+            }
+        }
+        Err(_) => std::fs::read("/etc/localtime")
+            .ok()
+            .and_then(|data| parse_tzdata("Local", &data)),
+    };
+    match loaded {
+        Some(loc) => Location {
+            inner: Arc::new(Inner {
+                name: "Local".to_string(),
+                zones: loc.inner.zones.clone(),
+                tx: loc.inner.tx.clone(),
+                extend: loc.inner.extend.clone(),
+            }),
+        },
+        // no zoneinfo, such as on windows
+        None => Location::fixed("Local", GLOBAL_OFFSET.whole_seconds()),
+    }
+}
+
+// the zone in use by a POSIX TZ string at a time
+struct TzZone<'a> {
+    name: &'a str,
+    offset: i32,
+}
+
+#[derive(Clone, Copy)]
+enum RuleKind {
+    // "Jn", the julian day, 1 to 365, February 29 is never counted
+    Julian,
+    // "n", the zero based day of year, 0 to 365
+    Doy,
+    // "Mm.w.d", the day d of the week w of the month m
+    MonthWeekDay,
+}
+
+#[derive(Clone, Copy)]
+struct Rule {
+    kind: RuleKind,
+    day: i64,
+    week: i64,
+    mon: i64,
+    // the transition time in seconds of the local time
+    time: i64,
+}
+
+// return the zone in use at the unix time by the POSIX TZ string, such as
+// "CET-1CEST,M3.5.0,M10.5.0/3"
+fn tzset(s: &str, sec: i64) -> Option<TzZone<'_>> {
+    let (std_name, s) = tzset_name(s)?;
+    // the numbers in the TZ string are added to the local time to get
+    // UTC, but the offsets here are added to UTC, so they are negated
+    let (std_offset, s) = tzset_offset(s)?;
+    let std_offset = -std_offset;
+    if s.is_empty() || s.starts_with(',') {
+        // no daylight saving time
+        return Some(TzZone {
+            name: std_name,
+            offset: std_offset as i32,
+        });
+    }
+    let (dst_name, mut s) = tzset_name(s)?;
+    let dst_offset = if s.is_empty() || s.starts_with(',') {
+        std_offset + SECONDS_PER_HOUR
+    } else {
+        let (offset, rest) = tzset_offset(s)?;
+        s = rest;
+        -offset
+    };
+    // the default DST rules per tzcode
+    let s = if s.is_empty() { ",M3.2.0,M11.1.0" } else { s };
+    // the TZ definition does not mention ';' here but tzcode accepts it
+    let s = s.strip_prefix(',').or_else(|| s.strip_prefix(';'))?;
+    let (start_rule, s) = tzset_rule(s)?;
+    let s = s.strip_prefix(',')?;
+    let (end_rule, s) = tzset_rule(s)?;
+    if !s.is_empty() {
+        return None;
+    }
+
+    let t = OffsetDateTime::from_unix_timestamp(sec).ok()?;
+    let year = t.year() as i64;
+    // the start of the year in seconds since the unix epoch
+    let abs = Date::from_calendar_date(t.year(), time::Month::January, 1)
+        .ok()?
+        .midnight()
+        .assume_utc()
+        .unix_timestamp();
+    let ysec = sec - abs;
+    let mut start = tzrule_time(year, start_rule, std_offset);
+    let mut end = tzrule_time(year, end_rule, dst_offset);
+    let (mut outside, mut inside) = ((std_name, std_offset), (dst_name, dst_offset));
+    // the dst ends before it starts in the southern hemisphere, then the
+    // time between the two rules is standard time
+    if end < start {
+        std::mem::swap(&mut start, &mut end);
+        std::mem::swap(&mut outside, &mut inside);
+    }
+    let (name, offset) = if ysec < start || ysec >= end {
+        outside
+    } else {
+        inside
+    };
+    Some(TzZone {
+        name,
+        offset: offset as i32,
+    })
+}
+
+// the name of a zone, at least 3 letters or quoted by "<>"
+fn tzset_name(s: &str) -> Option<(&str, &str)> {
+    if let Some(s) = s.strip_prefix('<') {
+        let i = s.find('>')?;
+        return Some((&s[..i], &s[i + 1..]));
+    }
+    let i = s
+        .find(|c: char| c.is_ascii_digit() || c == ',' || c == '-' || c == '+')
+        .unwrap_or(s.len());
+    if i < 3 {
+        return None;
+    }
+    Some((&s[..i], &s[i..]))
+}
+
+// an offset like "[+-]hh[:mm[:ss]]" in seconds
+fn tzset_offset(s: &str) -> Option<(i64, &str)> {
+    let (neg, s) = match s.as_bytes().first() {
+        Some(b'+') => (false, &s[1..]),
+        Some(b'-') => (true, &s[1..]),
+        _ => (false, s),
+    };
+    // the tzdata code permits values up to 24 * 7 here, although POSIX
+    // does not
+    let (hours, mut s) = tzset_num(s, 0, 24 * 7)?;
+    let mut off = hours * SECONDS_PER_HOUR;
+    if let Some(rest) = s.strip_prefix(':') {
+        let (mins, rest) = tzset_num(rest, 0, 59)?;
+        off += mins * SECONDS_PER_MINUTE;
+        s = rest;
+        if let Some(rest) = s.strip_prefix(':') {
+            let (secs, rest) = tzset_num(rest, 0, 59)?;
+            off += secs;
+            s = rest;
+        }
+    }
+    Some((if neg { -off } else { off }, s))
+}
+
+// a rule like "Jn", "n" or "Mm.w.d", with an optional "/time"
+fn tzset_rule(s: &str) -> Option<(Rule, &str)> {
+    let mut r = Rule {
+        kind: RuleKind::Doy,
+        day: 0,
+        week: 0,
+        mon: 0,
+        time: 2 * SECONDS_PER_HOUR,
+    };
+    let mut s = if let Some(s) = s.strip_prefix('J') {
+        let (day, s) = tzset_num(s, 1, 365)?;
+        r.kind = RuleKind::Julian;
+        r.day = day;
+        s
+    } else if let Some(s) = s.strip_prefix('M') {
+        let (mon, s) = tzset_num(s, 1, 12)?;
+        let (week, s) = tzset_num(s.strip_prefix('.')?, 1, 5)?;
+        let (day, s) = tzset_num(s.strip_prefix('.')?, 0, 6)?;
+        r.kind = RuleKind::MonthWeekDay;
+        r.mon = mon;
+        r.week = week;
+        r.day = day;
+        s
+    } else {
+        let (day, s) = tzset_num(s, 0, 365)?;
+        r.day = day;
+        s
+    };
+    // 2am is the default
+    if let Some(rest) = s.strip_prefix('/') {
+        let (time, rest) = tzset_offset(rest)?;
+        r.time = time;
+        s = rest;
+    }
+    Some((r, s))
+}
+
+fn tzset_num(s: &str, min: i64, max: i64) -> Option<(i64, &str)> {
+    let i = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if i == 0 {
+        return None;
+    }
+    let num = s[..i].parse::<i64>().ok()?;
+    if num < min || num > max {
+        return None;
+    }
+    Some((num, &s[i..]))
+}
+
+// the seconds since the start of the year in UTC when the rule takes
+// effect, the offset is the one in use before it
+fn tzrule_time(year: i64, r: Rule, off: i64) -> i64 {
+    let s = match r.kind {
+        RuleKind::Julian => {
+            let mut s = (r.day - 1) * SECONDS_PER_DAY;
+            if is_leap(year) && r.day >= 60 {
+                s += SECONDS_PER_DAY;
+            }
+            s
+        }
+        RuleKind::Doy => r.day * SECONDS_PER_DAY,
+        RuleKind::MonthWeekDay => {
+            // Zeller's Congruence
+            let m1 = (r.mon + 9) % 12 + 1;
+            let yy0 = if r.mon <= 2 { year - 1 } else { year };
+            let yy1 = yy0 / 100;
+            let yy2 = yy0 % 100;
+            let dow = ((26 * m1 - 2) / 10 + 1 + yy2 + yy2 / 4 + yy1 / 4 - 2 * yy1).rem_euclid(7);
+            // dow is the day of week of the first day of the month, get the
+            // day of month of the first "day" in it
+            let mut d = (r.day - dow).rem_euclid(7);
+            for _ in 1..r.week {
+                if d + 7 >= days_in(r.mon, year) {
+                    break;
+                }
+                d += 7;
+            }
+            d += DAYS_BEFORE[r.mon as usize - 1];
+            if is_leap(year) && r.mon > 2 {
+                d += 1;
+            }
+            d * SECONDS_PER_DAY
+        }
+    };
+    s + r.time - off
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    // the unix time of the date time in UTC
+    fn unix(y: i32, m: u8, d: u8, h: u8) -> i64 {
+        Date::from_calendar_date(y, time::Month::try_from(m).unwrap(), d)
+            .unwrap()
+            .with_hms(h, 0, 0)
+            .unwrap()
+            .assume_utc()
+            .unix_timestamp()
+    }
+
+    #[test]
+    fn test_tzset() {
+        let berlin = location_from_tz("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(berlin.lookup(unix(2022, 1, 1, 0)), ("CET", 3600));
+        assert_eq!(berlin.lookup(unix(2022, 7, 1, 0)), ("CEST", 7200));
+        // the transitions are at 1:00 UTC on the last Sundays of March and
+        // October in 2022, March 27 and October 30
+        assert_eq!(berlin.lookup(unix(2022, 3, 27, 1) - 1), ("CET", 3600));
+        assert_eq!(berlin.lookup(unix(2022, 3, 27, 1)), ("CEST", 7200));
+        assert_eq!(berlin.lookup(unix(2022, 10, 30, 1) - 1), ("CEST", 7200));
+        assert_eq!(berlin.lookup(unix(2022, 10, 30, 1)), ("CET", 3600));
+
+        // the southern hemisphere
+        let sydney = location_from_tz("Australia/Sydney", "AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.lookup(unix(2022, 1, 1, 0)), ("AEDT", 39600));
+        assert_eq!(sydney.lookup(unix(2022, 7, 1, 0)), ("AEST", 36000));
+
+        let kolkata = location_from_tz("Asia/Kolkata", "IST-5:30").unwrap();
+        assert_eq!(kolkata.lookup(0), ("IST", 19800));
+        let bogota = location_from_tz("America/Bogota", "<-05>5").unwrap();
+        assert_eq!(bogota.lookup(0), ("-05", -18000));
+        assert!(location_from_tz("X", "X1").is_err());
+    }
+
+    #[test]
+    fn test_tzdata() {
+        // a TZif v2 file with one transition from LMT to CET in 1893, and
+        // the footer for the following time
+        let mut data = Vec::new();
+        let header = |data: &mut Vec<u8>, ntime: u32| {
+            data.extend_from_slice(b"TZif2");
+            data.extend_from_slice(&[0; 15]);
+            for n in [0, 0, 0, ntime, 2, 8] {
+                data.extend_from_slice(&n.to_be_bytes());
+            }
+        };
+        let zones = |data: &mut Vec<u8>| {
+            data.extend_from_slice(&3208i32.to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(&3600i32.to_be_bytes());
+            data.extend_from_slice(&[0, 4]);
+            data.extend_from_slice(b"LMT\0CET\0");
+        };
+        header(&mut data, 1);
+        data.extend_from_slice(&(-2422054408i64 as i32).to_be_bytes());
+        data.push(1);
+        zones(&mut data);
+        header(&mut data, 1);
+        data.extend_from_slice(&(-2422054408i64).to_be_bytes());
+        data.push(1);
+        zones(&mut data);
+        data.extend_from_slice(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+
+        let loc = load_location_from_tzdata("Europe/Berlin", &data).unwrap();
+        assert_eq!(loc.name(), "Europe/Berlin");
+        assert_eq!(loc.lookup(-2422054409), ("LMT", 3208));
+        assert_eq!(loc.lookup(0), ("CET", 3600));
+        assert_eq!(loc.lookup(unix(2022, 7, 1, 0)), ("CEST", 7200));
+        assert_eq!(loc.lookup_name("CEST", unix(2022, 7, 1, 0)), Some(7200));
+
+        assert!(load_location_from_tzdata("bad", &data[..30]).is_err());
+        assert!(load_location_from_tzdata("bad", b"TZif").is_err());
+    }
+
+    #[test]
+    fn test_load_location() {
+        assert_eq!(load_location("").unwrap(), Location::utc());
+        assert_eq!(load_location("Local").unwrap().name(), "Local");
+        assert!(load_location("../etc/passwd").is_err());
+        assert!(load_location("/etc/localtime").is_err());
+        assert!(load_location("Not/A_Zone").is_err());
+
+        // from the system database or the embedded table
+        let ny = load_location("America/New_York").unwrap();
+        assert_eq!(ny.name(), "America/New_York");
+        assert_eq!(ny.lookup(unix(2022, 1, 1, 0)), ("EST", -18000));
+        assert_eq!(ny.lookup(unix(2022, 7, 1, 0)), ("EDT", -14400));
+    }
+}
@@ -1,14 +1,17 @@
 use std::cmp;
 use std::collections::{BinaryHeap, HashMap};
 use std::mem;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use crate::config::TimerKind;
 use crate::std::queue::mpsc_list_v1::Entry;
 use crate::std::queue::mpsc_list_v1::Queue as TimeoutQueue;
 use crate::std::queue::seg_queue::SegQueue as mpsc;
+use crate::std::time::clock;
+use crate::timer_wheel::{TimerWheel, WheelHandle};
 use crossbeam::atomic::AtomicCell;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
@@ -19,7 +22,7 @@ const NANOS_PER_SEC: u64 = 1_000_000_000;
 const HASH_CAP: usize = 1024;
 
 #[inline]
-fn dur_to_ns(dur: Duration) -> u64 {
+pub fn dur_to_ns(dur: Duration) -> u64 {
     // Note that a duration is a (u64, u32) (seconds, nanoseconds) pair
     dur.as_secs()
         .saturating_mul(NANOS_PER_SEC)
@@ -42,8 +45,8 @@ pub static START_TIME: Lazy<Instant> = Lazy::new(|| Instant::now());
 // get the current wall clock in ns
 #[inline]
 pub fn now() -> u64 {
-    // we need a Monotonic Clock here
-    START_TIME.elapsed().as_nanos() as u64
+    // we need a Monotonic Clock here, it's virtual if a mock is installed
+    dur_to_ns(clock::elapsed())
 }
 
 // timeout event data
@@ -122,6 +125,9 @@ pub struct TimeOutList<T> {
     interval_map: RwLock<HashMap<u64, IntervalList<T>>>,
     // a priority queue, each element is the head of a mpsc queue
     timer_bh: Mutex<BinaryHeap<IntervalEntry<T>>>,
+    // the number of pending timeout events, only the removals done
+    // through `TimerThread::del_timer` are counted
+    len: AtomicUsize,
 }
 
 impl<T> TimeOutList<T> {
@@ -129,9 +135,20 @@ impl<T> TimeOutList<T> {
         TimeOutList {
             interval_map: RwLock::new(HashMap::with_capacity(HASH_CAP)),
             timer_bh: Mutex::new(BinaryHeap::new()),
+            len: AtomicUsize::new(0),
         }
     }
 
+    // the number of pending timeout events
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    // a timeout event is removed before it expires
+    fn removed(&self) {
+        self.len.fetch_sub(1, Ordering::Relaxed);
+    }
+
     fn install_timer_bh(&self, entry: IntervalEntry<T>) {
         if entry.list.in_use.fetch_add(1, Ordering::AcqRel) == 0 {
             self.timer_bh.lock().push(entry);
@@ -147,6 +164,7 @@ impl<T> TimeOutList<T> {
                                      //println!("add timer = {:?}", time);
 
         let timeout = TimeoutData { time, data };
+        self.len.fetch_add(1, Ordering::Relaxed);
 
         let interval_list = {
             // use the read lock protect
@@ -206,6 +224,10 @@ impl<T> TimeOutList<T> {
     // and call the supplied function with registered data
     // return the time in ns for the next expiration
     pub fn schedule_timer<F: Fn(T)>(&self, now: u64, f: &F) -> Option<u64> {
+        let f = &|data: T| {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+            f(data)
+        };
         loop {
             // first peek the BH to see if there is any timeout event
             let mut entry = {
@@ -261,26 +283,95 @@ impl<T> TimeOutList<T> {
     }
 }
 
+// the handle of a timer in the timer thread
+pub enum TimerHandle<T> {
+    List(TimeoutHandle<T>),
+    Wheel(WheelHandle),
+}
+
+impl<T> TimerHandle<T> {
+    // the handle of the wheel is not removed when the timer expires, the
+    // cancellation of an expired timer is ignored
+    #[inline]
+    pub fn is_link(&self) -> bool {
+        match self {
+            TimerHandle::List(h) => h.is_link(),
+            TimerHandle::Wheel(_) => true,
+        }
+    }
+
+    // the list entry is stored as it is, the wheel handle is boxed and
+    // tagged with the lowest bit of the pointer
+    #[inline]
+    pub fn into_ptr(self) -> *mut Self {
+        match self {
+            TimerHandle::List(h) => h.into_ptr() as *mut Self,
+            TimerHandle::Wheel(h) => (Box::into_raw(Box::new(h)) as usize | 1) as *mut Self,
+        }
+    }
+
+    #[inline]
+    pub unsafe fn from_ptr(ptr: *mut Self) -> Self {
+        let addr = ptr as usize;
+        if addr & 1 == 0 {
+            TimerHandle::List(TimeoutHandle::from_ptr(ptr as *mut _))
+        } else {
+            TimerHandle::Wheel(*Box::from_raw((addr & !1) as *mut WheelHandle))
+        }
+    }
+}
+
+// the timers of the timer thread
+enum Timers<T> {
+    List(TimeOutList<T>),
+    Wheel(TimerWheel<T>),
+}
+
 pub struct TimerThread<T> {
-    timer_list: TimeOutList<T>,
+    timers: Timers<T>,
     // collect the remove request
     remove_list: mpsc<TimeoutHandle<T>>,
     // the timer thread wakeup handler
     wakeup: AtomicCell<Option<thread::Thread>>,
+    // set when the timer thread is asked to exit
+    stopped: AtomicBool,
+    // the time of the last scheduling, the timers expired before it are fired
+    scheduled: AtomicU64,
 }
 
 impl<T> TimerThread<T> {
     pub fn new() -> Self {
+        Self::with_kind(TimerKind::List, 1)
+    }
+
+    // create the timer thread with the timer implementation, the wheel has
+    // one shard for each worker
+    pub fn with_kind(kind: TimerKind, workers: usize) -> Self {
+        let timers = match kind {
+            TimerKind::List => Timers::List(TimeOutList::new()),
+            TimerKind::Wheel => Timers::Wheel(TimerWheel::new(workers)),
+        };
         TimerThread {
-            timer_list: TimeOutList::new(),
+            timers,
             remove_list: mpsc::new(),
             wakeup: AtomicCell::new(None),
+            stopped: AtomicBool::new(false),
+            scheduled: AtomicU64::new(0),
         }
     }
 
-    pub fn add_timer(&self, dur: Duration, data: T) -> TimeoutHandle<T> {
-        let (h, is_recal) = self.timer_list.add_timer(dur, data);
-        // wake up the timer thread if it's a new queue
+    pub fn add_timer(&self, dur: Duration, data: T) -> TimerHandle<T> {
+        let (h, is_recal) = match &self.timers {
+            Timers::List(list) => {
+                let (h, is_recal) = list.add_timer(dur, data);
+                (TimerHandle::List(h), is_recal)
+            }
+            Timers::Wheel(wheel) => {
+                let (h, is_recal) = wheel.add_timer(dur, data);
+                (TimerHandle::Wheel(h), is_recal)
+            }
+        };
+        // wake up the timer thread if it's a new queue or an earlier deadline
         if is_recal {
             if let Some(t) = self.wakeup.take() {
                 t.unpark();
@@ -289,31 +380,102 @@ impl<T> TimerThread<T> {
         h
     }
 
-    pub fn del_timer(&self, handle: TimeoutHandle<T>) {
-        self.remove_list.push(handle);
+    // the number of pending timers
+    pub fn len(&self) -> usize {
+        match &self.timers {
+            Timers::List(list) => list.len(),
+            Timers::Wheel(wheel) => wheel.len(),
+        }
+    }
+
+    pub fn del_timer(&self, handle: TimerHandle<T>) {
+        match (&self.timers, handle) {
+            (_, TimerHandle::List(h)) => {
+                self.remove_list.push(h);
+                if let Some(t) = self.wakeup.take() {
+                    t.unpark();
+                }
+            }
+            // the wheel is cancelled in place, the timer thread would find
+            // nothing when it wakes up for the deadline
+            (Timers::Wheel(wheel), TimerHandle::Wheel(h)) => {
+                wheel.cancel(&h);
+            }
+            (Timers::List(_), TimerHandle::Wheel(_)) => {
+                unreachable!("the wheel handle is deleted from the list")
+            }
+        }
+    }
+
+    // wake up the timer thread to check the expired timers again
+    pub fn wakeup(&self) {
+        if let Some(t) = self.wakeup.take() {
+            t.unpark();
+        }
+    }
+
+    // wait until the timers expired at `now` are fired, it's used when the
+    // clock is moved manually. it must not be called on the timer thread
+    pub fn fire_expired(&self, now: u64) {
+        while self.scheduled.load(Ordering::Acquire) < now && !self.stopped.load(Ordering::Acquire)
+        {
+            self.wakeup();
+            thread::yield_now();
+        }
+    }
+
+    // ask the timer thread to exit, the pending timers are not fired
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
         if let Some(t) = self.wakeup.take() {
             t.unpark();
         }
     }
 
-    // the timer thread function
+    // take out all the pending timers, it's called after the timer thread exits
+    pub fn clear<F: Fn(T)>(&self, f: &F) {
+        while let Some(h) = self.remove_list.pop() {
+            if let (Some(_), Timers::List(list)) = (h.remove(), &self.timers) {
+                list.removed();
+            }
+        }
+        match &self.timers {
+            Timers::List(list) => list.schedule_timer(u64::MAX, f),
+            Timers::Wheel(wheel) => wheel.schedule_timer(u64::MAX, f),
+        };
+    }
+
+    // the timer thread function, return after `stop` is called
     pub fn run<F: Fn(T)>(&self, f: &F) {
         let current_thread = thread::current();
         loop {
             while let Some(h) = self.remove_list.pop() {
-                h.remove();
+                if let (Some(_), Timers::List(list)) = (h.remove(), &self.timers) {
+                    list.removed();
+                }
             }
             // we must register the thread handle first
             // or there will be no signal to wakeup the timer thread
             self.wakeup.swap(Some(current_thread.clone()));
 
+            // re-check after the registration so that we never miss a stop
+            if self.stopped.load(Ordering::Acquire) {
+                break;
+            }
+
             if !self.remove_list.is_empty() {
                 if let Some(t) = self.wakeup.take() {
                     t.unpark();
                 }
             }
 
-            match self.timer_list.schedule_timer(now(), f) {
+            let now = now();
+            let next = match &self.timers {
+                Timers::List(list) => list.schedule_timer(now, f),
+                Timers::Wheel(wheel) => wheel.schedule_timer(now, f),
+            };
+            self.scheduled.store(now, Ordering::Release);
+            match next {
                 Some(time) => thread::park_timeout(ns_to_dur(time)),
                 None => thread::park(),
             }
@@ -351,4 +513,41 @@ mod tests {
 
         thread::sleep(Duration::from_millis(1500));
     }
+
+    #[test]
+    fn test_timer_wheel_thread() {
+        use std::sync::mpsc::channel;
+
+        let timer = Arc::new(TimerThread::<usize>::with_kind(TimerKind::Wheel, 4));
+        let (tx, rx) = channel();
+        let t = timer.clone();
+        let tx = Mutex::new(tx);
+        let h = thread::spawn(move || t.run(&|data| tx.lock().send(data).unwrap()));
+        let start = Instant::now();
+        timer.add_timer(Duration::from_millis(200), 20);
+        let h1 = timer.add_timer(Duration::from_millis(100), 10);
+        timer.add_timer(Duration::from_millis(50), 5);
+        assert!(h1.is_link());
+        timer.del_timer(h1);
+        assert_eq!(timer.len(), 2);
+
+        assert_eq!(rx.recv().unwrap(), 5);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(rx.recv().unwrap(), 20);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(timer.len(), 0);
+        timer.stop();
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn test_timer_thread_stop() {
+        let timer = Arc::new(TimerThread::<usize>::new());
+        let t = timer.clone();
+        let h = thread::spawn(move || t.run(&|_| unreachable!()));
+        timer.add_timer(Duration::from_secs(100), 10);
+        thread::sleep(Duration::from_millis(10));
+        timer.stop();
+        h.join().unwrap();
+    }
 }
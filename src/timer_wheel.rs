@@ -0,0 +1,509 @@
+//! hierarchical timing wheel for the timer thread
+//!
+//! the timers are hashed into 6 levels of 64 slots, a slot of level `n`
+//! covers `64^n` ticks of 1ms. a timer is put into the lowest level that
+//! the deadline differs from the current tick, and it's moved down to a
+//! lower level when the slot of it is reached, until it's due.
+//!
+//! the entries are linked in the slots by the indexes of a slab, so both
+//! inserting and cancelling a timer are O(1), and the handle is still valid
+//! after the entry is moved down. there is one wheel for each worker,
+//! guarded by its own lock, so the workers don't contend with each other.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::scheduler::worker_id;
+use crate::timeout_list::{dur_to_ns, now};
+
+// the resolution of the wheel, a timer never expires earlier than its
+// deadline, but it may be late by up to one tick
+const TICK_NS: u64 = 1_000_000;
+const LEVEL_BITS: usize = 6;
+const LEVEL_MULT: usize = 1 << LEVEL_BITS;
+const NUM_LEVELS: usize = 6;
+// the deadlines further than this are kept in the top level, they are
+// moved around it until they are near enough
+const MAX_DURATION: u64 = 1 << (LEVEL_BITS * NUM_LEVELS);
+const NIL: usize = usize::MAX;
+
+// convert the time in ns to the tick that is not earlier than it
+#[inline]
+fn ns_to_tick(ns: u64) -> u64 {
+    ns.div_ceil(TICK_NS)
+}
+
+// the level of the deadline, the lowest one that it differs from elapsed
+#[inline]
+fn level_for(elapsed: u64, when: u64) -> usize {
+    const SLOT_MASK: u64 = (1 << LEVEL_BITS) - 1;
+    let mut masked = (elapsed ^ when) | SLOT_MASK;
+    if masked >= MAX_DURATION {
+        masked = MAX_DURATION - 1;
+    }
+    let significant = 63 - masked.leading_zeros() as usize;
+    significant / LEVEL_BITS
+}
+
+#[inline]
+fn slot_for(when: u64, level: usize) -> usize {
+    ((when >> (level * LEVEL_BITS)) % LEVEL_MULT as u64) as usize
+}
+
+// where the entry is linked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Loc {
+    Free,
+    // already expired when inserted, fired on the next poll
+    Pending,
+    Slot(usize, usize),
+}
+
+struct Node<T> {
+    // the deadline in ticks
+    when: u64,
+    data: Option<T>,
+    prev: usize,
+    next: usize,
+    // increased when the node is freed, so a stale handle is ignored
+    gen: u32,
+    loc: Loc,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct List {
+    head: usize,
+    tail: usize,
+}
+
+impl List {
+    const EMPTY: List = List {
+        head: NIL,
+        tail: NIL,
+    };
+
+    fn is_empty(&self) -> bool {
+        self.head == NIL
+    }
+}
+
+struct Level {
+    // a bit is set if the slot is not empty
+    occupied: u64,
+    slots: [List; LEVEL_MULT],
+}
+
+// the slot to process next and the tick it begins
+struct Expiration {
+    level: usize,
+    slot: usize,
+    deadline: u64,
+}
+
+struct Wheel<T> {
+    // all the timers before this tick are fired
+    elapsed: u64,
+    levels: Vec<Level>,
+    pending: List,
+    nodes: Vec<Node<T>>,
+    // the head of the free nodes, linked by `next`
+    free: usize,
+}
+
+impl<T> Wheel<T> {
+    fn new() -> Self {
+        Wheel {
+            elapsed: 0,
+            levels: (0..NUM_LEVELS)
+                .map(|_| Level {
+                    occupied: 0,
+                    slots: [List::EMPTY; LEVEL_MULT],
+                })
+                .collect(),
+            pending: List::EMPTY,
+            nodes: Vec::new(),
+            free: NIL,
+        }
+    }
+
+    fn list(&mut self, loc: Loc) -> &mut List {
+        match loc {
+            Loc::Pending => &mut self.pending,
+            Loc::Slot(level, slot) => &mut self.levels[level].slots[slot],
+            Loc::Free => unreachable!("the timer entry is not linked"),
+        }
+    }
+
+    // link the node to the slot of its deadline
+    fn link(&mut self, key: usize) {
+        let when = self.nodes[key].when;
+        let loc = if when <= self.elapsed {
+            Loc::Pending
+        } else {
+            let level = level_for(self.elapsed, when);
+            let slot = slot_for(when, level);
+            self.levels[level].occupied |= 1 << slot;
+            Loc::Slot(level, slot)
+        };
+        let list = *self.list(loc);
+        let node = &mut self.nodes[key];
+        node.loc = loc;
+        node.prev = list.tail;
+        node.next = NIL;
+        if list.tail == NIL {
+            *self.list(loc) = List {
+                head: key,
+                tail: key,
+            };
+        } else {
+            self.nodes[list.tail].next = key;
+            self.list(loc).tail = key;
+        }
+    }
+
+    fn unlink(&mut self, key: usize) {
+        let (loc, prev, next) = {
+            let node = &self.nodes[key];
+            (node.loc, node.prev, node.next)
+        };
+        match prev {
+            NIL => self.list(loc).head = next,
+            p => self.nodes[p].next = next,
+        }
+        match next {
+            NIL => self.list(loc).tail = prev,
+            n => self.nodes[n].prev = prev,
+        }
+        if let Loc::Slot(level, slot) = loc {
+            if self.levels[level].slots[slot].is_empty() {
+                self.levels[level].occupied &= !(1 << slot);
+            }
+        }
+        self.nodes[key].loc = Loc::Free;
+    }
+
+    // take the list out of its place, the nodes are still marked as linked
+    fn take_list(&mut self, loc: Loc) -> usize {
+        let list = std::mem::replace(self.list(loc), List::EMPTY);
+        if let Loc::Slot(level, slot) = loc {
+            self.levels[level].occupied &= !(1 << slot);
+        }
+        list.head
+    }
+
+    fn insert(&mut self, when: u64, data: T) -> (usize, u32) {
+        let key = match self.free {
+            NIL => {
+                self.nodes.push(Node {
+                    when,
+                    data: Some(data),
+                    prev: NIL,
+                    next: NIL,
+                    gen: 0,
+                    loc: Loc::Free,
+                });
+                self.nodes.len() - 1
+            }
+            key => {
+                let node = &mut self.nodes[key];
+                self.free = node.next;
+                node.when = when;
+                node.data = Some(data);
+                key
+            }
+        };
+        self.link(key);
+        (key, self.nodes[key].gen)
+    }
+
+    fn release(&mut self, key: usize) -> Option<T> {
+        let node = &mut self.nodes[key];
+        node.loc = Loc::Free;
+        node.gen = node.gen.wrapping_add(1);
+        node.next = self.free;
+        self.free = key;
+        node.data.take()
+    }
+
+    fn cancel(&mut self, key: usize, gen: u32) -> Option<T> {
+        match self.nodes.get(key) {
+            Some(node) if node.gen == gen && node.loc != Loc::Free => {}
+            // already fired or cancelled
+            _ => return None,
+        }
+        self.unlink(key);
+        self.release(key)
+    }
+
+    // the next slot that has timers in the level
+    fn level_expiration(&self, level: usize) -> Option<Expiration> {
+        let occupied = self.levels[level].occupied;
+        if occupied == 0 {
+            return None;
+        }
+        let slot_range = 1u64 << (level * LEVEL_BITS);
+        let level_range = slot_range << LEVEL_BITS;
+        let now_slot = self.elapsed / slot_range;
+        let zeros = occupied.rotate_right(now_slot as u32).trailing_zeros() as u64;
+        let slot = ((zeros + now_slot) % LEVEL_MULT as u64) as usize;
+        let mut deadline = (self.elapsed & !(level_range - 1)) + slot as u64 * slot_range;
+        if deadline <= self.elapsed {
+            // only the far timers in the top level could wrap around
+            deadline += level_range;
+        }
+        Some(Expiration {
+            level,
+            slot,
+            deadline,
+        })
+    }
+
+    // the lower levels always expire earlier
+    fn next_expiration(&self) -> Option<Expiration> {
+        (0..NUM_LEVELS).find_map(|level| self.level_expiration(level))
+    }
+
+    // the tick of the next timer to fire
+    fn next_deadline(&self) -> Option<u64> {
+        if !self.pending.is_empty() {
+            return Some(self.elapsed);
+        }
+        self.next_expiration().map(|e| e.deadline)
+    }
+
+    // move the wheel to the tick, the expired timers are collected
+    fn poll(&mut self, now: u64, expired: &mut Vec<T>) {
+        let mut key = self.take_list(Loc::Pending);
+        while key != NIL {
+            let next = self.nodes[key].next;
+            expired.extend(self.release(key));
+            key = next;
+        }
+
+        while let Some(exp) = self.next_expiration() {
+            if exp.deadline > now {
+                break;
+            }
+            self.elapsed = exp.deadline;
+            let mut key = self.take_list(Loc::Slot(exp.level, exp.slot));
+            while key != NIL {
+                let next = self.nodes[key].next;
+                if self.nodes[key].when <= self.elapsed {
+                    expired.extend(self.release(key));
+                } else {
+                    // move down to a lower level
+                    self.link(key);
+                }
+                key = next;
+            }
+        }
+        if now > self.elapsed {
+            self.elapsed = now;
+        }
+    }
+}
+
+/// The handle of a timer in the wheel
+#[derive(Debug)]
+pub struct WheelHandle {
+    shard: usize,
+    key: usize,
+    gen: u32,
+}
+
+/// the timing wheels of the timer thread, one for each worker
+pub struct TimerWheel<T> {
+    shards: Vec<Mutex<Wheel<T>>>,
+    len: AtomicUsize,
+    // the tick the timer thread would wake up at, it's `u64::MAX` while the
+    // wheels are polled or when there is no timer, so that a new timer always
+    // wakes up the timer thread then
+    next_wake: AtomicU64,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new(shards: usize) -> Self {
+        TimerWheel {
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(Wheel::new()))
+                .collect(),
+            len: AtomicUsize::new(0),
+            next_wake: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    // the number of pending timers
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    // the wheel of the current worker, the other threads are spread over all
+    // of the wheels
+    fn shard(&self) -> usize {
+        thread_local! { static SHARD: Cell<usize> = const { Cell::new(NIL) }; }
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let id = match worker_id() {
+            id if id != !1 => id,
+            _ => SHARD.with(|s| {
+                if s.get() == NIL {
+                    s.set(NEXT.fetch_add(1, Ordering::Relaxed));
+                }
+                s.get()
+            }),
+        };
+        id % self.shards.len()
+    }
+
+    // add a timer that expires after the duration, return true if the timer
+    // thread needs to be woken up to recalculate the next expiration
+    pub fn add_timer(&self, dur: Duration, data: T) -> (WheelHandle, bool) {
+        let when = ns_to_tick(now().saturating_add(dur_to_ns(dur)));
+        let shard = self.shard();
+        let (key, gen) = self.shards[shard].lock().insert(when, data);
+        self.len.fetch_add(1, Ordering::Relaxed);
+        let recal = when < self.next_wake.load(Ordering::SeqCst);
+        (WheelHandle { shard, key, gen }, recal)
+    }
+
+    // cancel the timer, return the data if it's not expired yet
+    pub fn cancel(&self, handle: &WheelHandle) -> Option<T> {
+        let data = self.shards[handle.shard]
+            .lock()
+            .cancel(handle.key, handle.gen);
+        if data.is_some() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+        data
+    }
+
+    // fire all the timers expired at `now` in ns with the supplied function,
+    // return the time in ns to the next expiration
+    pub fn schedule_timer<F: Fn(T)>(&self, now: u64, f: &F) -> Option<u64> {
+        self.next_wake.store(u64::MAX, Ordering::SeqCst);
+        let tick = now / TICK_NS;
+        let mut expired = Vec::new();
+        for shard in &self.shards {
+            shard.lock().poll(tick, &mut expired);
+            // the lock is released, the function may add a new timer
+            self.len.fetch_sub(expired.len(), Ordering::Relaxed);
+            expired.drain(..).for_each(f);
+        }
+        // the timers added while polling would wake up the timer thread
+        let next = self
+            .shards
+            .iter()
+            .filter_map(|s| s.lock().next_deadline())
+            .min()?;
+        self.next_wake.store(next, Ordering::SeqCst);
+        Some(next.saturating_mul(TICK_NS).saturating_sub(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(w: &mut Wheel<u64>, now: u64) -> Vec<u64> {
+        let mut v = Vec::new();
+        w.poll(now, &mut v);
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn test_wheel() {
+        let mut w = Wheel::new();
+        let whens = [1, 2, 63, 64, 65, 100, 4095, 4096, 5000, 300_000, 20_000_000];
+        for &when in &whens {
+            w.insert(when, when);
+        }
+        assert_eq!(w.next_deadline(), Some(1));
+        assert_eq!(poll(&mut w, 0), Vec::<u64>::new());
+        assert_eq!(poll(&mut w, 2), vec![1, 2]);
+        assert_eq!(poll(&mut w, 64), vec![63, 64]);
+        assert_eq!(w.next_deadline(), Some(65));
+        assert_eq!(poll(&mut w, 4999), vec![65, 100, 4095, 4096]);
+        assert_eq!(poll(&mut w, 5000), vec![5000]);
+        // the far ones are moved down level by level
+        assert_eq!(poll(&mut w, 299_999), Vec::<u64>::new());
+        assert_eq!(poll(&mut w, 300_000), vec![300_000]);
+        assert_eq!(poll(&mut w, u64::MAX / 2), vec![20_000_000]);
+        assert_eq!(w.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_wheel_cancel() {
+        let mut w = Wheel::new();
+        let (a, ga) = w.insert(10, 1);
+        let (b, gb) = w.insert(10, 2);
+        let (c, gc) = w.insert(10_000, 3);
+        assert_eq!(w.cancel(a, ga), Some(1));
+        assert_eq!(w.cancel(a, ga), None);
+        // the freed node is reused, the old handle doesn't match
+        let (d, gd) = w.insert(20, 4);
+        assert_eq!(d, a);
+        assert_eq!(w.cancel(a, ga), None);
+        assert_eq!(poll(&mut w, 10), vec![2]);
+        assert_eq!(w.cancel(b, gb), None);
+        // the handle is still valid after it's moved down
+        assert_eq!(poll(&mut w, 9_000), vec![4]);
+        assert_eq!(w.cancel(c, gc), Some(3));
+        assert_eq!(w.cancel(d, gd), None);
+        assert_eq!(w.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_wheel_pending() {
+        let mut w = Wheel::new();
+        assert_eq!(poll(&mut w, 100), Vec::<u64>::new());
+        // the deadline is already passed
+        w.insert(50, 50);
+        w.insert(100, 100);
+        w.insert(101, 101);
+        assert_eq!(w.next_deadline(), Some(100));
+        assert_eq!(poll(&mut w, 100), vec![50, 100]);
+        assert_eq!(poll(&mut w, 101), vec![101]);
+    }
+
+    #[test]
+    fn test_wheel_far() {
+        let mut w = Wheel::new();
+        let far = MAX_DURATION * 3 + 12345;
+        w.insert(far, 1);
+        let mut now = 0;
+        // it's in the top level until it's near enough
+        while let Some(d) = w.next_deadline() {
+            assert!(d <= far);
+            now = d;
+            if !poll(&mut w, d).is_empty() {
+                break;
+            }
+        }
+        assert_eq!(now, far);
+    }
+
+    #[test]
+    fn test_timer_wheel() {
+        let wheel = TimerWheel::new(2);
+        let (h1, recal) = wheel.add_timer(Duration::from_millis(5), 1);
+        assert!(recal);
+        let (_h2, _) = wheel.add_timer(Duration::from_secs(10), 2);
+        assert_eq!(wheel.len(), 2);
+        assert_eq!(wheel.cancel(&h1), Some(1));
+        assert_eq!(wheel.len(), 1);
+
+        let fired = std::cell::RefCell::new(Vec::new());
+        let f = |v| fired.borrow_mut().push(v);
+        let next = wheel.schedule_timer(now(), &f).unwrap();
+        assert!(next <= dur_to_ns(Duration::from_secs(10)) + TICK_NS);
+        // the earlier timer needs to wake up the timer thread
+        assert!(wheel.add_timer(Duration::from_secs(1), 3).1);
+        assert!(!wheel.add_timer(Duration::from_secs(60), 4).1);
+        wheel.schedule_timer(now() + dur_to_ns(Duration::from_secs(20)), &f);
+        assert_eq!(*fired.borrow(), vec![3, 2]);
+        assert_eq!(wheel.len(), 1);
+    }
+}
@@ -0,0 +1,243 @@
+//! blocking coroutine detection
+//!
+//! a coroutine that calls a blocking api stalls all the coroutines queued
+//! on its worker. when the watchdog is enabled each worker records the
+//! coroutine it's running, and a watchdog thread samples the workers. a
+//! worker that stays on the same run longer than the threshold is reported
+//! with the coroutine and a backtrace of the worker thread, once per run.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::scheduler::Scheduler;
+
+// the last sampled state of a worker
+#[derive(Clone, Copy)]
+struct Sample {
+    switches: u64,
+    since: Instant,
+    reported: bool,
+}
+
+/// the watchdog thread body, it exits when the scheduler is shutdown
+pub(crate) fn run(s: &'static Scheduler, threshold: Duration) {
+    let interval = (threshold / 4).max(Duration::from_millis(1));
+    let now = Instant::now();
+    let mut samples = vec![
+        Sample {
+            switches: 0,
+            since: now,
+            reported: false,
+        };
+        s.workers()
+    ];
+    while !s.is_shutdown() {
+        thread::sleep(interval);
+        let now = Instant::now();
+        for (id, sample) in samples.iter_mut().enumerate() {
+            let counters = s.worker_counters(id);
+            let switches = counters.switches.get();
+            let running = counters.running();
+            if switches != sample.switches || running == 0 {
+                *sample = Sample {
+                    switches,
+                    since: now,
+                    reported: false,
+                };
+                continue;
+            }
+            let elapsed = now - sample.since;
+            if sample.reported || elapsed < threshold {
+                continue;
+            }
+            sample.reported = true;
+            counters.blocked.inc();
+            report(s, id, running, elapsed);
+        }
+    }
+}
+
+fn report(s: &Scheduler, id: usize, co_id: usize, elapsed: Duration) {
+    let co = match s.coroutine_info(co_id) {
+        Some(info) => info.to_string(),
+        None => format!("coroutine {}", co_id),
+    };
+    let trace = s
+        .worker_thread(id)
+        .and_then(backtrace::capture)
+        .map(|frames| frames.join("\n"))
+        .unwrap_or_else(|| "    <backtrace unavailable>".to_owned());
+    warn!(
+        "worker {} is blocked for {:?} by {}\nworker backtrace:\n{}",
+        id, elapsed, co, trace
+    );
+}
+
+/// the handle of the current thread that a backtrace can be taken from
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub(crate) fn current_thread() -> usize {
+    unsafe { libc::pthread_self() as usize }
+}
+
+/// the handle of the current thread that a backtrace can be taken from
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+pub(crate) fn current_thread() -> usize {
+    0
+}
+
+// the worker is interrupted by a signal whose handler records the frames,
+// then the watchdog thread resolves the symbols. this is only supported
+// with glibc that provides `backtrace`
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+mod backtrace {
+    use std::cell::UnsafeCell;
+    use std::ffi::CStr;
+    use std::mem;
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Mutex, Once};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use libc::{c_int, c_void};
+
+    const MAX_FRAMES: usize = 64;
+    // ignored by default, so a late signal would never kill the process
+    const SIGNAL: c_int = libc::SIGURG;
+
+    struct Frames(UnsafeCell<[*mut c_void; MAX_FRAMES]>);
+
+    // only written by the signal handler that takes the pending request, and
+    // only read after the handler echoes the sequence number of the request
+    unsafe impl Sync for Frames {}
+
+    static FRAMES: Frames = Frames(UnsafeCell::new([ptr::null_mut(); MAX_FRAMES]));
+    static LEN: AtomicUsize = AtomicUsize::new(0);
+    // the sequence number of the pending request, zero if there is none
+    static REQUEST: AtomicUsize = AtomicUsize::new(0);
+    // the thread of the pending request
+    static TARGET: AtomicUsize = AtomicUsize::new(0);
+    // the sequence number of the request whose frames are recorded
+    static DONE: AtomicUsize = AtomicUsize::new(0);
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    // the handler runs in signal context on the interrupted worker, so
+    // `backtrace` unwinds the stack it's running on, that is the stack of the
+    // blocking coroutine rather than the one of the worker thread. a late
+    // signal of a withdrawn request, or one sent to another thread, finds no
+    // request to take and leaves the frames alone
+    extern "C" fn on_signal(_: c_int) {
+        let seq = REQUEST.load(Ordering::Acquire);
+        if seq == 0 || TARGET.load(Ordering::Relaxed) != unsafe { libc::pthread_self() } as usize {
+            return;
+        }
+        if REQUEST
+            .compare_exchange(seq, 0, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        let n = unsafe { libc::backtrace(FRAMES.0.get() as *mut *mut c_void, MAX_FRAMES as c_int) };
+        LEN.store(n.max(0) as usize, Ordering::Relaxed);
+        DONE.store(seq, Ordering::Release);
+    }
+
+    // withdraw the request if no handler has taken it, return false if a
+    // handler is recording the frames, it must be waited for
+    fn withdraw(seq: usize) -> bool {
+        REQUEST
+            .compare_exchange(seq, 0, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    // install the handler unless the application owns the signal
+    fn install() -> bool {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| unsafe {
+            // the first call loads the unwinder, which is not signal safe
+            let mut buf = [ptr::null_mut(); 1];
+            libc::backtrace(buf.as_mut_ptr(), 1);
+
+            let mut old: libc::sigaction = mem::zeroed();
+            if libc::sigaction(SIGNAL, ptr::null(), &mut old) != 0 {
+                return;
+            }
+            if old.sa_sigaction != libc::SIG_DFL && old.sa_sigaction != libc::SIG_IGN {
+                warn!(
+                    "signal {} is in use, the worker backtrace is disabled",
+                    SIGNAL
+                );
+                return;
+            }
+            let mut act: libc::sigaction = mem::zeroed();
+            act.sa_sigaction = on_signal as extern "C" fn(c_int) as usize;
+            act.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut act.sa_mask);
+            if libc::sigaction(SIGNAL, &act, ptr::null_mut()) == 0 {
+                INSTALLED.store(true, Ordering::Release);
+            }
+        });
+        INSTALLED.load(Ordering::Acquire)
+    }
+
+    /// take a backtrace of the thread, one line for each frame
+    pub fn capture(thread: usize) -> Option<Vec<String>> {
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if thread == 0 || !install() {
+            return None;
+        }
+        let seq = match NEXT.fetch_add(1, Ordering::Relaxed).wrapping_add(1) {
+            0 => 1,
+            n => n,
+        };
+        TARGET.store(thread, Ordering::Relaxed);
+        REQUEST.store(seq, Ordering::Release);
+        let sent = unsafe { libc::pthread_kill(thread as libc::pthread_t, SIGNAL) } == 0;
+        if !sent && withdraw(seq) {
+            return None;
+        }
+        let deadline = Instant::now() + Duration::from_millis(100);
+        while DONE.load(Ordering::Acquire) != seq {
+            if Instant::now() >= deadline && withdraw(seq) {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        let len = LEN.load(Ordering::Relaxed).min(MAX_FRAMES);
+        let frames = unsafe { *FRAMES.0.get() };
+        Some(
+            frames[..len]
+                .iter()
+                .enumerate()
+                .map(|(i, &addr)| symbolize(i, addr))
+                .collect(),
+        )
+    }
+
+    fn symbolize(i: usize, addr: *mut c_void) -> String {
+        let mut info: libc::Dl_info = unsafe { mem::zeroed() };
+        if unsafe { libc::dladdr(addr, &mut info) } == 0 || info.dli_fname.is_null() {
+            return format!("    #{:<2} {:p}", i, addr);
+        }
+        // the symbols of the executable are only exported with `-rdynamic`,
+        // the module offset can be resolved by `addr2line`
+        if info.dli_sname.is_null() {
+            let file = unsafe { CStr::from_ptr(info.dli_fname) }.to_string_lossy();
+            let offset = addr as usize - info.dli_fbase as usize;
+            return format!("    #{:<2} {:p} ({}+{:#x})", i, addr, file, offset);
+        }
+        let name = unsafe { CStr::from_ptr(info.dli_sname) }.to_string_lossy();
+        let offset = addr as usize - info.dli_saddr as usize;
+        format!("    #{:<2} {:p} {}+{:#x}", i, addr, name, offset)
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+mod backtrace {
+    /// the worker backtrace is not supported on this platform
+    pub fn capture(_thread: usize) -> Option<Vec<String>> {
+        None
+    }
+}
@@ -1,8 +1,8 @@
 use std::thread;
 
 use crate::coroutine_impl::{current_cancel_data, is_coroutine};
-use crate::coroutine_impl::{CoroutineImpl, EventResult, EventSource, EventSubscriber};
-use crate::scheduler::get_scheduler;
+use crate::dump::WaitReason;
+use crate::coroutine_impl::{co_scheduler, CoroutineImpl, EventResult, EventSource, EventSubscriber};
 use mco_gen::{co_get_yield, co_set_para, co_yield_with};
 
 struct Yield {}
@@ -10,7 +10,11 @@ struct Yield {}
 impl EventSource for Yield {
     fn subscribe(&mut self, co: CoroutineImpl) {
         // just re-push the coroutine to the ready list
-        get_scheduler().schedule(co);
+        co_scheduler(&co).schedule(co);
+    }
+
+    fn wait_reason(&self) -> WaitReason {
+        WaitReason::Yield
     }
 }
 
@@ -1,7 +1,9 @@
 use mco::co;
 use mco::coroutine::sleep;
-use mco::std::sync::channel::channel;
+use mco::std::sync::channel::{channel, SendError, TrySendError};
 use mco::std::sync::WaitGroup;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 #[test]
@@ -35,3 +37,107 @@ fn channel_recv() {
     }
     wait_group.wait();
 }
+
+// ch := make(chan int)
+// go func() { ch <- 1; sent = true }()
+// the send is blocked until the value is received
+#[test]
+fn unbuffered_send_blocks_until_received() {
+    let (tx, rx) = mco::chan!(0);
+    let sent = Arc::new(AtomicBool::new(false));
+    let sent2 = sent.clone();
+    let h = co!(move || {
+        tx.send(1).unwrap();
+        sent2.store(true, Ordering::SeqCst);
+    });
+    sleep(Duration::from_millis(50));
+    assert!(!sent.load(Ordering::SeqCst));
+    assert_eq!(rx.remain(), 1);
+    assert_eq!(rx.recv(), Ok(1));
+    h.join().unwrap();
+    assert!(sent.load(Ordering::SeqCst));
+    assert_eq!(rx.remain(), 0);
+}
+
+// select { case ch <- v: default: } only succeeds with a waiting receiver,
+// and select { case v := <-ch: default: } only with a waiting sender
+#[test]
+fn unbuffered_try_send_try_recv() {
+    let (tx, rx) = mco::chan!(0);
+    assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+    assert!(rx.try_recv().is_err());
+
+    let rx2 = rx.clone();
+    let h = co!(move || rx2.recv());
+    sleep(Duration::from_millis(50));
+    tx.try_send(2).unwrap();
+    assert_eq!(h.join().unwrap(), Ok(2));
+    assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+
+    let tx2 = tx.clone();
+    let h = co!(move || tx2.send(4));
+    sleep(Duration::from_millis(50));
+    assert_eq!(rx.try_recv(), Ok(4));
+    h.join().unwrap().unwrap();
+}
+
+// done := make(chan bool)
+// go func() { work(); done <- true }()
+// <-done
+#[test]
+fn unbuffered_ping_pong() {
+    let (ping_tx, ping_rx) = mco::chan!(0);
+    let (pong_tx, pong_rx) = mco::chan!(0);
+    let h = co!(move || {
+        for v in ping_rx.iter() {
+            pong_tx.send(v + 1).unwrap();
+        }
+    });
+    for i in 0..100 {
+        ping_tx.send(i).unwrap();
+        assert_eq!(pong_rx.recv(), Ok(i + 1));
+    }
+    drop(ping_tx);
+    h.join().unwrap();
+}
+
+// unlike go, a blocked sender gets the value back when the receivers are gone
+#[test]
+fn unbuffered_receiver_dropped() {
+    let (tx, rx) = mco::chan!(0);
+    let h = co!(move || tx.send(1));
+    sleep(Duration::from_millis(50));
+    drop(rx);
+    assert_eq!(h.join().unwrap(), Err(SendError::Disconnected(1)));
+}
+
+#[test]
+fn unbuffered_closed_while_blocked() {
+    let (tx, rx) = mco::chan!(0);
+    let tx2 = tx.clone();
+    let h = co!(move || tx2.send(1));
+    sleep(Duration::from_millis(50));
+    assert!(tx.close());
+    assert_eq!(h.join().unwrap(), Err(SendError::Closed(1)));
+    // the retracted message is not received
+    assert!(rx.recv().is_err());
+}
+
+#[test]
+fn unbuffered_select_send() {
+    let (tx, rx) = mco::chan!(0);
+    let id = mco::select! {
+        send(tx, 1) => unreachable!(),
+        after(Duration::from_millis(10)) => {},
+    };
+    assert_eq!(id, 1);
+    assert!(rx.try_recv().is_err());
+
+    let h = co!(move || rx.recv());
+    let id = mco::select! {
+        send(tx, 2) => {},
+        after(Duration::from_secs(10)) => unreachable!(),
+    };
+    assert_eq!(id, 0);
+    assert_eq!(h.join().unwrap(), Ok(2));
+}
@@ -0,0 +1,143 @@
+use std::io;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mco::coroutine::{self, WaitReason};
+use mco::net::UdpSocket;
+use mco::std::context::{self, ContextError};
+use mco::std::sync::channel::bounded;
+use mco::std::time::{after, reset_clock, set_clock, MockClock, Ticker, Time};
+use mco::Runtime;
+
+// spawn the coroutine and wait until it's parked on its timer
+fn spawn_timer<F, T>(f: F) -> coroutine::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let timers = || Runtime::global().metrics().timer_entries;
+    let before = timers();
+    let h = coroutine::spawn(f);
+    while timers() == before {
+        thread::yield_now();
+    }
+    // the timer of a park is added just before the coroutine is registered
+    thread::sleep(Duration::from_millis(10));
+    h
+}
+
+// wait until a coroutine is parked on an io
+fn wait_io() {
+    let blocked = || {
+        Runtime::global()
+            .coroutines()
+            .iter()
+            .any(|c| c.wait_reason == Some(WaitReason::Io))
+    };
+    while !blocked() {
+        thread::yield_now();
+    }
+    // the io timer is added when the coroutine is registered
+    thread::sleep(Duration::from_millis(10));
+}
+
+// the clock is global to the process, the steps run in one test so that
+// they don't interfere with each other
+#[test]
+fn mock_clock() {
+    let start = Instant::now();
+    let clock = Arc::new(MockClock::new());
+    set_clock(clock.clone());
+
+    // the wall clock follows the mock
+    let now = Time::now_utc();
+    clock.advance(Duration::from_secs(86400));
+    assert_eq!(Time::now_utc().sub_time(&now), time::Duration::days(1));
+
+    // sleep
+    let h = spawn_timer(|| coroutine::sleep(Duration::from_secs(3600)));
+    assert!(!h.is_done());
+    clock.advance(Duration::from_secs(1800));
+    assert!(!h.is_done());
+    clock.advance(Duration::from_secs(1800));
+    h.join().unwrap();
+
+    // park_timeout
+    let h = spawn_timer(|| coroutine::park_timeout(Duration::from_secs(60)));
+    clock.advance(Duration::from_secs(60));
+    h.join().unwrap();
+
+    // the timeout of a channel
+    let (_tx, rx) = bounded::<i32>(1);
+    let h = spawn_timer(move || rx.recv_timeout(Duration::from_secs(10)));
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(h.join().unwrap(), Err(RecvTimeoutError::Timeout));
+
+    // the read timeout of an io, it's in the timer list of the event loop
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let h = coroutine::spawn(move || socket.recv(&mut [0u8; 16]).map_err(|e| e.kind()));
+    wait_io();
+    assert!(!h.is_done());
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(h.join().unwrap(), Err(io::ErrorKind::TimedOut));
+
+    // timer
+    let rx = after(Duration::from_secs(5));
+    clock.advance(Duration::from_secs(4));
+    assert!(rx.try_recv().is_err());
+    clock.advance(Duration::from_secs(1));
+    let t = rx.try_recv().unwrap();
+    assert_eq!(t, Time::now_utc());
+
+    // the ticker keeps one tick for a slow receiver
+    let ticker = Ticker::new(Duration::from_secs(1));
+    clock.advance(Duration::from_secs(3));
+    assert!(ticker.recv.try_recv().is_ok());
+    assert!(ticker.recv.try_recv().is_err());
+    clock.advance(Duration::from_secs(1));
+    assert!(ticker.recv.try_recv().is_ok());
+    ticker.stop();
+
+    // the ticks are at absolute deadlines, the work between them doesn't
+    // delay the next one
+    let start = Time::now();
+    let ticker = Ticker::new(Duration::from_secs(20));
+    for i in 1..=5 {
+        clock.advance(Duration::from_secs(5));
+        assert!(ticker.recv.try_recv().is_err());
+        clock.advance(Duration::from_secs(15));
+        let t = ticker.recv.try_recv().unwrap();
+        assert_eq!(t.sub_time(&start), time::Duration::seconds(20 * i));
+    }
+    ticker.stop();
+
+    // the deadline of a context is on the clock too
+    let (parent, _c) = context::with_timeout(&context::background(), Duration::from_secs(20));
+    let (child, _c) = context::with_timeout(&parent, Duration::from_secs(60));
+    assert_eq!(child.deadline(), parent.deadline());
+    let (canceled, cancel) = context::with_timeout(&parent, Duration::from_secs(10));
+    cancel.cancel();
+    clock.advance(Duration::from_secs(19));
+    assert_eq!(child.err(), None);
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(child.err(), Some(ContextError::DeadlineExceeded));
+    assert_eq!(parent.err(), Some(ContextError::DeadlineExceeded));
+    // the cancelled one never fires
+    assert_eq!(canceled.err(), Some(ContextError::Canceled));
+    let (ctx, _c) = context::with_deadline(&context::background(), parent.deadline().unwrap());
+    assert_eq!(ctx.err(), Some(ContextError::DeadlineExceeded));
+
+    reset_clock();
+    // nothing waited for the real time
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        start.elapsed()
+    );
+    coroutine::sleep(Duration::from_millis(10));
+}
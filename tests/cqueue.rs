@@ -229,3 +229,127 @@ fn cqueue_loop() {
 
     assert_eq!(result, 50);
 }
+
+#[test]
+fn select_send() {
+    let (tx, rx) = chan!(1);
+    tx.send(1).unwrap();
+    co!(move || {
+        coroutine::sleep(Duration::from_millis(50));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+    });
+
+    // blocked until the receiver takes the first one
+    let id = select! {
+        send(tx, 2) => {},
+        after(Duration::from_secs(10)) => unreachable!(),
+    };
+    assert_eq!(id, 0);
+
+    let (tx, rx) = chan!(1);
+    rx.close();
+    let id = select! {
+        _ = coroutine::sleep(Duration::from_secs(10)) => {},
+        r = send(tx, 1) => assert!(r.unwrap_err().is_closed()),
+    };
+    assert_eq!(id, 1);
+}
+
+#[test]
+fn select_after() {
+    let (_tx, rx) = mco::std::sync::channel::channel::<i32>();
+    let id = select! {
+        _ = rx.recv() => unreachable!(),
+        after(Duration::from_millis(10)) => {},
+    };
+    assert_eq!(id, 1);
+    assert_eq!(rx.try_recv().is_err(), true);
+}
+
+#[test]
+fn select_default() {
+    let (tx, rx) = chan!();
+    let id = select! {
+        _ = rx.recv() => unreachable!(),
+        default => {},
+        after(Duration::from_secs(10)) => unreachable!(),
+    };
+    assert_eq!(id, 1);
+
+    tx.send(1).unwrap();
+    let id = select! {
+        default => unreachable!(),
+        v = rx.recv() => assert_eq!(v, Ok(1)),
+    };
+    assert_eq!(id, 1);
+
+    // works in a coroutine too, even when it's the only worker
+    let rt = mco::Runtime::builder().workers(1).build().unwrap();
+    let id = rt
+        .spawn(move || {
+            select! {
+                _ = rx.recv() => unreachable!(),
+                default => {},
+            }
+        })
+        .join()
+        .unwrap();
+    assert_eq!(id, 1);
+}
+
+#[test]
+fn select_send_not_selected() {
+    let (tx1, rx1) = mco::std::sync::channel::channel();
+    tx1.send(7).unwrap();
+
+    // the send arm is ready too, but the recv arm is selected first
+    let (tx2, rx2) = chan!(1);
+    let id = select! {
+        v = rx1.recv() => assert_eq!(v, Ok(7)),
+        send(tx2, 2) => unreachable!(),
+    };
+    assert_eq!(id, 0);
+    assert!(rx2.try_recv().is_err());
+
+    // the send arm is blocked on a full channel when the recv arm is selected
+    tx1.send(8).unwrap();
+    let (tx3, rx3) = chan!(1);
+    tx3.send(1).unwrap();
+    let id = select! {
+        send(tx3, 2) => unreachable!(),
+        v = rx1.recv() => assert_eq!(v, Ok(8)),
+    };
+    assert_eq!(id, 1);
+    assert_eq!(rx3.recv(), Ok(1));
+    coroutine::sleep(Duration::from_millis(10));
+    assert!(rx3.try_recv().is_err());
+}
+
+#[test]
+fn select_oneshot() {
+    use mco::std::sync::oneshot;
+
+    let (tx, rx) = oneshot::channel::<i32>();
+    let id = select! {
+        _ = rx.recv() => unreachable!(),
+        after(Duration::from_millis(10)) => {},
+    };
+    assert_eq!(id, 1);
+
+    // the value is not taken by the canceled arm
+    tx.send(1).unwrap();
+    let id = select! {
+        v = rx.recv() => assert_eq!(v, Ok(1)),
+        after(Duration::from_secs(10)) => unreachable!(),
+    };
+    assert_eq!(id, 0);
+
+    let (tx, rx) = oneshot::channel::<i32>();
+    drop(tx);
+    let id = select! {
+        v = rx.recv() => assert_eq!(v, Err(oneshot::RecvError)),
+        after(Duration::from_secs(10)) => unreachable!(),
+    };
+    assert_eq!(id, 0);
+}
@@ -0,0 +1,338 @@
+use mco::coroutine;
+use mco::Runtime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn runtime_block_on() {
+    let rt = Runtime::builder().workers(2).build().unwrap();
+    assert_eq!(rt.workers(), 2);
+    let v = rt.block_on(|| {
+        coroutine::sleep(Duration::from_millis(10));
+        1 + 1
+    });
+    assert_eq!(v, 2);
+}
+
+#[test]
+fn runtime_isolated() {
+    let rt1 = Runtime::builder().workers(1).build().unwrap();
+    let rt2 = Runtime::builder()
+        .workers(1)
+        .stack_size(0x2000)
+        .build()
+        .unwrap();
+    assert_ne!(rt1, rt2);
+    assert_ne!(rt1, Runtime::global());
+
+    let rt = rt1.block_on(move || {
+        // spawn inside a coroutine would use the same runtime
+        let inner = coroutine::spawn(Runtime::current).join().unwrap();
+        assert_eq!(inner, coroutine::current().runtime());
+        inner
+    });
+    assert_eq!(rt, rt1);
+    assert_eq!(rt2.block_on(Runtime::current), rt2);
+    assert_eq!(rt2.block_on(Runtime::current).stack_size(), 0x2000);
+}
+
+#[test]
+fn runtime_thread_name() {
+    let rt = Runtime::builder()
+        .workers(1)
+        .thread_name("test-rt")
+        .build()
+        .unwrap();
+    let name = rt.block_on(|| std::thread::current().name().map(|n| n.to_string()));
+    assert_eq!(name.as_deref(), Some("test-rt-0"));
+}
+
+#[test]
+fn runtime_builder_spawn() {
+    let rt = Runtime::builder().workers(1).build().unwrap();
+    let h = coroutine::Builder::new()
+        .name("in_rt".to_owned())
+        .runtime(rt.clone())
+        .spawn(|| coroutine::current().runtime());
+    assert_eq!(h.join().unwrap(), rt);
+}
+
+#[test]
+fn runtime_panic() {
+    let rt = Runtime::builder().workers(1).build().unwrap();
+    let r = std::panic::catch_unwind(|| rt.block_on(|| panic!("panic in runtime")));
+    assert!(r.is_err());
+}
+
+#[test]
+fn runtime_shutdown() {
+    let rt = Runtime::builder().workers(2).build().unwrap();
+    let h = rt.spawn(|| coroutine::sleep(Duration::from_millis(10)));
+    let report = rt.shutdown_timeout(Duration::from_secs(5)).unwrap();
+    assert!(h.is_done());
+    assert!(rt.is_shutdown());
+    assert_eq!(report.canceled, 0);
+    assert_eq!(report.alive, 0);
+
+    // spawn after shutdown is rejected
+    assert!(rt.spawn(|| 1).join().is_err());
+    // shutdown twice is an error
+    assert!(rt.shutdown_timeout(Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn runtime_shutdown_cancel() {
+    let rt = Runtime::builder().workers(1).build().unwrap();
+    let h = rt.spawn(|| coroutine::sleep(Duration::from_secs(1000)));
+    let report = rt.shutdown_timeout(Duration::from_millis(50)).unwrap();
+    assert_eq!(report.canceled, 1);
+    assert_eq!(report.alive, 0);
+    assert!(h.join().is_err());
+}
+
+#[test]
+fn runtime_drop() {
+    // dropping the last handle shuts the runtime down and frees it
+    for _ in 0..20 {
+        let rt = Runtime::builder().workers(2).build().unwrap();
+        assert_eq!(rt.block_on(|| 1 + 1), 2);
+    }
+
+    let rt = Runtime::builder().workers(1).build().unwrap();
+    let h = rt.spawn(|| coroutine::park());
+    let rt2 = rt.clone();
+    drop(rt);
+    assert!(!rt2.is_shutdown());
+    assert!(!h.is_done());
+    drop(rt2);
+    // the alive coroutine is cancelled
+    assert!(h.is_done());
+    assert!(h.join().is_err());
+}
+
+#[test]
+fn runtime_shutdown_spawn_race() {
+    let rt = Runtime::builder().workers(2).build().unwrap();
+    let spawners: Vec<_> = (0..4)
+        .map(|_| {
+            let rt = rt.clone();
+            std::thread::spawn(move || {
+                let mut handles = Vec::new();
+                while !rt.is_shutdown() {
+                    handles.push(rt.spawn(|| coroutine::sleep(Duration::from_millis(1))));
+                }
+                handles
+            })
+        })
+        .collect();
+    std::thread::sleep(Duration::from_millis(20));
+    let report = rt.shutdown_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(report.alive, 0);
+    // every spawn is either run to the end or rejected
+    for t in spawners {
+        for h in t.join().unwrap() {
+            assert!(h.is_done());
+        }
+    }
+}
+
+#[test]
+fn runtime_shutdown_in_runtime() {
+    let rt = Runtime::builder().workers(1).build().unwrap();
+    assert!(rt
+        .block_on(|| Runtime::current().shutdown_timeout(Duration::from_secs(1)))
+        .is_err());
+    assert!(!rt.is_shutdown());
+}
+
+#[test]
+fn runtime_yield_fairness() {
+    let rt = Runtime::builder().workers(1).build().unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let flag = done.clone();
+    let spinner = rt.spawn(move || {
+        while !flag.load(Ordering::SeqCst) {
+            coroutine::yield_now();
+        }
+    });
+    // the spinner keeps re-queuing itself on the only worker
+    std::thread::sleep(Duration::from_millis(10));
+    let sibling = rt.spawn(move || done.store(true, Ordering::SeqCst));
+    sibling.join().unwrap();
+    spinner.join().unwrap();
+}
+
+#[test]
+fn runtime_many_parked_coroutines() {
+    use mco::std::sync::SyncFlag;
+
+    // each coroutine owns a dedicated stack, keep them small so that many of
+    // them could be alive at the same time
+    const N: usize = 10_000;
+    let rt = Runtime::builder()
+        .workers(2)
+        .stack_size(0x4000)
+        .build()
+        .unwrap();
+    let flag = Arc::new(SyncFlag::new());
+    let handles: Vec<_> = (0..N)
+        .map(|_| {
+            let flag = flag.clone();
+            rt.spawn(move || flag.wait())
+        })
+        .collect();
+    while rt.metrics().live_coroutines < N {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    flag.fire();
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(rt.metrics().live_coroutines, 0);
+}
+
+#[test]
+fn runtime_io_dropped_in_other_runtime() {
+    let rt1 = Runtime::builder().workers(1).build().unwrap();
+    let rt2 = Runtime::builder().workers(1).build().unwrap();
+    let listener = rt1.block_on(|| mco::net::TcpListener::bind("127.0.0.1:0").unwrap());
+    let addr = listener.local_addr().unwrap();
+    // deregistered from the selector of rt1, not the one of rt2
+    rt2.block_on(move || drop(listener));
+    let listener = rt1.block_on(move || mco::net::TcpListener::bind(addr).unwrap());
+    drop(listener);
+    assert_eq!(rt1.shutdown_timeout(Duration::from_secs(1)).unwrap().alive, 0);
+    assert_eq!(rt2.shutdown_timeout(Duration::from_secs(1)).unwrap().alive, 0);
+}
+
+#[test]
+fn runtime_metrics() {
+    let rt = Runtime::builder().workers(2).build().unwrap();
+    let m = rt.metrics();
+    assert_eq!(m.workers.len(), 2);
+    assert_eq!(m.live_coroutines, 0);
+
+    let (tx, rx) = mco::chan!();
+    let h = rt.spawn(move || {
+        rx.recv().unwrap();
+        coroutine::sleep(Duration::from_millis(10));
+    });
+    // wait the coroutine blocked on the channel
+    while rt.metrics().total_polled() == 0 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(rt.metrics().live_coroutines, 1);
+    tx.send(()).unwrap();
+    h.join().unwrap();
+
+    let m = rt.metrics();
+    assert_eq!(m.live_coroutines, 0);
+    assert_eq!(m.global_queue_depth, 0);
+    assert_eq!(m.timer_entries, 0);
+    // spawned, resumed by the channel and resumed by the timer
+    assert!(m.total_polled() >= 3);
+    assert!(m.workers.iter().map(|w| w.parks).sum::<u64>() > 0);
+}
+
+#[test]
+fn runtime_dump() {
+    use mco::coroutine::{Builder, CoroutineState, WaitReason};
+
+    let rt = Runtime::builder().workers(2).build().unwrap();
+    assert!(rt.coroutines().is_empty());
+
+    let (tx, rx) = mco::chan!();
+    let lock = std::sync::Arc::new(mco::std::sync::Mutex::new(()));
+    let guard = lock.lock().unwrap();
+    let lock2 = lock.clone();
+    let h1 = Builder::new()
+        .name("receiver".to_owned())
+        .runtime(rt.clone())
+        .spawn(move || rx.recv().unwrap());
+    let h2 = rt.spawn(move || drop(lock2.lock().unwrap()));
+    // wait both coroutines get parked
+    while rt.coroutines().iter().any(|co| co.wait_reason.is_none()) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let cos = rt.coroutines();
+    assert_eq!(cos.len(), 2);
+    assert_eq!(cos[0].id, h1.coroutine().id());
+    assert_eq!(cos[0].name.as_deref(), Some("receiver"));
+    assert_eq!(cos[0].state, CoroutineState::Parked);
+    assert_eq!(cos[0].wait_reason, Some(WaitReason::ChanRecv));
+    assert_eq!(cos[0].location.file(), file!());
+    assert_eq!(cos[1].wait_reason, Some(WaitReason::Mutex));
+    assert_eq!(cos[1].location.file(), file!());
+
+    let dump = rt.dump();
+    assert!(dump.contains("\"receiver\" [parked, chan receive]\n    spawned at tests/runtime.rs:"));
+    assert!(dump.contains("[parked, mutex]"));
+    let json = rt.dump_json();
+    assert!(json.contains("\"state\":\"parked\",\"wait_reason\":\"mutex\""));
+
+    tx.send(()).unwrap();
+    drop(guard);
+    h1.join().unwrap();
+    h2.join().unwrap();
+    assert!(rt.dump().is_empty());
+}
+
+#[test]
+fn runtime_channel_backpressure() {
+    let rt = Runtime::builder().workers(2).build().unwrap();
+    let (fast_tx, fast_rx) = mco::std::sync::channel::named("fast", 16);
+    let (slow_tx, slow_rx) = mco::std::sync::channel::named("slow", 1);
+    fast_tx.send(0).unwrap();
+    slow_tx.send(0).unwrap();
+    let h = rt.spawn(move || slow_tx.send(1).unwrap());
+    while slow_rx.metrics().blocked_senders == 0 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let m = rt.metrics();
+    let c = m.most_blocked_channel().unwrap();
+    assert_eq!(c.name.as_deref(), Some("slow"));
+    assert_eq!(c.blocked_senders, 1);
+    assert_eq!(slow_rx.recv(), Ok(0));
+    h.join().unwrap();
+    assert_eq!(fast_rx.recv(), Ok(0));
+}
+
+#[test]
+fn runtime_timer_wheel() {
+    use mco::std::time::Timer;
+    use mco::TimerKind;
+    use std::time::Instant;
+
+    let rt = Runtime::builder()
+        .workers(2)
+        .timer(TimerKind::Wheel)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    let hs: Vec<_> = (1..=100)
+        .map(|i| {
+            rt.spawn(move || {
+                let d = Duration::from_millis(i);
+                let t = Instant::now();
+                coroutine::sleep(d);
+                assert!(t.elapsed() >= d);
+            })
+        })
+        .collect();
+    // the stopped timers never fire
+    let timers = rt.block_on(|| {
+        (0..100)
+            .map(|i| Timer::new(Duration::from_secs(60 + i)))
+            .collect::<Vec<_>>()
+    });
+    for h in hs {
+        h.join().unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(rt.metrics().timer_entries, 100);
+    rt.block_on(move || timers.iter().for_each(|t| assert!(t.stop())));
+    assert_eq!(rt.metrics().timer_entries, 0);
+}